@@ -0,0 +1,71 @@
+//! Compares [`Tilemap::render_const`] against [`Tilemap::render`] on a screen-sized map of 16x16 tiles.
+//!
+//! Run with `cargo bench --bench render_const`.
+
+use simple_tilemap::{Buffer, BufferMut, Color, Tile, Tilemap, Tileset, TilesetOptions};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 200;
+
+struct Surface {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,
+}
+
+impl Buffer<Color> for Surface {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn get(&self, x: u32, y: u32) -> &Color {
+        &self.pixels[(y * self.width + x) as usize]
+    }
+}
+
+impl BufferMut<Color> for Surface {
+    fn get_mut(&mut self, x: u32, y: u32) -> &mut Color {
+        &mut self.pixels[(y * self.width + x) as usize]
+    }
+}
+
+fn time(mut f: impl FnMut()) -> Duration {
+    // warm up
+    f();
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    let data: Vec<u8> = (0..128 * 128)
+        .flat_map(|i| [i as u8, (i >> 8) as u8, 128, 255])
+        .collect();
+    let tileset = Tileset::new(data, 128, 128, TilesetOptions::new(16, 16)).unwrap();
+
+    let mut map: Tilemap<_> = Tilemap::new(40, 23, tileset);
+    for (i, tile) in map.tiles_mut().iter_mut().enumerate() {
+        *tile = Tile::new(i as u32 % 64);
+    }
+
+    let mut surface = Surface {
+        width: 640,
+        height: 360,
+        pixels: vec![Color::default(); 640 * 360],
+    };
+
+    let dynamic = time(|| map.render(black_box(&mut surface), 0, 0));
+    let constant = time(|| map.render_const::<16, 16>(black_box(&mut surface), 0, 0));
+
+    println!("render:               {dynamic:?} per frame");
+    println!("render_const<16,16>: {constant:?} per frame");
+}
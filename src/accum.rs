@@ -0,0 +1,237 @@
+use crate::{multiply, BlendMode, Buffer, BufferMut, Color, Tilemap};
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::{Index, IndexMut};
+use fast_srgb8::{f32x4_to_srgb8, srgb8_to_f32};
+use simple_blit::{blit_with, BlitOptions};
+
+/// Linear premultiplied-alpha color used by [`AccumulationBuffer`].
+pub type LinearColor = [f32; 4];
+
+/// Scratch buffer for compositing several semi-transparent maps before blending them onto a surface.
+///
+/// Maps are composited into it with [`Tilemap::render_accumulated`] using source-over blending
+/// in linear premultiplied-alpha space, and the result is blended onto the surface
+/// with a single [`blend_onto`](Self::blend_onto) call.
+#[derive(Clone, Debug)]
+pub struct AccumulationBuffer {
+    pixels: Vec<LinearColor>,
+    width: u32,
+    height: u32,
+}
+
+impl AccumulationBuffer {
+    /// Construct a new fully transparent buffer.
+    pub fn new(width: u32, height: u32) -> Self {
+        let len = width.checked_mul(height).expect("buffer too large");
+
+        Self {
+            pixels: vec![[0.; 4]; len as usize],
+            width,
+            height,
+        }
+    }
+
+    /// Make the buffer fully transparent again.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.pixels.fill([0.; 4]);
+    }
+
+    /// Composite `color` over the pixel at (x, y).
+    #[inline]
+    pub fn composite(&mut self, x: u32, y: u32, color: Color) {
+        src_over(self.get_mut(x, y), color);
+    }
+
+    /// Blend the buffer onto a surface at pixel offset `(offset_x, offset_y)` using source-over blending.
+    pub fn blend_onto(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+    ) {
+        blit_with(
+            surface,
+            (offset_x, offset_y),
+            self,
+            (0, 0),
+            (self.width, self.height),
+            BlitOptions::None,
+            |dest, src, _| {
+                let mut result = *src;
+                let alpha = dest.a as f32 / 255.;
+                let keep = 1. - src[3];
+
+                result[0] += srgb8_to_f32(dest.r) * alpha * keep;
+                result[1] += srgb8_to_f32(dest.g) * alpha * keep;
+                result[2] += srgb8_to_f32(dest.b) * alpha * keep;
+                result[3] += alpha * keep;
+
+                *dest = to_color(result);
+            },
+        );
+    }
+}
+
+impl<C, M> Tilemap<C, (), M>
+where
+    C: AsRef<[u8]>,
+{
+    /// Composite the map into an accumulation buffer at pixel offset `(offset_x, offset_y)`.
+    ///
+    /// Tiles with the normal [blend mode](crate::Tile::blend) are composited with source-over blending
+    /// so that the maps below show through, the other modes are applied the same as with [`Tilemap::render`].
+    pub fn render_accumulated(
+        &self,
+        buffer: &mut AccumulationBuffer,
+        offset_x: i32,
+        offset_y: i32,
+    ) {
+        self.render_tiles(buffer, offset_x, offset_y, None, |dest, src, tile| {
+            let color = multiply(*src, tile.color);
+
+            match tile.blend {
+                BlendMode::Normal | BlendMode::Alpha => src_over(dest, color),
+                blend => *dest = to_linear(blend.blend(to_color(*dest), color)),
+            }
+        });
+    }
+}
+
+/// Composite a straight-alpha color over a linear premultiplied one.
+#[inline]
+fn src_over(dest: &mut LinearColor, color: Color) {
+    let alpha = color.a as f32 / 255.;
+    let keep = 1. - alpha;
+
+    dest[0] = srgb8_to_f32(color.r) * alpha + dest[0] * keep;
+    dest[1] = srgb8_to_f32(color.g) * alpha + dest[1] * keep;
+    dest[2] = srgb8_to_f32(color.b) * alpha + dest[2] * keep;
+    dest[3] = alpha + dest[3] * keep;
+}
+
+/// Blend `color` over `dest` using source-over blending in linear space.
+#[inline]
+pub(crate) fn blend_over(dest: Color, color: Color) -> Color {
+    let mut result = to_linear(dest);
+    src_over(&mut result, color);
+    to_color(result)
+}
+
+/// Convert a straight-alpha color to a linear premultiplied one.
+#[inline]
+fn to_linear(color: Color) -> LinearColor {
+    let alpha = color.a as f32 / 255.;
+
+    [
+        srgb8_to_f32(color.r) * alpha,
+        srgb8_to_f32(color.g) * alpha,
+        srgb8_to_f32(color.b) * alpha,
+        alpha,
+    ]
+}
+
+/// Convert a linear premultiplied color to a straight-alpha one.
+#[inline]
+fn to_color([r, g, b, a]: LinearColor) -> Color {
+    if a <= 0. {
+        return Color::new(0, 0, 0, 0);
+    }
+
+    let [r, g, b, _] = f32x4_to_srgb8([r / a, g / a, b / a, 0.]);
+
+    Color::new(r, g, b, (a * 255. + 0.5) as u8)
+}
+
+impl Buffer<LinearColor> for AccumulationBuffer {
+    #[inline]
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[inline]
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[inline]
+    fn get(&self, x: u32, y: u32) -> &LinearColor {
+        self.pixels.index((y * self.width + x) as usize)
+    }
+}
+
+impl BufferMut<LinearColor> for AccumulationBuffer {
+    #[inline]
+    fn get_mut(&mut self, x: u32, y: u32) -> &mut LinearColor {
+        self.pixels.index_mut((y * self.width + x) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{tileset_from, Surface};
+    use crate::{BlendMode, Tile, TilesetOptions};
+
+    fn translucent(color: Color) -> Tilemap<Vec<u8>> {
+        let white = [Color::new(255, 255, 255, 255); 4];
+        let tileset = tileset_from(&white, 2, TilesetOptions::new(2, 2));
+
+        let mut map = Tilemap::new(1, 1, tileset);
+        map.set_tile(0, 0, Tile::new(0).with_color(color));
+        map
+    }
+
+    #[test]
+    fn accumulated_translucency() {
+        let green = Color::new(0, 255, 0, 255);
+        let red = translucent(Color::new(255, 0, 0, 128));
+        let blue = translucent(Color::new(0, 0, 255, 128));
+
+        // drawn one after another, the second tile replaces everything under it
+        let mut naive = Surface::filled(2, 2, green);
+        red.render(&mut naive, 0, 0);
+        blue.render(&mut naive, 0, 0);
+        assert_eq!(naive.pixels[0].g, 0);
+
+        let mut buffer = AccumulationBuffer::new(2, 2);
+        red.render_accumulated(&mut buffer, 0, 0);
+        blue.render_accumulated(&mut buffer, 0, 0);
+        let mut accumulated = Surface::filled(2, 2, green);
+        buffer.blend_onto(&mut accumulated, 0, 0);
+
+        let pixel = accumulated.pixels[0];
+        assert_eq!(pixel.a, 255);
+        assert!(pixel.r > 0 && pixel.g > 0 && pixel.b > 0);
+
+        // the result matches blending each tile over the surface in turn
+        let mut blended = Surface::filled(2, 2, green);
+        for mut map in [red, blue] {
+            map.tiles_mut()[0].blend = BlendMode::Alpha;
+            map.render(&mut blended, 0, 0);
+        }
+        assert_eq!(accumulated.pixels, blended.pixels);
+    }
+
+    #[test]
+    fn accumulated_blend_modes() {
+        let red = translucent(Color::new(255, 0, 0, 255));
+        let mut blue = translucent(Color::new(0, 0, 255, 255));
+        blue.tiles_mut()[0].blend = BlendMode::Additive;
+
+        let mut buffer = AccumulationBuffer::new(2, 2);
+        red.render_accumulated(&mut buffer, 0, 0);
+        blue.render_accumulated(&mut buffer, 0, 0);
+        let mut accumulated = Surface::new(2, 2);
+        buffer.blend_onto(&mut accumulated, 0, 0);
+
+        let mut rendered = Surface::new(2, 2);
+        red.render(&mut rendered, 0, 0);
+        blue.render(&mut rendered, 0, 0);
+
+        assert_eq!(accumulated.pixels, rendered.pixels);
+        assert_eq!(accumulated.pixels[0], Color::new(255, 0, 255, 255));
+    }
+}
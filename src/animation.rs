@@ -0,0 +1,204 @@
+use crate::{draw_tile_pixel, BufferMut, Color, Tile, TileId, Tilemap, Tileset};
+
+use alloc::vec::Vec;
+use core::time::Duration;
+
+/// Sequence of frames a tile cycles through, see [`Tileset::set_animation`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TileAnimation {
+    /// Frames of the animation: the tile to draw and for how long.
+    pub frames: Vec<(TileId, Duration)>,
+}
+
+impl TileAnimation {
+    /// Create a new TileAnimation.
+    #[inline]
+    pub fn new(frames: Vec<(TileId, Duration)>) -> Self {
+        Self { frames }
+    }
+
+    /// Duration of one loop of the animation.
+    #[inline]
+    pub fn total_duration(&self) -> Duration {
+        self.frames.iter().map(|&(_, duration)| duration).sum()
+    }
+
+    /// Index of the frame shown at `time`, with the animation looping forever.
+    ///
+    /// `phase` shifts the result by that many frames.
+    /// Returns `None` if the animation has no frames.
+    pub fn frame_index(&self, time: Duration, phase: u32) -> Option<usize> {
+        if self.frames.is_empty() {
+            return None;
+        }
+
+        let total = self.total_duration().as_nanos();
+        let mut time = if total == 0 {
+            0
+        } else {
+            time.as_nanos() % total
+        };
+
+        let mut index = self.frames.len() - 1;
+
+        for (i, &(_, duration)) in self.frames.iter().enumerate() {
+            if time < duration.as_nanos() {
+                index = i;
+                break;
+            }
+
+            time -= duration.as_nanos();
+        }
+
+        Some((index + phase as usize % self.frames.len()) % self.frames.len())
+    }
+
+    /// Tile shown at `time`, see [`frame_index`](Self::frame_index).
+    #[inline]
+    pub fn frame_at(&self, time: Duration, phase: u32) -> Option<TileId> {
+        Some(self.frames[self.frame_index(time, phase)?].0)
+    }
+}
+
+impl<C, M> Tileset<C, M> {
+    /// Register an animation for tile `id`.
+    ///
+    /// Map tiles with this id are drawn as the animation's current frame by [`Tilemap::render_at_time`].
+    /// Replaces the previous animation of the tile, if any.
+    #[inline]
+    pub fn set_animation(&mut self, id: TileId, animation: TileAnimation) {
+        self.animations.insert(id, animation);
+    }
+
+    /// Get the animation of tile `id`.
+    #[inline]
+    pub fn animation(&self, id: TileId) -> Option<&TileAnimation> {
+        self.animations.get(&id)
+    }
+
+    /// Remove the animation of tile `id`, returning it.
+    #[inline]
+    pub fn remove_animation(&mut self, id: TileId) -> Option<TileAnimation> {
+        self.animations.remove(&id)
+    }
+
+    /// Get the tile that should be drawn in place of tile `id` at `time`,
+    /// accounting for its animation and `phase` (in frames).
+    ///
+    /// Tiles without an animation (or with an empty one) are returned as is.
+    #[inline]
+    pub fn animated_id(&self, id: TileId, time: Duration, phase: u32) -> TileId {
+        self.animation(id)
+            .and_then(|animation| animation.frame_at(time, phase))
+            .unwrap_or(id)
+    }
+}
+
+impl<C, M> Tilemap<C, (), M>
+where
+    C: AsRef<[u8]>,
+{
+    /// Render the map onto a buffer at pixel offset `(offset_x, offset_y)`,
+    /// drawing animated tiles as their frame at `time`.
+    ///
+    /// Each tile's [`anim_phase`](Tile::anim_phase) shifts its animation by that many frames.
+    pub fn render_at_time(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+        time: Duration,
+    ) {
+        let cells = self.visible_cells(offset_x, offset_y, surface.width(), surface.height());
+
+        for (tx, ty) in cells {
+            let tile = &self.tiles()[self.xy_to_index(tx, ty)];
+
+            if !tile.visible {
+                continue;
+            }
+
+            // animations are looked up in the tile's own tileset
+            let (tileset, id) = self.tileset_for(tile.id);
+            let first_id = tile.id - id;
+            let frame = Tile {
+                id: first_id + tileset.animated_id(id, time, tile.anim_phase),
+                ..*tile
+            };
+
+            self.render_map_tile(
+                surface,
+                &frame,
+                self.tile_dest(tx, ty, offset_x, offset_y),
+                None,
+                &mut draw_tile_pixel,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{shade, tileset, Surface};
+    use alloc::vec;
+
+    const FRAME: Duration = Duration::from_millis(100);
+
+    /// Shared test [`tileset`] of 8 tiles, where tile 0 cycles through tiles 5, 6 and 7.
+    fn animated() -> Tileset<Vec<u8>> {
+        let mut tileset = tileset(8);
+        tileset.set_animation(
+            0,
+            TileAnimation::new(vec![(5, FRAME), (6, FRAME), (7, FRAME)]),
+        );
+        tileset
+    }
+
+    #[test]
+    fn frame_at_loops() {
+        let ms = Duration::from_millis;
+        let animation = TileAnimation::new(vec![(1, ms(100)), (2, ms(50)), (0, ms(50))]);
+
+        assert_eq!(animation.frame_at(ms(0), 0), Some(1));
+        assert_eq!(animation.frame_at(ms(120), 0), Some(2));
+        assert_eq!(animation.frame_at(ms(150), 0), Some(0));
+        assert_eq!(animation.frame_at(ms(210), 0), Some(1));
+
+        assert_eq!(animation.frame_at(ms(0), 1), Some(2));
+        assert_eq!(animation.frame_at(ms(160), 4), Some(1));
+
+        assert_eq!(TileAnimation::default().frame_at(ms(5), 0), None);
+    }
+
+    #[test]
+    fn phases_desynchronize_tiles() {
+        let tileset = animated();
+        let a: Tile = Tile::new(0);
+        let b: Tile = Tile::new(0).with_anim_phase(1);
+        let time = FRAME / 2;
+
+        assert_eq!(tileset.animated_id(a.id, time, a.anim_phase), 5);
+        assert_eq!(tileset.animated_id(b.id, time, b.anim_phase), 6);
+
+        // phases wrap around the animation
+        assert_eq!(tileset.animated_id(0, time, 3), 5);
+        assert_eq!(tileset.animated_id(0, FRAME * 2, 2), 6);
+    }
+
+    #[test]
+    fn render_at_time() {
+        let mut map: Tilemap<_> = Tilemap::new(2, 1, animated());
+        map.set_tile(0, 0, Tile::new(0));
+        map.set_tile(1, 0, Tile::new(0).with_anim_phase(1));
+
+        let mut surface = Surface::new(4, 2);
+
+        map.render_at_time(&mut surface, 0, 0, FRAME / 2);
+        assert_eq!((surface.at(0, 0), surface.at(2, 0)), (shade(5), shade(6)));
+
+        map.render_at_time(&mut surface, 0, 0, FRAME * 5 / 2);
+        assert_eq!((surface.at(0, 0), surface.at(2, 0)), (shade(7), shade(5)));
+    }
+}
@@ -0,0 +1,362 @@
+use crate::{Tile, TileId, Tilemap};
+
+use alloc::vec::Vec;
+
+/// A set of 16 tiles for auto-tiling, indexed by a 4-bit mask of matching orthogonal neighbors.
+///
+/// Mask bits are [`NORTH`](Self::NORTH), [`EAST`](Self::EAST),
+/// [`SOUTH`](Self::SOUTH) and [`WEST`](Self::WEST).
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AutoTileSet {
+    /// Tile ids indexed by neighbor mask.
+    pub tiles: [TileId; 16],
+    /// Whether neighbors outside of the map are treated as matching.
+    pub edges_match: bool,
+}
+
+impl AutoTileSet {
+    /// Mask bit for the neighbor above.
+    pub const NORTH: u8 = 1;
+    /// Mask bit for the neighbor to the right.
+    pub const EAST: u8 = 2;
+    /// Mask bit for the neighbor below.
+    pub const SOUTH: u8 = 4;
+    /// Mask bit for the neighbor to the left.
+    pub const WEST: u8 = 8;
+    /// Mask bit for the neighbor above and to the right, only used by [`Neighborhood::Eight`].
+    pub const NORTH_EAST: u8 = 16;
+    /// Mask bit for the neighbor below and to the right, only used by [`Neighborhood::Eight`].
+    pub const SOUTH_EAST: u8 = 32;
+    /// Mask bit for the neighbor below and to the left, only used by [`Neighborhood::Eight`].
+    pub const SOUTH_WEST: u8 = 64;
+    /// Mask bit for the neighbor above and to the left, only used by [`Neighborhood::Eight`].
+    pub const NORTH_WEST: u8 = 128;
+
+    /// Create a new AutoTileSet.
+    #[inline]
+    pub const fn new(tiles: [TileId; 16]) -> Self {
+        Self {
+            tiles,
+            edges_match: false,
+        }
+    }
+
+    /// Specify whether neighbors outside of the map are treated as matching.
+    #[inline]
+    pub const fn with_edges_match(mut self, edges_match: bool) -> Self {
+        self.edges_match = edges_match;
+        self
+    }
+
+    /// Tile id for a neighbor mask.
+    #[inline]
+    pub const fn get(&self, mask: u8) -> TileId {
+        self.tiles[(mask & 0xF) as usize]
+    }
+}
+
+/// Which neighbors make up an auto-tiling mask.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Neighborhood {
+    /// Orthogonal neighbors only, giving a 4-bit mask.
+    #[default]
+    Four,
+    /// Orthogonal and diagonal neighbors, giving an 8-bit mask.
+    ///
+    /// A diagonal neighbor only counts if both orthogonal neighbors next to it match as well,
+    /// which leaves 47 distinct masks.
+    Eight,
+}
+
+/// Auto-tiling rule: tiles matching `predicate` get their id looked up by the mask of their matching neighbors.
+///
+/// Mask bits are the ones defined on [`AutoTileSet`]. Masks without an entry in `lookup` leave the tile unchanged.
+#[derive(Clone, Debug)]
+pub struct AutoTileRule<F> {
+    /// Whether a tile takes part in auto-tiling.
+    pub predicate: F,
+    /// Which neighbors make up the mask.
+    pub neighborhood: Neighborhood,
+    /// Tile ids indexed by neighbor mask.
+    pub lookup: Vec<TileId>,
+    /// Whether neighbors outside of the map are treated as matching.
+    pub edges_match: bool,
+}
+
+impl<F> AutoTileRule<F> {
+    /// Create a new AutoTileRule.
+    #[inline]
+    pub fn new(predicate: F, neighborhood: Neighborhood, lookup: Vec<TileId>) -> Self {
+        Self {
+            predicate,
+            neighborhood,
+            lookup,
+            edges_match: false,
+        }
+    }
+
+    /// Specify whether neighbors outside of the map are treated as matching.
+    #[inline]
+    pub fn with_edges_match(mut self, edges_match: bool) -> Self {
+        self.edges_match = edges_match;
+        self
+    }
+
+    /// Tile id for a neighbor mask, if there is one.
+    #[inline]
+    pub fn get(&self, mask: u8) -> Option<TileId> {
+        self.lookup.get(mask as usize).copied()
+    }
+}
+
+impl<C, U, M> Tilemap<C, U, M> {
+    /// Compute the neighbor mask of the tile at (x, y) for `rule`.
+    ///
+    /// The tile itself doesn't have to match the rule's predicate.
+    /// Returns `None` if the position is out of bounds.
+    pub fn autotile_mask<F>(&self, x: u32, y: u32, rule: &AutoTileRule<F>) -> Option<u8>
+    where
+        F: Fn(&Tile<U>) -> bool,
+    {
+        self.get_tile(x, y)?;
+
+        let matches = |dx: i64, dy: i64| {
+            let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+
+            if nx < 0 || ny < 0 || nx >= self.width() as i64 || ny >= self.height() as i64 {
+                rule.edges_match
+            } else {
+                self.get_tile(nx as u32, ny as u32)
+                    .is_some_and(|tile| (rule.predicate)(tile))
+            }
+        };
+
+        let mut mask = 0;
+
+        for (dx, dy, bit) in [
+            (0, -1, AutoTileSet::NORTH),
+            (1, 0, AutoTileSet::EAST),
+            (0, 1, AutoTileSet::SOUTH),
+            (-1, 0, AutoTileSet::WEST),
+        ] {
+            if matches(dx, dy) {
+                mask |= bit;
+            }
+        }
+
+        if rule.neighborhood == Neighborhood::Eight {
+            for (dx, dy, sides, bit) in [
+                (
+                    1,
+                    -1,
+                    AutoTileSet::NORTH | AutoTileSet::EAST,
+                    AutoTileSet::NORTH_EAST,
+                ),
+                (
+                    1,
+                    1,
+                    AutoTileSet::SOUTH | AutoTileSet::EAST,
+                    AutoTileSet::SOUTH_EAST,
+                ),
+                (
+                    -1,
+                    1,
+                    AutoTileSet::SOUTH | AutoTileSet::WEST,
+                    AutoTileSet::SOUTH_WEST,
+                ),
+                (
+                    -1,
+                    -1,
+                    AutoTileSet::NORTH | AutoTileSet::WEST,
+                    AutoTileSet::NORTH_WEST,
+                ),
+            ] {
+                if mask & sides == sides && matches(dx, dy) {
+                    mask |= bit;
+                }
+            }
+        }
+
+        Some(mask)
+    }
+
+    /// Replace the id of every tile matching the rule's predicate with the entry of the rule's lookup table
+    /// corresponding to its neighbors that also match.
+    ///
+    /// Masks are computed before any of the tiles are changed.
+    pub fn autotile<F>(&mut self, rule: &AutoTileRule<F>)
+    where
+        F: Fn(&Tile<U>) -> bool,
+    {
+        let width = self.width();
+
+        let ids: Vec<_> = (0..self.height())
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&(x, y)| (rule.predicate)(&self.tiles()[(y * width + x) as usize]))
+            .filter_map(|(x, y)| {
+                let id = rule.get(self.autotile_mask(x, y, rule)?)?;
+                Some(((y * width + x) as usize, id))
+            })
+            .collect();
+
+        for (i, id) in ids {
+            if let Some(tile) = self.get_mut_tile_index(i) {
+                tile.id = id;
+            }
+        }
+    }
+
+    /// Replace the id of every tile with id `match_id` with the entry of `autotile`
+    /// corresponding to its neighbors that also have id `match_id`.
+    pub fn apply_autotile(&mut self, match_id: TileId, autotile: &AutoTileSet) {
+        let (width, height) = (self.width(), self.height());
+
+        let matches = |x: u32, y: u32, dx: i32, dy: i32| {
+            let (nx, ny) = (x as i64 + dx as i64, y as i64 + dy as i64);
+
+            if nx < 0 || ny < 0 || nx >= width as i64 || ny >= height as i64 {
+                autotile.edges_match
+            } else {
+                self.tiles()[(ny as u32 * width + nx as u32) as usize].id == match_id
+            }
+        };
+
+        let masks: Vec<_> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.tiles()[(y * width + x) as usize].id == match_id)
+            .map(|(x, y)| {
+                let mut mask = 0;
+
+                if matches(x, y, 0, -1) {
+                    mask |= AutoTileSet::NORTH;
+                }
+                if matches(x, y, 1, 0) {
+                    mask |= AutoTileSet::EAST;
+                }
+                if matches(x, y, 0, 1) {
+                    mask |= AutoTileSet::SOUTH;
+                }
+                if matches(x, y, -1, 0) {
+                    mask |= AutoTileSet::WEST;
+                }
+
+                ((y * width + x) as usize, mask)
+            })
+            .collect();
+
+        let tiles = self.tiles_mut();
+
+        for (i, mask) in masks {
+            tiles[i].id = autotile.get(mask);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::tileset;
+    use crate::Tile;
+    use alloc::vec;
+
+    /// Map of the shared test [`tileset`], only ids matter here.
+    fn map(width: u32, height: u32) -> Tilemap<Vec<u8>> {
+        Tilemap::new(width, height, tileset(1))
+    }
+
+    #[test]
+    fn apply_autotile_t_junction() {
+        // ###
+        // .#.
+        let mut map = map(3, 2);
+        for (x, y) in [(0, 0), (1, 0), (2, 0), (1, 1)] {
+            map.set_tile(x, y, Tile::new(1));
+        }
+
+        let ids = core::array::from_fn(|mask| 100 + mask as TileId);
+        map.apply_autotile(1, &AutoTileSet::new(ids));
+
+        let id = |x, y| map.get_tile(x, y).unwrap().id;
+        assert_eq!(
+            id(1, 0),
+            100 + (AutoTileSet::EAST | AutoTileSet::SOUTH | AutoTileSet::WEST) as TileId
+        );
+        assert_eq!(id(0, 0), 100 + AutoTileSet::EAST as TileId);
+        assert_eq!(id(1, 1), 100 + AutoTileSet::NORTH as TileId);
+        assert_eq!(id(0, 1), 0);
+    }
+
+    #[test]
+    fn apply_autotile_edges_match() {
+        let mut map = map(1, 1);
+        map.set_tile(0, 0, Tile::new(1));
+
+        let ids = core::array::from_fn(|mask| 100 + mask as TileId);
+        map.apply_autotile(1, &AutoTileSet::new(ids).with_edges_match(true));
+
+        assert_eq!(map.get_tile(0, 0).unwrap().id, 115);
+    }
+
+    #[test]
+    fn rule_masks() {
+        let mut map = map(3, 3);
+        map.fill_rect(0, 0, 3, 3, Tile::new(1));
+        map.set_tile(2, 2, Tile::new(0));
+
+        let is_wall = |tile: &Tile| tile.id == 1;
+        let four = AutoTileRule::new(is_wall, Neighborhood::Four, (100..116).collect());
+        assert_eq!(map.autotile_mask(1, 1, &four), Some(15));
+        assert_eq!(
+            map.autotile_mask(0, 0, &four),
+            Some(AutoTileSet::EAST | AutoTileSet::SOUTH)
+        );
+        assert_eq!(map.autotile_mask(3, 0, &four), None);
+
+        let eight = AutoTileRule::new(is_wall, Neighborhood::Eight, (1000..1256).collect());
+        assert_eq!(
+            map.autotile_mask(1, 1, &eight),
+            Some(!AutoTileSet::SOUTH_EAST)
+        );
+        assert_eq!(
+            map.autotile_mask(0, 0, &eight),
+            Some(AutoTileSet::EAST | AutoTileSet::SOUTH | AutoTileSet::SOUTH_EAST)
+        );
+
+        // corners only count if both of their sides do
+        map.set_tile(1, 0, Tile::new(0));
+        assert_eq!(
+            map.autotile_mask(0, 1, &eight).unwrap() & AutoTileSet::NORTH_EAST,
+            0
+        );
+    }
+
+    #[test]
+    fn autotile_with_rule() {
+        let mut map = map(3, 3);
+        map.fill_rect(0, 0, 3, 3, Tile::new(1));
+        map.set_tile(1, 0, Tile::new(0));
+        map.set_tile(2, 2, Tile::new(0));
+
+        let is_wall = |tile: &Tile| tile.id == 1;
+
+        let mut walls = map.clone();
+        walls.autotile(&AutoTileRule::new(
+            is_wall,
+            Neighborhood::Four,
+            (100..116).collect(),
+        ));
+        assert_eq!(
+            walls.get_tile(1, 1).unwrap().id,
+            100 + (AutoTileSet::SOUTH | AutoTileSet::WEST | AutoTileSet::EAST) as TileId
+        );
+        assert_eq!(walls.get_tile(2, 2).unwrap().id, 0);
+
+        // masks missing from the lookup table leave the tiles unchanged
+        let short =
+            AutoTileRule::new(is_wall, Neighborhood::Four, vec![7; 4]).with_edges_match(true);
+        map.autotile(&short);
+        assert_eq!(map.get_tile(0, 0).unwrap().id, 1);
+    }
+}
@@ -0,0 +1,107 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// A grid of bits, one per cell, e.g. for collision or pathfinding data.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BitGrid {
+    words: Vec<u32>,
+    width: u32,
+    height: u32,
+}
+
+impl BitGrid {
+    /// Construct a new grid with all bits cleared.
+    pub fn new(width: u32, height: u32) -> Self {
+        let len = width.checked_mul(height).expect("grid too large");
+
+        Self {
+            words: vec![0; len.div_ceil(u32::BITS) as usize],
+            width,
+            height,
+        }
+    }
+
+    /// Grid's width.
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Grid's height.
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Get the bit at (x, y).
+    ///
+    /// Returns `false` if the position is out of bounds.
+    #[inline]
+    pub fn get(&self, x: u32, y: u32) -> bool {
+        match self.index_of(x, y) {
+            Some(i) => self.words[i / 32] & (1 << (i % 32)) != 0,
+            None => false,
+        }
+    }
+
+    /// Set the bit at (x, y).
+    ///
+    /// Does nothing if the position is out of bounds.
+    #[inline]
+    pub fn set(&mut self, x: u32, y: u32, bit: bool) {
+        if let Some(i) = self.index_of(x, y) {
+            if bit {
+                self.words[i / 32] |= 1 << (i % 32);
+            } else {
+                self.words[i / 32] &= !(1 << (i % 32));
+            }
+        }
+    }
+
+    /// Clear all the bits.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.words.fill(0);
+    }
+
+    /// Set all the bits.
+    #[inline]
+    pub fn set_all(&mut self) {
+        self.words.fill(u32::MAX);
+    }
+
+    #[inline]
+    fn index_of(&self, x: u32, y: u32) -> Option<usize> {
+        if x < self.width && y < self.height {
+            Some((y * self.width + x) as usize)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_and_set() {
+        let mut grid = BitGrid::new(7, 7);
+        grid.set(6, 6, true);
+        grid.set(0, 1, true);
+
+        assert!(grid.get(6, 6));
+        assert!(grid.get(0, 1));
+        assert!(!grid.get(1, 0));
+        assert!(!grid.get(7, 0));
+
+        grid.set(6, 6, false);
+        assert!(!grid.get(6, 6));
+
+        grid.set_all();
+        assert!(grid.get(3, 3));
+        grid.clear();
+        assert!(!grid.get(3, 3));
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{blend_over, multiply, Color, Tile};
+
+use fast_srgb8::{f32x4_to_srgb8, srgb8_to_f32};
+
+/// How a tile's pixels are combined with the pixels already on the buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlendMode {
+    /// Replace the buffer's pixel, including its alpha.
+    #[default]
+    Normal,
+    /// Source-over alpha blending in linear space, so that translucent pixels are layered over the buffer's.
+    Alpha,
+    /// Add the tile's color to the buffer's, for glows and lights.
+    Additive,
+    /// Multiply the buffer's color by the tile's, for shadows.
+    Multiply,
+    /// Inverse of multiplying the inverted colors, brightens the buffer.
+    Screen,
+}
+
+impl BlendMode {
+    /// Blend `color` onto `dest`.
+    ///
+    /// For the additive, multiply and screen modes blending is done in linear space
+    /// and the result is faded towards `dest` by `color`'s alpha.
+    pub fn blend(self, dest: Color, color: Color) -> Color {
+        let op = match self {
+            Self::Normal => return color,
+            Self::Alpha => return blend_over(dest, color),
+            Self::Additive => |d: f32, s: f32| d + s,
+            Self::Multiply => |d: f32, s: f32| d * s,
+            Self::Screen => |d: f32, s: f32| d + s - d * s,
+        };
+
+        let alpha = color.a as f32 / 255.;
+        let channel = |d: u8, s: u8| {
+            let d = srgb8_to_f32(d);
+            d + (op(d, srgb8_to_f32(s)) - d) * alpha
+        };
+
+        let [r, g, b, _] = f32x4_to_srgb8([
+            channel(dest.r, color.r),
+            channel(dest.g, color.g),
+            channel(dest.b, color.b),
+            0.,
+        ]);
+        let a = color.a as u32 + dest.a as u32 * (255 - color.a as u32) / 255;
+
+        Color::new(r, g, b, a as u8)
+    }
+}
+
+/// Map-wide options for [`Tilemap::render_with_options`](crate::Tilemap::render_with_options).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RenderOptions {
+    /// Color multiplied with every tile's own color.
+    pub tint: Color,
+    /// Opacity of the whole map, multiplied with the alpha of its pixels.
+    pub opacity: u8,
+}
+
+impl RenderOptions {
+    /// Options that render the map the same as [`Tilemap::render`](crate::Tilemap::render).
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            tint: Color::new(255, 255, 255, 255),
+            opacity: 255,
+        }
+    }
+
+    /// Set the tint.
+    #[inline]
+    pub fn with_tint(mut self, tint: Color) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    /// Set the tint.
+    #[inline]
+    pub fn set_tint(&mut self, tint: Color) -> &mut Self {
+        self.tint = tint;
+        self
+    }
+
+    /// Set the opacity.
+    #[inline]
+    pub fn with_opacity(mut self, opacity: u8) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    /// Set the opacity.
+    #[inline]
+    pub fn set_opacity(&mut self, opacity: u8) -> &mut Self {
+        self.opacity = opacity;
+        self
+    }
+}
+
+impl Default for RenderOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Draw a pixel of a map tile: tint it with the tile's color and blend it with the tile's blend mode.
+#[inline]
+pub(crate) fn draw_tile_pixel<U>(dest: &mut Color, src: &Color, tile: &Tile<U>) {
+    *dest = tile.blend.blend(*dest, multiply(*src, tile.color));
+}
+
+/// Draw a pixel of a map tile drawn over other maps: like [`draw_tile_pixel`], but faded by `opacity`,
+/// and with the normal blend mode alpha blending so that whatever is below shows through.
+#[inline]
+pub(crate) fn draw_layer_pixel<U>(dest: &mut Color, src: &Color, tile: &Tile<U>, opacity: u8) {
+    let color = multiply(*src, tile.color);
+    let color = Color {
+        a: (color.a as u16 * opacity as u16 / 255) as u8,
+        ..color
+    };
+
+    let blend = match tile.blend {
+        BlendMode::Normal => BlendMode::Alpha,
+        blend => blend,
+    };
+
+    *dest = blend.blend(*dest, color);
+}
@@ -0,0 +1,174 @@
+use crate::{Buffer, BufferMut, Color, Tilemap};
+
+use alloc::vec::Vec;
+use core::ops::{Index, IndexMut};
+use simple_blit::{blit_with, BlitOptions};
+
+/// A map pre-rendered into an image, to be cheaply drawn every frame.
+///
+/// Useful for static layers that rarely change.
+/// Call [`invalidate`](Self::invalidate) after changing the map and [`update`](Self::update)
+/// to render it again. Edits of the map's tilesets are picked up by [`update`](Self::update) on its own
+/// through their [generation](crate::Tileset::generation).
+#[derive(Clone, Debug)]
+pub struct CachedLayer {
+    pixels: Vec<Color>,
+    width: u32,
+    height: u32,
+    valid: bool,
+    generations: Vec<u64>,
+}
+
+impl CachedLayer {
+    /// Render `map` into a new cached layer.
+    pub fn new<C, M>(map: &Tilemap<C, (), M>) -> Self
+    where
+        C: AsRef<[u8]>,
+    {
+        let mut layer = Self {
+            pixels: Vec::new(),
+            width: 0,
+            height: 0,
+            valid: false,
+            generations: Vec::new(),
+        };
+
+        layer.update(map);
+        layer
+    }
+
+    /// Whether the cached image is up to date.
+    #[inline]
+    pub fn is_valid(&self) -> bool {
+        self.valid
+    }
+
+    /// Mark the cached image as outdated, so that the next [`update`](Self::update) renders the map again.
+    #[inline]
+    pub fn invalidate(&mut self) {
+        self.valid = false;
+    }
+
+    /// Render `map` again if the layer was invalidated or one of the map's tilesets was edited since.
+    pub fn update<C, M>(&mut self, map: &Tilemap<C, (), M>)
+    where
+        C: AsRef<[u8]>,
+    {
+        let generations = || map.tilesets().map(|(_, tileset)| tileset.generation());
+
+        if self.valid && generations().eq(self.generations.iter().copied()) {
+            return;
+        }
+
+        (self.width, self.height) = map.pixel_size();
+        self.pixels.clear();
+        self.pixels.resize(
+            self.width as usize * self.height as usize,
+            Color::new(0, 0, 0, 0),
+        );
+        self.generations.clear();
+        self.generations.extend(generations());
+
+        map.render(self, 0, 0);
+        self.valid = true;
+    }
+
+    /// Draw the cached image onto a buffer at pixel offset `(offset_x, offset_y)`.
+    ///
+    /// Fully transparent pixels (including the ones no tile was drawn to) are skipped.
+    pub fn blit_to(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+    ) {
+        blit_with(
+            surface,
+            (offset_x, offset_y),
+            self,
+            (0, 0),
+            (self.width, self.height),
+            BlitOptions::None,
+            |dest, src, _| {
+                if src.a != 0 {
+                    *dest = *src;
+                }
+            },
+        );
+    }
+}
+
+impl Buffer<Color> for CachedLayer {
+    #[inline]
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[inline]
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[inline]
+    fn get(&self, x: u32, y: u32) -> &Color {
+        self.pixels
+            .index(y as usize * self.width as usize + x as usize)
+    }
+}
+
+impl BufferMut<Color> for CachedLayer {
+    #[inline]
+    fn get_mut(&mut self, x: u32, y: u32) -> &mut Color {
+        self.pixels
+            .index_mut(y as usize * self.width as usize + x as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{tileset, Surface};
+    use crate::{Tile, TileId};
+
+    #[test]
+    fn cached_layer_matches_render() {
+        let mut map: Tilemap<_> = Tilemap::new(3, 2, tileset(3));
+        for (i, tile) in map.tiles_mut().iter_mut().enumerate() {
+            *tile = Tile::new(i as TileId % 3);
+        }
+
+        let mut layer = CachedLayer::new(&map);
+        let (mut rendered, mut cached) = (Surface::new(9, 7), Surface::new(9, 7));
+        map.render(&mut rendered, 2, 1);
+        layer.blit_to(&mut cached, 2, 1);
+        assert_eq!(rendered.pixels, cached.pixels);
+
+        // stays stale until invalidated
+        map.set_tile(1, 0, Tile::new(0));
+        layer.update(&map);
+        let mut stale = Surface::new(9, 7);
+        layer.blit_to(&mut stale, 2, 1);
+        assert_eq!(stale.pixels, cached.pixels);
+
+        layer.invalidate();
+        layer.update(&map);
+        let (mut rendered, mut cached) = (Surface::new(9, 7), Surface::new(9, 7));
+        map.render(&mut rendered, 2, 1);
+        layer.blit_to(&mut cached, 2, 1);
+        assert_eq!(rendered.pixels, cached.pixels);
+        assert_ne!(cached.pixels, stale.pixels);
+
+        // edits of the tileset are picked up without invalidating
+        let map = map.map_tileset(|mut tileset| {
+            tileset.edit_pixels(|pixels| *pixels.get_mut(0, 0) = Color::new(1, 2, 3, 255));
+            tileset
+        });
+        layer.update(&map);
+        assert!(layer.is_valid());
+        let (mut rendered, mut cached) = (Surface::new(9, 7), Surface::new(9, 7));
+        map.render(&mut rendered, 2, 1);
+        layer.blit_to(&mut cached, 2, 1);
+        assert_eq!(rendered.pixels, cached.pixels);
+        assert_eq!(cached.at(2, 1), Color::new(1, 2, 3, 255));
+    }
+}
@@ -0,0 +1,221 @@
+use crate::{draw_tile_pixel, BufferMut, Color, Tilemap};
+
+/// A view of the world, drawn to a viewport in the top-left corner of a buffer.
+///
+/// World coordinates are map pixels, with the map's origin at (0, 0).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Camera {
+    /// World position shown at the viewport's top-left corner.
+    pub position: (i32, i32),
+    /// How many buffer pixels wide and high each world pixel is drawn. 0 is treated as 1.
+    pub zoom: u32,
+    /// Size (width x height) of the viewport in buffer pixels.
+    pub viewport_size: (u32, u32),
+}
+
+impl Camera {
+    /// Create a new camera at world position (0, 0) with no zoom.
+    #[inline]
+    pub const fn new(viewport_width: u32, viewport_height: u32) -> Self {
+        Self {
+            position: (0, 0),
+            zoom: 1,
+            viewport_size: (viewport_width, viewport_height),
+        }
+    }
+
+    /// Set the world position shown at the viewport's top-left corner.
+    #[inline]
+    pub fn with_position(mut self, x: i32, y: i32) -> Self {
+        self.position = (x, y);
+        self
+    }
+
+    /// Set the world position shown at the viewport's top-left corner.
+    #[inline]
+    pub fn set_position(&mut self, x: i32, y: i32) -> &mut Self {
+        self.position = (x, y);
+        self
+    }
+
+    /// Set the zoom.
+    #[inline]
+    pub fn with_zoom(mut self, zoom: u32) -> Self {
+        self.zoom = zoom;
+        self
+    }
+
+    /// Set the zoom.
+    #[inline]
+    pub fn set_zoom(&mut self, zoom: u32) -> &mut Self {
+        self.zoom = zoom;
+        self
+    }
+
+    /// Move the camera so that the world position (x, y) is in the middle of the viewport.
+    #[inline]
+    pub fn center_on(&mut self, x: i32, y: i32) -> &mut Self {
+        let (width, height) = self.view_size();
+
+        self.position = (
+            saturate(x as i64 - width as i64 / 2),
+            saturate(y as i64 - height as i64 / 2),
+        );
+        self
+    }
+
+    /// Size (width x height) of the part of the world the viewport shows, rounded up.
+    #[inline]
+    pub fn view_size(&self) -> (u32, u32) {
+        (
+            self.viewport_size.0.div_ceil(self.scale()),
+            self.viewport_size.1.div_ceil(self.scale()),
+        )
+    }
+
+    /// Buffer position of the top-left corner of the world pixel (x, y).
+    #[inline]
+    pub fn world_to_screen(&self, x: i32, y: i32) -> (i32, i32) {
+        let scale = self.scale() as i64;
+
+        (
+            saturate((x as i64 - self.position.0 as i64) * scale),
+            saturate((y as i64 - self.position.1 as i64) * scale),
+        )
+    }
+
+    /// World position of the pixel drawn at buffer position (x, y).
+    ///
+    /// The inverse of [`world_to_screen`](Self::world_to_screen), works outside of the viewport as well.
+    #[inline]
+    pub fn screen_to_world(&self, x: i32, y: i32) -> (i32, i32) {
+        let scale = self.scale() as i64;
+
+        (
+            saturate(self.position.0 as i64 + (x as i64).div_euclid(scale)),
+            saturate(self.position.1 as i64 + (y as i64).div_euclid(scale)),
+        )
+    }
+
+    /// Camera for a layer scrolling at `factor_x` and `factor_y` times the speed of this camera,
+    /// e.g. for a parallax background rendered with [`Tilemap::render_with_camera`].
+    ///
+    /// A factor of 1 scrolls along with the camera, 0 doesn't scroll at all.
+    #[inline]
+    pub fn parallax(&self, factor_x: f32, factor_y: f32) -> Self {
+        Self {
+            position: (
+                parallax_position(self.position.0, factor_x),
+                parallax_position(self.position.1, factor_y),
+            ),
+            ..*self
+        }
+    }
+
+    /// The zoom, at least 1.
+    #[inline]
+    fn scale(&self) -> u32 {
+        self.zoom.max(1)
+    }
+}
+
+impl<C, M> Tilemap<C, (), M>
+where
+    C: AsRef<[u8]>,
+{
+    /// Render the part of the map `camera` sees onto its viewport in the top-left corner of a buffer.
+    ///
+    /// Only the tiles inside the view are drawn, and nothing is drawn outside of the viewport.
+    pub fn render_with_camera(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        camera: &Camera,
+    ) {
+        let scale = camera.scale();
+
+        self.render_tiles_scaled(
+            surface,
+            (
+                -(camera.position.0 as i64) * scale as i64,
+                -(camera.position.1 as i64) * scale as i64,
+            ),
+            scale,
+            camera.viewport_size,
+            draw_tile_pixel,
+        );
+    }
+}
+
+/// Clamp a value to the range of `i32`.
+#[inline]
+pub(crate) fn saturate(value: i64) -> i32 {
+    value.clamp(i32::MIN as i64, i32::MAX as i64) as i32
+}
+
+/// Position of a layer scrolling at `factor` times the speed of the camera at `position`,
+/// rounded to the nearest pixel.
+#[inline]
+pub(crate) fn parallax_position(position: i32, factor: f32) -> i32 {
+    let position = position as f32 * factor;
+
+    // `f32::round` isn't available without std
+    (position + if position < 0. { -0.5 } else { 0.5 }) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{shade, tileset, Surface};
+    use crate::Tile;
+    use alloc::vec::Vec;
+
+    /// 3x2 map of 2x2 tiles, each filled with [`shade`] of its index.
+    fn map() -> Tilemap<Vec<u8>> {
+        let mut map = Tilemap::new(3, 2, tileset(6));
+        for index in 0..6 {
+            map.set_tile_index(index, Tile::new(index as u32));
+        }
+
+        map
+    }
+
+    #[test]
+    fn no_zoom_matches_render() {
+        let map = map();
+
+        for (x, y) in [(0, 0), (-3, 1), (2, -1), (5, 3)] {
+            let mut expected = Surface::new(5, 4);
+            map.render(&mut expected, -x, -y);
+
+            let mut surface = Surface::new(5, 4);
+            map.render_with_camera(&mut surface, &Camera::new(5, 4).with_position(x, y));
+            assert_eq!(surface.pixels, expected.pixels, "camera at ({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn zoom_and_viewport() {
+        let map = map();
+        let camera = Camera::new(7, 5).with_position(1, 0).with_zoom(3);
+
+        let mut surface = Surface::new(10, 10);
+        map.render_with_camera(&mut surface, &camera);
+        assert_eq!(surface.at(0, 0), shade(0));
+        assert_eq!(surface.at(2, 2), shade(0));
+        assert_eq!(surface.at(3, 0), shade(1));
+        assert_eq!(surface.at(6, 4), shade(1));
+        assert_eq!(surface.at(7, 0), Color::default());
+        assert_eq!(surface.at(0, 5), Color::default());
+
+        assert_eq!(camera.view_size(), (3, 2));
+        assert_eq!(camera.world_to_screen(2, 0), (3, 0));
+        assert_eq!(camera.screen_to_world(3, 0), (2, 0));
+        assert_eq!(camera.screen_to_world(-1, -4), (0, -2));
+        assert_eq!(Camera::new(4, 4).with_zoom(0).world_to_screen(1, 1), (1, 1));
+
+        let mut camera = Camera::new(10, 10).with_zoom(2);
+        camera.center_on(0, 0);
+        assert_eq!(camera.position, (-2, -2));
+    }
+}
@@ -0,0 +1,492 @@
+use crate::{cell_span, draw_tile_pixel, BufferMut, Color, Tile, Tileset, OFFSET_MARGIN};
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+/// Loader used by [`ChunkedTilemap`] to get the tiles of a chunk.
+type ChunkLoader<U> = Box<dyn FnMut(i32, i32) -> Vec<Tile<U>>>;
+
+/// A map that is split into square chunks of tiles, which are created or loaded on demand.
+///
+/// A map made with [`new`](Self::new) keeps only a limited amount of recently used chunks in memory,
+/// the rest get dropped and loaded again when needed.
+/// A map made with [`unbounded`](Self::unbounded) has no fixed size and keeps every chunk that was written to.
+/// Tile and chunk positions are signed, with chunk (0, 0) containing tiles from (0, 0)
+/// to (chunk_size - 1, chunk_size - 1).
+pub struct ChunkedTilemap<C, U = ()> {
+    tileset: Tileset<C>,
+    chunk_size: u32,
+    capacity: usize,
+    chunks: BTreeMap<(i32, i32), Chunk<U>>,
+    loader: Option<ChunkLoader<U>>,
+    tick: u64,
+}
+
+struct Chunk<U> {
+    tiles: Vec<Tile<U>>,
+    last_used: u64,
+}
+
+impl<C, U> ChunkedTilemap<C, U> {
+    /// Construct a new chunked map.
+    ///
+    /// `loader` gets called with chunk positions and should return the chunk's tiles row by row.
+    /// At most `capacity` chunks are kept in memory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` or `capacity` is 0.
+    pub fn new<F>(tileset: Tileset<C>, chunk_size: u32, capacity: usize, loader: F) -> Self
+    where
+        F: FnMut(i32, i32) -> Vec<Tile<U>> + 'static,
+    {
+        assert!(chunk_size > 0, "chunk size must not be 0");
+        assert!(capacity > 0, "capacity must not be 0");
+
+        Self {
+            tileset,
+            chunk_size,
+            capacity,
+            chunks: BTreeMap::new(),
+            loader: Some(Box::new(loader)),
+            tick: 0,
+        }
+    }
+
+    /// Construct a new map without bounds.
+    ///
+    /// Chunks are created filled with default tiles when one of their tiles is written to
+    /// and are never dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is 0.
+    pub fn unbounded(tileset: Tileset<C>, chunk_size: u32) -> Self {
+        assert!(chunk_size > 0, "chunk size must not be 0");
+
+        Self {
+            tileset,
+            chunk_size,
+            capacity: usize::MAX,
+            chunks: BTreeMap::new(),
+            loader: None,
+            tick: 0,
+        }
+    }
+
+    /// Tileset used by this map.
+    #[inline]
+    pub fn tileset(&self) -> &Tileset<C> {
+        &self.tileset
+    }
+
+    /// Chunk's width and height in tiles.
+    #[inline]
+    pub fn chunk_size(&self) -> u32 {
+        self.chunk_size
+    }
+
+    /// Maximum amount of chunks kept in memory, `usize::MAX` for unbounded maps.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Whether the map is unbounded, i.e. made with [`unbounded`](Self::unbounded).
+    #[inline]
+    pub fn is_unbounded(&self) -> bool {
+        self.loader.is_none()
+    }
+
+    /// Positions of the chunks currently in memory, row by row.
+    #[inline]
+    pub fn resident_chunks(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.chunks.keys().map(|&(y, x)| (x, y))
+    }
+
+    /// Whether the chunk at (chunk_x, chunk_y) is currently in memory.
+    #[inline]
+    pub fn is_resident(&self, chunk_x: i32, chunk_y: i32) -> bool {
+        self.chunks.contains_key(&(chunk_y, chunk_x))
+    }
+
+    /// Position of the chunk containing the tile at (x, y).
+    #[inline]
+    pub fn chunk_of(&self, x: i32, y: i32) -> (i32, i32) {
+        let size = self.chunk_size as i32;
+        (x.div_euclid(size), y.div_euclid(size))
+    }
+
+    /// Get a tile at (x, y) if its chunk is in memory.
+    pub fn get_tile(&self, x: i32, y: i32) -> Option<&Tile<U>> {
+        let (chunk_x, chunk_y) = self.chunk_of(x, y);
+        let chunk = self.chunks.get(&(chunk_y, chunk_x))?;
+
+        chunk.tiles.get(self.index_in_chunk(x, y))
+    }
+
+    /// Drop the chunk at (chunk_x, chunk_y) from memory, returning its tiles.
+    ///
+    /// For unbounded maps this removes the chunk's tiles for good.
+    #[inline]
+    pub fn unload_chunk(&mut self, chunk_x: i32, chunk_y: i32) -> Option<Vec<Tile<U>>> {
+        self.chunks
+            .remove(&(chunk_y, chunk_x))
+            .map(|chunk| chunk.tiles)
+    }
+
+    /// Drop all the chunks from memory.
+    #[inline]
+    pub fn unload_all(&mut self) {
+        self.chunks.clear();
+    }
+
+    #[inline]
+    fn index_in_chunk(&self, x: i32, y: i32) -> usize {
+        let size = self.chunk_size as i32;
+        (y.rem_euclid(size) * size + x.rem_euclid(size)) as usize
+    }
+}
+
+impl<C, U> ChunkedTilemap<C, U>
+where
+    U: Default,
+{
+    /// Get a mutable ref to a tile at (x, y), loading its chunk if needed.
+    pub fn get_mut_tile(&mut self, x: i32, y: i32) -> &mut Tile<U> {
+        let (chunk_x, chunk_y) = self.chunk_of(x, y);
+        let i = self.index_in_chunk(x, y);
+
+        &mut self.load_chunk(chunk_x, chunk_y)[i]
+    }
+
+    /// Set a tile at (x, y), loading its chunk if needed.
+    #[inline]
+    pub fn set_tile(&mut self, x: i32, y: i32, tile: Tile<U>) {
+        *self.get_mut_tile(x, y) = tile;
+    }
+
+    /// Get the tiles of the chunk at (chunk_x, chunk_y), loading (or creating) it if needed.
+    ///
+    /// If loading the chunk exceeds the capacity, the least recently used chunk gets dropped.
+    /// If the loader returns a wrong amount of tiles, the chunk gets padded with default tiles or truncated.
+    pub fn load_chunk(&mut self, chunk_x: i32, chunk_y: i32) -> &mut [Tile<U>] {
+        self.tick += 1;
+
+        let key = (chunk_y, chunk_x);
+
+        if !self.chunks.contains_key(&key) {
+            if self.chunks.len() >= self.capacity {
+                let lru = self
+                    .chunks
+                    .iter()
+                    .min_by_key(|(_, chunk)| chunk.last_used)
+                    .map(|(&key, _)| key);
+
+                if let Some(lru) = lru {
+                    self.chunks.remove(&lru);
+                }
+            }
+
+            let mut tiles = match &mut self.loader {
+                Some(loader) => loader(chunk_x, chunk_y),
+                None => Vec::new(),
+            };
+            tiles.resize_with((self.chunk_size * self.chunk_size) as usize, Tile::default);
+
+            self.chunks.insert(
+                key,
+                Chunk {
+                    tiles,
+                    last_used: 0,
+                },
+            );
+        }
+
+        let chunk = self.chunks.get_mut(&key).expect("chunk was just loaded");
+        chunk.last_used = self.tick;
+        &mut chunk.tiles
+    }
+}
+
+impl<C, U> ChunkedTilemap<C, U>
+where
+    C: AsRef<[u8]>,
+    U: Default,
+{
+    /// Render the part of the map that is visible on a buffer, with tile (0, 0) at pixel offset `(offset_x, offset_y)`.
+    ///
+    /// Only the chunks intersecting the buffer are loaded and drawn.
+    /// Unbounded maps don't create chunks when rendering, missing ones are skipped.
+    /// Tiles moved into the buffer by their [offset](Tile::offset) from other chunks are drawn
+    /// only if those chunks are already in memory.
+    pub fn render(
+        &mut self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+    ) {
+        let (tile_width, tile_height) = self.tileset.opts.tile_size;
+
+        if tile_width == 0 || tile_height == 0 {
+            return;
+        }
+
+        let chunk_width = self.chunk_size * tile_width;
+        let chunk_height = self.chunk_size * tile_height;
+
+        // chunks are culled like big tiles, whose tiles stick out as much as single ones
+        let (left, top, right, bottom) = self.tileset.overhang(self.tileset.opts.tile_size);
+        let span = |offset: i32, surface_size: u32, chunk_size: u32, overhang| {
+            cell_span(
+                offset as i64,
+                surface_size,
+                chunk_size,
+                chunk_size,
+                0,
+                overhang,
+            )
+        };
+
+        let columns = span(offset_x, surface.width(), chunk_width, (left, right));
+        let rows = span(offset_y, surface.height(), chunk_height, (top, bottom));
+
+        // offset tiles in resident chunks around those may reach into the buffer as well
+        let (before, after) = OFFSET_MARGIN;
+        let outer_columns = span(
+            offset_x,
+            surface.width(),
+            chunk_width,
+            (left + before, right + after),
+        );
+        let outer_rows = span(
+            offset_y,
+            surface.height(),
+            chunk_height,
+            (top + before, bottom + after),
+        );
+
+        let (chunk_width, chunk_height) = (chunk_width as i64, chunk_height as i64);
+
+        for chunk_y in outer_rows {
+            for chunk_x in outer_columns.clone() {
+                let inside = columns.contains(&chunk_x) && rows.contains(&chunk_y);
+                let (chunk_x, chunk_y) = (chunk_x as i32, chunk_y as i32);
+                let chunk_size = self.chunk_size;
+
+                if inside && !self.is_unbounded() {
+                    self.load_chunk(chunk_x, chunk_y);
+                }
+
+                let Some(chunk) = self.chunks.get(&(chunk_y, chunk_x)) else {
+                    continue;
+                };
+
+                let origin_x = offset_x as i64 + chunk_x as i64 * chunk_width;
+                let origin_y = offset_y as i64 + chunk_y as i64 * chunk_height;
+
+                for (i, tile) in chunk.tiles.iter().enumerate() {
+                    let (tx, ty) = (i as u32 % chunk_size, i as u32 / chunk_size);
+
+                    if tile.visible && (inside || tile.offset != (0, 0)) {
+                        self.tileset.render_map_tile(
+                            surface,
+                            tile,
+                            (
+                                (origin_x + (tx * tile_width) as i64) as i32,
+                                (origin_y + (ty * tile_height) as i64) as i32,
+                            ),
+                            None,
+                            &mut draw_tile_pixel,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{atlas, shade, tileset, Surface};
+    use alloc::rc::Rc;
+    use alloc::vec;
+    use core::cell::RefCell;
+
+    #[test]
+    fn loads_only_visible_chunks() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let loads = log.clone();
+
+        // 4x4 chunks of 2x2 tiles, so 8x8 pixels each
+        let mut map: ChunkedTilemap<_> = ChunkedTilemap::new(tileset(3), 4, 4, move |x, y| {
+            loads.borrow_mut().push((x, y));
+            vec![Tile::new(0); 16]
+        });
+
+        // world pixels 4..14 x 4..10 are visible
+        let mut surface = Surface::new(10, 6);
+        map.render(&mut surface, -4, -4);
+        log.borrow_mut().sort();
+        assert_eq!(*log.borrow(), vec![(0, 0), (0, 1), (1, 0), (1, 1)]);
+        assert!(surface.pixels.iter().all(|&pixel| pixel == shade(0)));
+
+        // resident chunks aren't loaded again
+        log.borrow_mut().clear();
+        map.render(&mut surface, -4, -4);
+        assert!(log.borrow().is_empty());
+
+        // world pixels -8..2 x -8..-2 are visible
+        map.render(&mut surface, 8, 8);
+        log.borrow_mut().sort();
+        assert_eq!(*log.borrow(), vec![(-1, -1), (0, -1)]);
+        assert_eq!(map.resident_chunks().count(), 4);
+        assert!(map.is_resident(0, -1));
+    }
+
+    #[test]
+    fn tiles_at_negative_positions() {
+        let mut map: ChunkedTilemap<_> =
+            ChunkedTilemap::new(tileset(3), 4, 4, |_, _| vec![Tile::new(1); 16]);
+
+        assert_eq!(map.chunk_of(-1, 4), (-1, 1));
+        map.set_tile(-1, 4, Tile::new(2));
+        assert_eq!(map.get_tile(-1, 4).unwrap().id, 2);
+    }
+
+    #[test]
+    fn unbounded_chunks() {
+        let mut map: ChunkedTilemap<_> = ChunkedTilemap::unbounded(tileset(3), 4);
+        assert!(map.is_unbounded());
+        assert_eq!(map.capacity(), usize::MAX);
+        assert!(map.get_tile(-100, 5).is_none());
+
+        map.set_tile(-100, 5, Tile::new(0));
+        map.set_tile(1_000_000, -7, Tile::new(0));
+        assert_eq!(map.get_tile(-100, 5).unwrap().id, 0);
+        assert_eq!(map.get_tile(-99, 5).unwrap().id, Tile::<()>::default().id);
+        assert_eq!(
+            map.resident_chunks().collect::<Vec<_>>(),
+            [(250_000, -2), (-25, 1)]
+        );
+
+        // only existing chunks are drawn, no new ones get created
+        let mut surface = Surface::new(4, 4);
+        map.render(&mut surface, 200, -10);
+        assert_eq!(surface.pixels[0], shade(0));
+        assert_eq!(map.resident_chunks().count(), 2);
+
+        // chunks are never unloaded to make room
+        for i in 0..50 {
+            map.set_tile(i * 4, 0, Tile::new(0));
+        }
+        assert_eq!(map.resident_chunks().count(), 52);
+
+        assert!(map.unload_chunk(-25, 1).is_some());
+        assert!(map.get_tile(-100, 5).is_none());
+    }
+
+    /// Positions and ids of the tiles of the 3x3 maps in the anchor tests.
+    const ANCHORED: [(u32, u32, u32); 4] = [(0, 0, 0), (1, 1, 1), (2, 2, 1), (2, 0, 1)];
+
+    /// Offsets at which the anchor tests render, some with only the tiles' overhang on the buffer.
+    const ANCHOR_OFFSETS: [(i32, i32); 6] = [(0, 0), (1, 1), (-2, 3), (-6, -5), (3, -5), (5, 0)];
+
+    fn anchored_reference(x: i32, y: i32) -> Vec<Color> {
+        let mut map = crate::Tilemap::new(3, 3, atlas());
+        map.fill_rect(0, 0, 3, 3, Tile::empty());
+        for (tx, ty, id) in ANCHORED {
+            map.set_tile(tx, ty, Tile::new(id));
+        }
+
+        let mut surface = Surface::new(5, 5);
+        map.render(&mut surface, x, y);
+        surface.pixels
+    }
+
+    #[test]
+    fn anchored_tiles_match_plain_map() {
+        let mut map: ChunkedTilemap<_> = ChunkedTilemap::unbounded(atlas(), 2);
+        for (x, y) in (0..4).flat_map(|y| (0..4).map(move |x| (x, y))) {
+            map.set_tile(x, y, Tile::empty());
+        }
+        for (x, y, id) in ANCHORED {
+            map.set_tile(x as i32, y as i32, Tile::new(id));
+        }
+
+        for (x, y) in ANCHOR_OFFSETS {
+            let mut surface = Surface::new(5, 5);
+            map.render(&mut surface, x, y);
+            assert_eq!(
+                surface.pixels,
+                anchored_reference(x, y),
+                "offset ({x}, {y})"
+            );
+        }
+    }
+
+    /// Tiles of the maps in the offset tests: one moved onto the buffer from outside it,
+    /// one moved off it and one in place.
+    fn offset_tiles() -> [(u32, u32, Tile); 3] {
+        [
+            (5, 0, Tile::new(1).with_offset(-7, -1)),
+            (0, 1, Tile::new(2).with_offset(0, -100)),
+            (1, 1, Tile::new(0)),
+        ]
+    }
+
+    fn offset_reference() -> Vec<Color> {
+        let mut map = crate::Tilemap::new(6, 2, atlas());
+        map.fill_rect(0, 0, 6, 2, Tile::empty());
+        for (x, y, tile) in offset_tiles() {
+            map.set_tile(x, y, tile);
+        }
+
+        let mut surface = Surface::new(4, 4);
+        map.render(&mut surface, 0, 0);
+        surface.pixels
+    }
+
+    #[test]
+    fn offset_tiles_match_plain_map() {
+        // 4x4 chunks of 2x2 tiles, the tile moved onto the buffer is in chunk (1, 0)
+        let mut map: ChunkedTilemap<_> = ChunkedTilemap::unbounded(atlas(), 4);
+        for (x, y) in (0..8).flat_map(|y| (0..4).map(move |x| (x, y))) {
+            map.set_tile(x, y, Tile::empty());
+        }
+        for (x, y, tile) in offset_tiles() {
+            map.set_tile(x as i32, y as i32, tile);
+        }
+
+        let mut surface = Surface::new(4, 4);
+        map.render(&mut surface, 0, 0);
+        assert_eq!(surface.pixels, offset_reference());
+
+        // the chunk is only drawn from while it's in memory
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let loads = log.clone();
+        let mut map: ChunkedTilemap<_> = ChunkedTilemap::new(atlas(), 4, 8, move |x, y| {
+            loads.borrow_mut().push((x, y));
+
+            let mut tiles = vec![Tile::empty(); 16];
+            if (x, y) == (1, 0) {
+                tiles[1] = offset_tiles()[0].2;
+            }
+            tiles
+        });
+
+        let mut surface = Surface::new(4, 4);
+        map.render(&mut surface, 0, 0);
+        assert!(!log.borrow().contains(&(1, 0)));
+        assert!(surface
+            .pixels
+            .iter()
+            .all(|&pixel| pixel == Color::default()));
+
+        map.load_chunk(1, 0);
+        map.render(&mut surface, 0, 0);
+        assert_eq!(surface.pixels[..4], offset_reference()[..4]);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::Tilemap;
+
+use core::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not};
+
+/// Set of built-in flags of a map tile, see [`Tile::flags`](crate::Tile::flags).
+///
+/// The flags don't change how tiles are rendered, they're there for the game's own logic.
+/// Bits 8 to 15 are free for user-defined flags, see [`TileFlags::user`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TileFlags(pub u16);
+
+impl TileFlags {
+    /// No flags.
+    pub const NONE: Self = Self(0);
+    /// The tile blocks movement.
+    pub const SOLID: Self = Self(1 << 0);
+    /// The tile blocks sight or light.
+    pub const OPAQUE: Self = Self(1 << 1);
+    /// Something happens when the tile is entered.
+    pub const TRIGGER: Self = Self(1 << 2);
+    /// The tile is animated.
+    pub const ANIMATED: Self = Self(1 << 3);
+
+    /// User-defined flag `n`, from 0 to 7.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is above 7.
+    #[inline]
+    pub const fn user(n: u32) -> Self {
+        assert!(n < 8, "user flag out of range");
+        Self(1 << (8 + n))
+    }
+
+    /// Whether no flags are set.
+    #[inline]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether all of the flags in `other` are set.
+    #[inline]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether any of the flags in `other` are set.
+    #[inline]
+    pub const fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Set the flags in `other`.
+    #[inline]
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    /// Clear the flags in `other`.
+    #[inline]
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+
+    /// Set or clear the flags in `other`.
+    #[inline]
+    pub fn set(&mut self, other: Self, value: bool) {
+        if value {
+            self.insert(other);
+        } else {
+            self.remove(other);
+        }
+    }
+}
+
+impl BitOr for TileFlags {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for TileFlags {
+    #[inline]
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for TileFlags {
+    type Output = Self;
+
+    #[inline]
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl BitAndAssign for TileFlags {
+    #[inline]
+    fn bitand_assign(&mut self, rhs: Self) {
+        self.0 &= rhs.0;
+    }
+}
+
+impl Not for TileFlags {
+    type Output = Self;
+
+    #[inline]
+    fn not(self) -> Self {
+        Self(!self.0)
+    }
+}
+
+impl<C, U, M> Tilemap<C, U, M> {
+    /// Whether the tile at (x, y) has all of the flags in `flags`.
+    ///
+    /// Returns `false` if the position is out of bounds.
+    #[inline]
+    pub fn has_flags(&self, x: u32, y: u32, flags: TileFlags) -> bool {
+        self.get_tile(x, y)
+            .is_some_and(|tile| tile.flags.contains(flags))
+    }
+
+    /// Whether the tile at (x, y) is [solid](TileFlags::SOLID).
+    ///
+    /// Returns `false` if the position is out of bounds.
+    #[inline]
+    pub fn is_solid(&self, x: u32, y: u32) -> bool {
+        self.has_flags(x, y, TileFlags::SOLID)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::tileset;
+    use crate::Tile;
+
+    #[test]
+    fn set_operations() {
+        let mut flags = TileFlags::SOLID | TileFlags::user(2);
+        assert!(flags.contains(TileFlags::SOLID));
+        assert!(!flags.contains(TileFlags::SOLID | TileFlags::OPAQUE));
+        assert!(flags.intersects(TileFlags::SOLID | TileFlags::OPAQUE));
+        assert!(!flags.intersects(TileFlags::NONE));
+        assert_eq!(TileFlags::user(2).0, 1 << 10);
+
+        flags.set(TileFlags::SOLID, false);
+        assert_eq!(flags, TileFlags::user(2));
+        flags.insert(TileFlags::TRIGGER);
+        assert_eq!(flags & TileFlags::TRIGGER, TileFlags::TRIGGER);
+        assert_eq!(!flags & TileFlags::user(2), TileFlags::NONE);
+
+        flags.remove(TileFlags::user(2) | TileFlags::TRIGGER);
+        assert!(flags.is_empty());
+        assert_eq!(Tile::<()>::default().flags, TileFlags::NONE);
+    }
+
+    #[test]
+    #[should_panic]
+    fn user_flag_out_of_range() {
+        let _ = TileFlags::user(8);
+    }
+
+    #[test]
+    fn map_queries() {
+        let mut map: Tilemap<_> = Tilemap::new(2, 1, tileset(1));
+        map.set_tile(
+            0,
+            0,
+            Tile::new(0).with_flags(TileFlags::SOLID | TileFlags::TRIGGER),
+        );
+
+        assert!(map.is_solid(0, 0));
+        assert!(!map.is_solid(1, 0));
+        assert!(!map.is_solid(5, 0));
+        assert!(map.has_flags(0, 0, TileFlags::TRIGGER));
+        assert!(map.has_flags(0, 0, TileFlags::SOLID | TileFlags::TRIGGER));
+        assert!(!map.has_flags(0, 0, TileFlags::ANIMATED));
+    }
+}
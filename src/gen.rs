@@ -0,0 +1,143 @@
+use crate::{Tile, TileId, Tilemap};
+
+use alloc::vec::Vec;
+
+/// Smallest width/height of a generated room, in tiles.
+const MIN_ROOM_SIZE: u32 = 3;
+/// Largest width/height of a generated room, in tiles.
+const MAX_ROOM_SIZE: u32 = 8;
+
+/// Fill the map with `wall` tiles and carve a layout of rectangular rooms
+/// connected by corridors out of `floor` tiles.
+///
+/// Up to `max_rooms` rooms are placed at random without overlapping, each one connected to the previous one,
+/// so all the floor tiles are reachable from each other. Rooms never touch the edges of the map.
+/// `rng` should return uniformly distributed random numbers.
+///
+/// Maps smaller than 5x5 tiles are just filled with walls.
+pub fn rooms_and_corridors<C, U, M, R>(
+    map: &mut Tilemap<C, U, M>,
+    wall: TileId,
+    floor: TileId,
+    max_rooms: u32,
+    mut rng: R,
+) where
+    U: Default + Clone,
+    R: FnMut() -> u32,
+{
+    let (width, height) = (map.width(), map.height());
+
+    map.fill_rect(0, 0, width, height, Tile::new(wall));
+
+    if width < MIN_ROOM_SIZE + 2 || height < MIN_ROOM_SIZE + 2 {
+        return;
+    }
+
+    let mut rooms: Vec<(u32, u32, u32, u32)> = Vec::new();
+
+    for _ in 0..max_rooms {
+        let w = random_in(&mut rng, MIN_ROOM_SIZE, MAX_ROOM_SIZE.min(width - 2));
+        let h = random_in(&mut rng, MIN_ROOM_SIZE, MAX_ROOM_SIZE.min(height - 2));
+        let x = random_in(&mut rng, 1, width - 1 - w);
+        let y = random_in(&mut rng, 1, height - 1 - h);
+
+        // Keep at least one wall tile between rooms.
+        let overlaps = rooms
+            .iter()
+            .any(|&(rx, ry, rw, rh)| x <= rx + rw && rx <= x + w && y <= ry + rh && ry <= y + h);
+
+        if overlaps {
+            continue;
+        }
+
+        map.fill_rect(x, y, w, h, Tile::new(floor));
+
+        if let Some(&(px, py, pw, ph)) = rooms.last() {
+            let (x0, y0) = (px + pw / 2, py + ph / 2);
+            let (x1, y1) = (x + w / 2, y + h / 2);
+
+            map.draw_line(x0, y0, x1, y0, Tile::new(floor));
+            map.draw_line(x1, y0, x1, y1, Tile::new(floor));
+        }
+
+        rooms.push((x, y, w, h));
+    }
+}
+
+/// Random number in `min..=max`.
+#[inline]
+fn random_in(rng: &mut impl FnMut() -> u32, min: u32, max: u32) -> u32 {
+    min + rng() % (max - min + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::tileset;
+
+    fn map(width: u32, height: u32) -> Tilemap<Vec<u8>> {
+        Tilemap::new(width, height, tileset(2))
+    }
+
+    #[test]
+    fn floors_are_connected() {
+        let mut map = map(40, 30);
+        let mut state = 12345u32;
+
+        // xorshift
+        rooms_and_corridors(&mut map, 1, 0, 12, || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        });
+
+        let floors = map.count_id(0);
+        assert!(floors > 30);
+
+        for x in 0..40 {
+            assert_eq!(map.get_tile(x, 0).unwrap().id, 1);
+            assert_eq!(map.get_tile(x, 29).unwrap().id, 1);
+        }
+
+        // flood fill from the first floor tile reaches all the others
+        let start = map.tiles().iter().position(|tile| tile.id == 0).unwrap();
+        let mut seen = alloc::vec![false; map.tiles().len()];
+        let mut stack = alloc::vec![start];
+        let mut reached = 0;
+        seen[start] = true;
+
+        while let Some(i) = stack.pop() {
+            reached += 1;
+            let (x, y) = map.index_to_xy(i);
+
+            for (nx, ny) in [
+                (x + 1, y),
+                (x.wrapping_sub(1), y),
+                (x, y + 1),
+                (x, y.wrapping_sub(1)),
+            ] {
+                if nx >= 40 || ny >= 30 {
+                    continue;
+                }
+
+                let j = map.xy_to_index(nx, ny);
+
+                if !seen[j] && map.tiles()[j].id == 0 {
+                    seen[j] = true;
+                    stack.push(j);
+                }
+            }
+        }
+
+        assert_eq!(reached, floors);
+    }
+
+    #[test]
+    fn small_maps_are_all_walls() {
+        let mut map = map(4, 4);
+        rooms_and_corridors(&mut map, 1, 0, 5, || 7);
+
+        assert_eq!(map.count_id(1), 16);
+    }
+}
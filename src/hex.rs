@@ -0,0 +1,297 @@
+use crate::{MapLayout, Tilemap};
+
+/// Orientation of the hexagons in a [`HexLayout`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HexOrientation {
+    /// Hexagons with a vertex on top, rows are staggered.
+    #[default]
+    PointyTop,
+    /// Hexagons with an edge on top, columns are staggered.
+    FlatTop,
+}
+
+/// Which rows (or columns) of a [`HexLayout`] are shifted by half a tile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HexStagger {
+    /// Odd rows (or columns) are shifted.
+    #[default]
+    Odd,
+    /// Even rows (or columns) are shifted.
+    Even,
+}
+
+/// Hexagonal grid layout with offset coordinates.
+///
+/// Every tile image is a hexagon inscribed in the tileset's tile size,
+/// same as in Tiled's "hexagonal" maps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HexLayout {
+    /// Orientation of the hexagons.
+    pub orientation: HexOrientation,
+    /// Which rows (or columns) are shifted.
+    pub stagger: HexStagger,
+    /// Length of the hexagon's flat sides in pixels (vertical sides for pointy top, horizontal for flat top).
+    ///
+    /// For a regular hexagon it's half of the tile's height (pointy top) or width (flat top).
+    pub side_length: u32,
+}
+
+impl HexLayout {
+    /// Create a new layout.
+    #[inline]
+    pub const fn new(orientation: HexOrientation, stagger: HexStagger, side_length: u32) -> Self {
+        Self {
+            orientation,
+            stagger,
+            side_length,
+        }
+    }
+
+    /// Whether the tile at (x, y) is shifted by half a tile.
+    #[inline]
+    pub const fn is_shifted(self, (x, y): (u32, u32)) -> bool {
+        match self.orientation {
+            HexOrientation::PointyTop => self.is_line_shifted(y),
+            HexOrientation::FlatTop => self.is_line_shifted(x),
+        }
+    }
+
+    /// Distance (horizontal, vertical) in pixels between neighboring columns and rows
+    /// for tiles of size `tile_size`.
+    #[inline]
+    pub const fn step(self, (tile_width, tile_height): (u32, u32)) -> (u32, u32) {
+        match self.orientation {
+            HexOrientation::PointyTop => (tile_width, (tile_height + self.side_length) / 2),
+            HexOrientation::FlatTop => ((tile_width + self.side_length) / 2, tile_height),
+        }
+    }
+
+    /// Pixel position of the tile at (x, y) relative to the map's origin for tiles of size `tile_size`.
+    #[inline]
+    pub const fn tile_offset(self, (x, y): (u32, u32), tile_size: (u32, u32)) -> (u32, u32) {
+        let (step_x, step_y) = self.step(tile_size);
+        let shifted = self.is_shifted((x, y));
+
+        match self.orientation {
+            HexOrientation::PointyTop => (
+                x * step_x + if shifted { tile_size.0 / 2 } else { 0 },
+                y * step_y,
+            ),
+            HexOrientation::FlatTop => (
+                x * step_x,
+                y * step_y + if shifted { tile_size.1 / 2 } else { 0 },
+            ),
+        }
+    }
+
+    /// Size (width x height) in pixels of a `width` x `height` map with tiles of size `tile_size`.
+    pub const fn pixel_size(
+        self,
+        (width, height): (u32, u32),
+        tile_size: (u32, u32),
+    ) -> (u32, u32) {
+        if width == 0 || height == 0 {
+            return (0, 0);
+        }
+
+        let (step_x, step_y) = self.step(tile_size);
+
+        // a single unshifted row (or column) doesn't stick out by half a tile
+        let lines = match self.orientation {
+            HexOrientation::PointyTop => height,
+            HexOrientation::FlatTop => width,
+        };
+        let has_shifted = lines > 1 || self.is_line_shifted(0);
+
+        match self.orientation {
+            HexOrientation::PointyTop => (
+                width * step_x + if has_shifted { tile_size.0 / 2 } else { 0 },
+                (height - 1) * step_y + tile_size.1,
+            ),
+            HexOrientation::FlatTop => (
+                (width - 1) * step_x + tile_size.0,
+                height * step_y + if has_shifted { tile_size.1 / 2 } else { 0 },
+            ),
+        }
+    }
+
+    /// Whether the pixel (x, y) relative to a tile's top-left corner is inside its hexagon.
+    pub fn contains(self, (x, y): (i64, i64), (tile_width, tile_height): (u32, u32)) -> bool {
+        // work as if the hexagon was pointy top
+        let ((x, y), (width, height)) = match self.orientation {
+            HexOrientation::PointyTop => ((x, y), (tile_width as i64, tile_height as i64)),
+            HexOrientation::FlatTop => ((y, x), (tile_height as i64, tile_width as i64)),
+        };
+
+        if x < 0 || y < 0 || x >= width || y >= height {
+            return false;
+        }
+
+        // doubled coordinates of the pixel's center, to stay in integers
+        let dx = (2 * x + 1 - width).abs();
+        let y = 2 * y + 1;
+        let cap = (height - self.side_length as i64).max(0);
+
+        y * width >= cap * dx && (2 * height - y) * width >= cap * dx
+    }
+
+    /// Whether row (or column) `i` is shifted by half a tile.
+    #[inline]
+    const fn is_line_shifted(self, i: u32) -> bool {
+        match self.stagger {
+            HexStagger::Odd => i % 2 == 1,
+            HexStagger::Even => i % 2 == 0,
+        }
+    }
+}
+
+impl<C, U, M> Tilemap<C, U, M> {
+    /// Get the position (x, y) of the hex tile under the pixel (x, y) of a buffer
+    /// the map was rendered to at pixel offset `(offset_x, offset_y)`.
+    ///
+    /// Returns `None` if the map doesn't use [`MapLayout::Hex`] or the pixel isn't over any of the map's tiles.
+    pub fn hex_tile_at(&self, x: i32, y: i32, offset_x: i32, offset_y: i32) -> Option<(u32, u32)> {
+        let MapLayout::Hex(hex) = self.layout() else {
+            return None;
+        };
+
+        let tile_size = self.tile_size();
+        let (step_x, step_y) = hex.step(tile_size);
+
+        if step_x == 0 || step_y == 0 {
+            return None;
+        }
+
+        let x = x as i64 - offset_x as i64;
+        let y = y as i64 - offset_y as i64;
+
+        // along the staggered axis tiles overlap the previous row (or column)
+        let (along, across, step_along, step_across, half) = match hex.orientation {
+            HexOrientation::PointyTop => (y, x, step_y, step_x, tile_size.0 / 2),
+            HexOrientation::FlatTop => (x, y, step_x, step_y, tile_size.1 / 2),
+        };
+
+        let line = along.div_euclid(step_along as i64);
+
+        for line in [line, line - 1] {
+            if line < 0 || line > u32::MAX as i64 {
+                continue;
+            }
+
+            let shift = if hex.is_line_shifted(line as u32) {
+                half as i64
+            } else {
+                0
+            };
+            let other = (across - shift).div_euclid(step_across as i64);
+
+            if other < 0 || other > u32::MAX as i64 {
+                continue;
+            }
+
+            let pos = match hex.orientation {
+                HexOrientation::PointyTop => (other as u32, line as u32),
+                HexOrientation::FlatTop => (line as u32, other as u32),
+            };
+
+            if pos.0 >= self.width() || pos.1 >= self.height() {
+                continue;
+            }
+
+            let (left, top) = hex.tile_offset(pos, tile_size);
+
+            if hex.contains((x - left as i64, y - top as i64), tile_size) {
+                return Some(pos);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{shade, tileset_from, Surface};
+    use crate::{Tile, TilesetOptions};
+    use alloc::vec::Vec;
+
+    /// Map with a single 4x4 tile filled with [`shade`] of 0.
+    fn map(width: u32, height: u32, hex: HexLayout) -> Tilemap<Vec<u8>> {
+        let tileset = tileset_from(&[shade(0); 16], 4, TilesetOptions::new(4, 4));
+        Tilemap::new(width, height, tileset).with_layout(MapLayout::Hex(hex))
+    }
+
+    /// Check that every pixel inside a tile's hexagon is picked as that tile.
+    fn assert_picking_matches_placement(map: &Tilemap<Vec<u8>>, hex: HexLayout) {
+        for ty in 0..map.height() {
+            for tx in 0..map.width() {
+                let (left, top, _, _) = map.tile_screen_rect(tx, ty, 0, 0).unwrap();
+
+                for y in 0..4 {
+                    for x in 0..4 {
+                        if hex.contains((x, y), (4, 4)) {
+                            let picked = map.hex_tile_at(left + x as i32, top + y as i32, 0, 0);
+                            assert_eq!(picked, Some((tx, ty)), "{tx}, {ty} at {x}, {y}");
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pointy_top() {
+        // side 2, so rows are 3 pixels apart
+        let hex = HexLayout::new(HexOrientation::PointyTop, HexStagger::Odd, 2);
+        let mut map = map(2, 2, hex);
+        map.fill_rect(0, 0, 2, 2, Tile::new(0));
+
+        assert_eq!(map.pixel_size(), (10, 7));
+        assert_eq!(map.tile_screen_rect(0, 1, 0, 0), Some((2, 3, 4, 4)));
+        assert_eq!(map.tile_screen_rect(1, 1, 5, 5), Some((11, 8, 4, 4)));
+
+        let mut surface = Surface::new(10, 7);
+        map.render(&mut surface, 0, 0);
+        assert_eq!(surface.at(9, 4), shade(0));
+        assert_eq!(surface.at(9, 0).a, 0);
+
+        assert_eq!(map.hex_tile_at(1, 1, 0, 0), Some((0, 0)));
+        assert_eq!(map.hex_tile_at(5, 1, 0, 0), Some((1, 0)));
+        assert_eq!(map.hex_tile_at(3, 3, 0, 0), Some((0, 1)));
+        assert_eq!(map.hex_tile_at(2, 2, 0, 0), Some((0, 0)));
+        assert_eq!(map.hex_tile_at(8, 6, 0, 0), Some((1, 1)));
+        assert_eq!(map.hex_tile_at(13, 11, 5, 5), Some((1, 1)));
+        assert_eq!(map.hex_tile_at(0, 6, 0, 0), None);
+        assert_eq!(map.hex_tile_at(0, 0, 0, 0), None);
+        assert_picking_matches_placement(&map, hex);
+
+        // the shifted row reaches into the surface
+        assert_eq!(map.visible_range(-6, 0, 4, 7), (1..2, 0..2));
+
+        assert_eq!(self::map(2, 1, hex).pixel_size(), (8, 4));
+    }
+
+    #[test]
+    fn flat_top() {
+        let hex = HexLayout::new(HexOrientation::FlatTop, HexStagger::Even, 2);
+        let map = map(3, 2, hex);
+
+        assert_eq!(map.pixel_size(), (10, 10));
+        assert_eq!(map.tile_screen_rect(0, 0, 0, 0), Some((0, 2, 4, 4)));
+        assert_eq!(map.tile_screen_rect(1, 1, 0, 0), Some((3, 4, 4, 4)));
+        assert_picking_matches_placement(&map, hex);
+    }
+
+    #[test]
+    fn picking_needs_hex_layout() {
+        let hex = HexLayout::new(HexOrientation::PointyTop, HexStagger::Odd, 2);
+        let mut map = map(2, 2, hex);
+        map.set_layout(MapLayout::Orthogonal);
+
+        assert_eq!(map.hex_tile_at(1, 1, 0, 0), None);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::{BlitOptions, Color, Rotation, Tile, TileId, Tilemap, Tileset};
+
+use alloc::vec::Vec;
+
+/// Tiles of a [`Tilemap`] without its tileset.
+///
+/// Meant for saving level data separately from the (usually shared) tileset.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IdGrid {
+    /// Map's width in tiles.
+    pub width: u32,
+    /// Map's height in tiles.
+    pub height: u32,
+    /// Tile ids, row by row.
+    pub ids: Vec<TileId>,
+    /// Tile colors, row by row. `None` if all of them are white.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub colors: Option<Vec<Color>>,
+    /// Tile blit options, row by row. `None` if all of them are [`BlitOptions::None`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub opts: Option<Vec<BlitOptions>>,
+    /// Tile rotations, row by row. `None` if none of the tiles are rotated.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rotations: Option<Vec<Rotation>>,
+}
+
+impl<C, U, M> Tilemap<C, U, M> {
+    /// Get the map's tiles without the tileset.
+    pub fn to_id_grid(&self) -> IdGrid {
+        let colors = self
+            .tiles()
+            .iter()
+            .any(|tile| tile.color != Color::new(255, 255, 255, 255))
+            .then(|| self.tiles().iter().map(|tile| tile.color).collect());
+
+        let opts = self
+            .tiles()
+            .iter()
+            .any(|tile| tile.opts != BlitOptions::None)
+            .then(|| self.tiles().iter().map(|tile| tile.opts).collect());
+
+        let rotations = self
+            .tiles()
+            .iter()
+            .any(|tile| tile.rotation != Rotation::None)
+            .then(|| self.tiles().iter().map(|tile| tile.rotation).collect());
+
+        IdGrid {
+            width: self.width(),
+            height: self.height(),
+            ids: self.tiles().iter().map(|tile| tile.id).collect(),
+            colors,
+            opts,
+            rotations,
+        }
+    }
+}
+
+impl<C, U, M> Tilemap<C, U, M>
+where
+    U: Default + Clone,
+{
+    /// Construct a map from tiles saved with [`to_id_grid`](Self::to_id_grid) and a tileset.
+    ///
+    /// Returns `None` if the amount of ids, colors, blit options or rotations doesn't match the grid's size.
+    pub fn from_id_grid(grid: IdGrid, tileset: Tileset<C, M>) -> Option<Self> {
+        let len = (grid.width as usize).checked_mul(grid.height as usize)?;
+
+        if grid.ids.len() != len
+            || grid
+                .colors
+                .as_ref()
+                .is_some_and(|colors| colors.len() != len)
+            || grid.opts.as_ref().is_some_and(|opts| opts.len() != len)
+            || grid
+                .rotations
+                .as_ref()
+                .is_some_and(|rotations| rotations.len() != len)
+        {
+            return None;
+        }
+
+        let mut map = Self::new(grid.width, grid.height, tileset);
+
+        for (i, tile) in map.tiles_mut().iter_mut().enumerate() {
+            *tile = Tile::new(grid.ids[i]);
+
+            if let Some(colors) = &grid.colors {
+                tile.color = colors[i];
+            }
+
+            if let Some(opts) = &grid.opts {
+                tile.opts = opts[i];
+            }
+
+            if let Some(rotations) = &grid.rotations {
+                tile.rotation = rotations[i];
+            }
+        }
+
+        Some(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::tileset;
+
+    #[test]
+    fn id_grid_round_trip() {
+        let mut map: Tilemap<_> = Tilemap::new(3, 2, tileset(3));
+        for (i, tile) in map.tiles_mut().iter_mut().enumerate() {
+            *tile = Tile::new(i as TileId % 3);
+        }
+
+        let grid = map.to_id_grid();
+        assert!(grid.colors.is_none());
+        assert!(grid.opts.is_none());
+        let restored: Tilemap<_> = Tilemap::from_id_grid(grid, tileset(3)).unwrap();
+        assert_eq!(restored.tiles(), map.tiles());
+
+        map.set_tile(
+            1,
+            1,
+            Tile::new(2)
+                .with_color(Color::new(1, 2, 3, 4))
+                .with_blit_options(BlitOptions::FlipBoth),
+        );
+        let mut grid = map.to_id_grid();
+        let restored: Tilemap<_> = Tilemap::from_id_grid(grid.clone(), tileset(3)).unwrap();
+        assert_eq!(restored.tiles(), map.tiles());
+
+        grid.ids.pop();
+        assert!(Tilemap::<_>::from_id_grid(grid, tileset(3)).is_none());
+    }
+}
@@ -0,0 +1,266 @@
+use crate::{MapLayout, Tilemap};
+
+use core::ops::Range;
+
+/// Isometric (diamond) grid layout.
+///
+/// Columns go down and to the right, rows go down and to the left.
+/// Tiles are drawn with their bottom-left corner at the bottom-left corner of the diamond's bounding box,
+/// so tiles taller than the base stick out upwards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IsoLayout {
+    /// Width of the diamond in pixels.
+    pub base_width: u32,
+    /// Height of the diamond in pixels.
+    pub base_height: u32,
+}
+
+impl IsoLayout {
+    /// Create a new layout with a `base_width` x `base_height` diamond.
+    #[inline]
+    pub const fn new(base_width: u32, base_height: u32) -> Self {
+        Self {
+            base_width,
+            base_height,
+        }
+    }
+
+    /// Pixel position of the tile at (x, y) relative to the map's origin
+    /// for a map `map_height` tiles high with tiles of size `tile_size`.
+    #[inline]
+    pub const fn tile_offset(
+        self,
+        (x, y): (u32, u32),
+        map_height: u32,
+        (_, tile_height): (u32, u32),
+    ) -> (u32, u32) {
+        (
+            (x + map_height - 1 - y) * self.base_width / 2,
+            (x + y) * self.base_height / 2 + self.lift(tile_height) + self.base_height
+                - tile_height,
+        )
+    }
+
+    /// Size (width x height) in pixels of a `width` x `height` map with tiles of size `tile_size`.
+    pub const fn pixel_size(
+        self,
+        (width, height): (u32, u32),
+        tile_size: (u32, u32),
+    ) -> (u32, u32) {
+        if width == 0 || height == 0 {
+            return (0, 0);
+        }
+
+        (
+            (width + height) * self.base_width / 2,
+            (width + height - 2) * self.base_height / 2 + self.base_height + self.lift(tile_size.1),
+        )
+    }
+
+    /// Position (x, y) of the diamond containing the pixel (x, y) relative to the map's origin
+    /// for a map `map_height` tiles high with tiles of size `tile_size`.
+    ///
+    /// The position may be outside of the map. Returns `None` if the base size is 0.
+    pub fn tile_at(
+        self,
+        (x, y): (i64, i64),
+        map_height: u32,
+        (_, tile_height): (u32, u32),
+    ) -> Option<(i64, i64)> {
+        let (width, height) = (self.base_width as i64, self.base_height as i64);
+
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        // doubled coordinates of the pixel's center relative to the top vertex of the tile at (0, 0)
+        let x = 2 * x + 1 - map_height as i64 * width;
+        let y = 2 * (y - self.lift(tile_height) as i64) + 1;
+
+        let (along, across) = (y * width, x * height);
+        let denom = 2 * width * height;
+
+        Some((
+            (along + across).div_euclid(denom),
+            (along - across).div_euclid(denom),
+        ))
+    }
+
+    /// How far tiles taller than the base stick out above the diamond.
+    #[inline]
+    const fn lift(self, tile_height: u32) -> u32 {
+        tile_height.saturating_sub(self.base_height)
+    }
+}
+
+impl<C, U, M> Tilemap<C, U, M> {
+    /// Get the position (x, y) of the isometric tile whose diamond is under the pixel (x, y) of a buffer
+    /// the map was rendered to at pixel offset `(offset_x, offset_y)`.
+    ///
+    /// Parts of tiles sticking out of their diamond aren't taken into account.
+    /// Returns `None` if the map doesn't use [`MapLayout::Isometric`] or the pixel isn't over any of the map's tiles.
+    pub fn iso_tile_at(&self, x: i32, y: i32, offset_x: i32, offset_y: i32) -> Option<(u32, u32)> {
+        let MapLayout::Isometric(iso) = self.layout() else {
+            return None;
+        };
+
+        let (tx, ty) = iso.tile_at(
+            (x as i64 - offset_x as i64, y as i64 - offset_y as i64),
+            self.height(),
+            self.tile_size(),
+        )?;
+
+        (tx >= 0 && ty >= 0 && tx < self.width() as i64 && ty < self.height() as i64)
+            .then_some((tx as u32, ty as u32))
+    }
+
+    /// [`visible_range`](Tilemap::visible_range) for the isometric layout.
+    ///
+    /// The ranges cover every tile that may intersect the surface, which includes some that don't.
+    pub(crate) fn iso_visible_range(
+        &self,
+        iso: IsoLayout,
+        offset_x: i32,
+        offset_y: i32,
+        surface_width: u32,
+        surface_height: u32,
+    ) -> (Range<u32>, Range<u32>) {
+        let tile_size = self.tile_size();
+
+        // the surface in map pixels, grown by a tile so tiles sticking out of their diamonds are included
+        let (left, top, right, bottom) = self.overhang();
+        let margin_x = tile_size.0.max(iso.base_width) as i64 + left.max(right) as i64;
+        let margin_y = tile_size.1.max(iso.base_height) as i64 + top.max(bottom) as i64;
+        let left = -(offset_x as i64) - margin_x;
+        let top = -(offset_y as i64) - margin_y;
+        let right = surface_width as i64 - offset_x as i64 + margin_x;
+        let bottom = surface_height as i64 - offset_y as i64 + margin_y;
+
+        let corner = |x, y| iso.tile_at((x, y), self.height(), tile_size);
+
+        let (Some((x0, _)), Some((x1, _)), Some((_, y0)), Some((_, y1))) = (
+            corner(left, top),
+            corner(right, bottom),
+            corner(right, top),
+            corner(left, bottom),
+        ) else {
+            return (0..0, 0..0);
+        };
+
+        let range = |start: i64, end: i64, map_size: u32| {
+            let start = start.clamp(0, map_size as i64);
+            let end = (end + 1).clamp(start, map_size as i64);
+
+            start as u32..end as u32
+        };
+
+        (range(x0, x1, self.width()), range(y0, y1, self.height()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{shade, tileset_from, Surface};
+    use crate::{Color, Tile, TilesetOptions};
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    /// Isometric map on a 4x2 grid with a single `tile_width` x `tile_height` tile filled with [`shade`] of 0.
+    fn map(width: u32, height: u32, tile_width: u32, tile_height: u32) -> Tilemap<Vec<u8>> {
+        let pixels = vec![shade(0); (tile_width * tile_height) as usize];
+        let tileset = tileset_from(
+            &pixels,
+            tile_width,
+            TilesetOptions::new(tile_width, tile_height),
+        );
+
+        Tilemap::new(width, height, tileset).with_layout(MapLayout::Isometric(IsoLayout::new(4, 2)))
+    }
+
+    #[test]
+    fn placement_and_picking() {
+        let mut map = map(2, 3, 4, 2);
+
+        assert_eq!(map.pixel_size(), (10, 5));
+        assert_eq!(map.tile_screen_rect(0, 0, 0, 0), Some((4, 0, 4, 2)));
+        assert_eq!(map.tile_screen_rect(1, 0, 0, 0), Some((6, 1, 4, 2)));
+        assert_eq!(map.tile_screen_rect(0, 2, 0, 0), Some((0, 2, 4, 2)));
+        assert_eq!(map.tile_screen_rect(1, 2, 0, 0), Some((2, 3, 4, 2)));
+
+        // the middle pixels of each diamond
+        for ty in 0..3 {
+            for tx in 0..2 {
+                let (left, top, _, _) = map.tile_screen_rect(tx, ty, 3, 7).unwrap();
+
+                for (x, y) in [(left + 1, top), (left + 2, top + 1), (left + 1, top + 1)] {
+                    assert_eq!(map.iso_tile_at(x, y, 3, 7), Some((tx, ty)));
+                }
+            }
+        }
+        assert_eq!(map.iso_tile_at(0, 0, 0, 0), None);
+
+        // tiles in front are drawn over the ones behind them
+        map.fill_rect(
+            0,
+            0,
+            2,
+            3,
+            Tile::new(0).with_color(Color::new(255, 255, 255, 255)),
+        );
+
+        let mut surface = Surface::new(10, 5);
+        map.render(&mut surface, 0, 0);
+        assert_eq!(surface.at(5, 0), shade(0));
+
+        map.set_layout(MapLayout::Orthogonal);
+        assert_eq!(map.iso_tile_at(1, 0, 0, 0), None);
+    }
+
+    #[test]
+    fn visible_range_covers_visible_tiles() {
+        let map = map(2, 3, 4, 2);
+
+        for offset_x in -12..12 {
+            for offset_y in -8..8 {
+                let (columns, rows) = map.visible_range(offset_x, offset_y, 3, 2);
+
+                for ty in 0..3 {
+                    for tx in 0..2 {
+                        if map.tile_visible(tx, ty, offset_x, offset_y, 3, 2) {
+                            assert!(
+                                columns.contains(&tx) && rows.contains(&ty),
+                                "{offset_x}, {offset_y}: {tx}, {ty} not in {columns:?}, {rows:?}"
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tall_tiles() {
+        // 4x6 tiles stick out 4 pixels above their 4x2 diamond
+        let mut map = map(2, 2, 4, 6);
+
+        assert_eq!(map.pixel_size(), (8, 8));
+        assert_eq!(map.tile_screen_rect(0, 0, 0, 0), Some((2, 0, 4, 6)));
+        assert_eq!(map.tile_screen_rect(1, 1, 0, 0), Some((2, 2, 4, 6)));
+
+        // picking uses the diamonds, not the images
+        assert_eq!(map.iso_tile_at(4, 7, 0, 0), Some((1, 1)));
+        assert_eq!(map.iso_tile_at(4, 4, 0, 0), Some((0, 0)));
+
+        map.set_tile(
+            1,
+            1,
+            Tile::new(0).with_color(Color::new(255, 255, 255, 255)),
+        );
+
+        let mut surface = Surface::new(8, 8);
+        map.render(&mut surface, 0, 0);
+        assert_eq!(surface.at(3, 3), shade(0));
+    }
+}
@@ -0,0 +1,449 @@
+use crate::{draw_layer_pixel, parallax_position, BufferMut, Color, Tile, Tileset, OFFSET_MARGIN};
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// A single layer of a [`LayeredTilemap`].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Layer<U = ()> {
+    tiles: Vec<Tile<U>>,
+    /// Whether the layer gets rendered.
+    pub visible: bool,
+    /// Opacity of the whole layer, multiplied with the alpha of its pixels.
+    pub opacity: u8,
+    /// How fast the layer scrolls (horizontally, vertically) relative to the camera
+    /// in [`LayeredTilemap::render_parallax`]. Background layers use factors below 1.
+    #[cfg_attr(feature = "serde", serde(default = "default_parallax"))]
+    pub parallax: (f32, f32),
+}
+
+#[cfg(feature = "serde")]
+#[inline]
+const fn default_parallax() -> (f32, f32) {
+    (1., 1.)
+}
+
+impl<U> Layer<U> {
+    /// Layer's tiles, row by row.
+    #[inline]
+    pub fn tiles(&self) -> &[Tile<U>] {
+        &self.tiles
+    }
+
+    /// Layer's tiles, row by row (mutable).
+    #[inline]
+    pub fn tiles_mut(&mut self) -> &mut [Tile<U>] {
+        &mut self.tiles
+    }
+}
+
+/// A map with multiple layers of tiles sharing one tileset and size.
+///
+/// Layers are rendered back-to-front, starting from layer 0.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LayeredTilemap<C, U = ()> {
+    tileset: Tileset<C>,
+    layers: Vec<Layer<U>>,
+    width: u32,
+    height: u32,
+}
+
+impl<C, U> LayeredTilemap<C, U> {
+    /// Construct a new map without any layers.
+    /// `width` and `height` are map's size in tiles.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the total amount of tiles doesn't fit into a `u32`.
+    #[inline]
+    pub fn new(width: u32, height: u32, tileset: Tileset<C>) -> Self {
+        width.checked_mul(height).expect("map too large");
+
+        Self {
+            tileset,
+            layers: Vec::new(),
+            width,
+            height,
+        }
+    }
+
+    /// Map's width in tiles.
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Map's height in tiles.
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Map's tileset.
+    #[inline]
+    pub fn tileset(&self) -> &Tileset<C> {
+        &self.tileset
+    }
+
+    /// Amount of layers.
+    #[inline]
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Get a layer.
+    #[inline]
+    pub fn layer(&self, layer: usize) -> Option<&Layer<U>> {
+        self.layers.get(layer)
+    }
+
+    /// Get a mutable ref to a layer.
+    #[inline]
+    pub fn layer_mut(&mut self, layer: usize) -> Option<&mut Layer<U>> {
+        self.layers.get_mut(layer)
+    }
+
+    /// Remove a layer, shifting the layers above it down.
+    ///
+    /// Returns `None` if the layer doesn't exist.
+    #[inline]
+    pub fn remove_layer(&mut self, layer: usize) -> Option<Layer<U>> {
+        (layer < self.layers.len()).then(|| self.layers.remove(layer))
+    }
+
+    /// Get a tile at (x, y) in a layer.
+    ///
+    /// Returns `None` if the layer doesn't exist or the position is out of bounds.
+    #[inline]
+    pub fn get_tile(&self, layer: usize, x: u32, y: u32) -> Option<&Tile<U>> {
+        let i = self.index_of(x, y)?;
+        self.layers.get(layer)?.tiles.get(i)
+    }
+
+    /// Get a mutable ref to a tile at (x, y) in a layer.
+    ///
+    /// Returns `None` if the layer doesn't exist or the position is out of bounds.
+    #[inline]
+    pub fn get_mut_tile(&mut self, layer: usize, x: u32, y: u32) -> Option<&mut Tile<U>> {
+        let i = self.index_of(x, y)?;
+        self.layers.get_mut(layer)?.tiles.get_mut(i)
+    }
+
+    /// Set a tile at (x, y) in a layer.
+    ///
+    /// Does nothing if the layer doesn't exist or the position is out of bounds.
+    #[inline]
+    pub fn set_tile(&mut self, layer: usize, x: u32, y: u32, tile: Tile<U>) {
+        if let Some(t) = self.get_mut_tile(layer, x, y) {
+            *t = tile;
+        }
+    }
+
+    #[inline]
+    fn index_of(&self, x: u32, y: u32) -> Option<usize> {
+        if x < self.width && y < self.height {
+            Some((y * self.width + x) as usize)
+        } else {
+            None
+        }
+    }
+}
+
+impl<C, U> LayeredTilemap<C, U>
+where
+    U: Default + Clone,
+{
+    /// Add a new visible and fully opaque layer of default tiles on top of the others.
+    ///
+    /// Returns the index of the new layer.
+    pub fn add_layer(&mut self) -> usize {
+        self.layers.push(Layer {
+            tiles: vec![Tile::default(); (self.width * self.height) as usize],
+            visible: true,
+            opacity: 255,
+            parallax: (1., 1.),
+        });
+
+        self.layers.len() - 1
+    }
+}
+
+impl<C, U> LayeredTilemap<C, U>
+where
+    C: AsRef<[u8]>,
+{
+    /// Render the visible layers back-to-front onto a buffer at pixel offset `(offset_x, offset_y)`,
+    /// drawing each one over the ones below.
+    ///
+    /// Tiles are drawn with their [`blend`](Tile::blend) mode, except that the normal mode alpha blends
+    /// so that the layers below show through.
+    pub fn render(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+    ) {
+        for layer in &self.layers {
+            self.render_layer(surface, layer, offset_x, offset_y);
+        }
+    }
+
+    /// Render the visible layers like [`render`](Self::render) as seen from camera position `(camera_x, camera_y)`,
+    /// with each layer scrolled by its [parallax](Layer::parallax) factor.
+    ///
+    /// A layer with a factor of 1 is drawn at pixel offset `(-camera_x, -camera_y)`, same as the map's origin
+    /// in [`Camera`](crate::Camera)'s world coordinates.
+    pub fn render_parallax(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        camera_x: i32,
+        camera_y: i32,
+    ) {
+        for layer in &self.layers {
+            let offset_x = -parallax_position(camera_x, layer.parallax.0);
+            let offset_y = -parallax_position(camera_y, layer.parallax.1);
+
+            self.render_layer(surface, layer, offset_x, offset_y);
+        }
+    }
+
+    /// Render a layer, if it's visible.
+    ///
+    /// Only the tiles intersecting the buffer are drawn.
+    fn render_layer(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        layer: &Layer<U>,
+        offset_x: i32,
+        offset_y: i32,
+    ) {
+        if !layer.visible || layer.opacity == 0 {
+            return;
+        }
+
+        let (tile_width, tile_height) = self.tileset.opts.tile_size;
+        let offset = (offset_x, offset_y);
+        let surface_size = (surface.width(), surface.height());
+        let map_size = (self.width, self.height);
+
+        let (columns, rows) =
+            self.tileset
+                .map_visible_range(offset, surface_size, map_size, (0, 0));
+
+        // offset tiles around those may reach into the buffer as well
+        let (outer_columns, outer_rows) =
+            self.tileset
+                .map_visible_range(offset, surface_size, map_size, OFFSET_MARGIN);
+
+        for (tx, ty) in outer_rows.flat_map(|ty| outer_columns.clone().map(move |tx| (tx, ty))) {
+            let tile = &layer.tiles[(ty * self.width + tx) as usize];
+            let inside = columns.contains(&tx) && rows.contains(&ty);
+
+            if !tile.visible || !(inside || tile.offset != (0, 0)) {
+                continue;
+            }
+
+            self.tileset.render_map_tile(
+                surface,
+                tile,
+                (
+                    offset_x + (tx * tile_width) as i32,
+                    offset_y + (ty * tile_height) as i32,
+                ),
+                None,
+                &mut |dest, src, tile| draw_layer_pixel(dest, src, tile, layer.opacity),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{atlas, shade, tileset, Surface};
+
+    #[test]
+    fn layers_render_back_to_front() {
+        let mut map: LayeredTilemap<_> = LayeredTilemap::new(2, 1, tileset(3));
+        assert_eq!(map.add_layer(), 0);
+        assert_eq!(map.add_layer(), 1);
+
+        map.set_tile(0, 0, 0, Tile::new(0));
+        map.set_tile(0, 1, 0, Tile::new(0));
+        map.set_tile(1, 0, 0, Tile::new(2));
+        map.layer_mut(1).unwrap().tiles_mut()[1].visible = false;
+
+        let mut surface = Surface::new(4, 2);
+        map.render(&mut surface, 0, 0);
+        assert_eq!(surface.at(0, 0), shade(2));
+        assert_eq!(surface.at(2, 0), shade(0));
+
+        map.layer_mut(1).unwrap().opacity = 128;
+
+        let mut surface = Surface::new(4, 2);
+        map.render(&mut surface, 0, 0);
+        let blended = surface.at(0, 0);
+        assert!(blended.r > 10 && blended.r < 30, "{blended:?}");
+
+        map.layer_mut(1).unwrap().visible = false;
+
+        let mut surface = Surface::new(4, 2);
+        map.render(&mut surface, 0, 0);
+        assert_eq!(surface.at(0, 0), shade(0));
+    }
+
+    #[test]
+    fn remove_layer() {
+        let mut map: LayeredTilemap<_> = LayeredTilemap::new(2, 1, tileset(3));
+        map.add_layer();
+        map.add_layer();
+        map.set_tile(1, 0, 0, Tile::new(2));
+
+        assert!(map.remove_layer(5).is_none());
+        assert!(map.remove_layer(0).is_some());
+        assert_eq!(map.layer_count(), 1);
+        assert_eq!(map.get_tile(0, 0, 0).unwrap().id, 2);
+        assert!(map.get_tile(0, 2, 0).is_none());
+    }
+
+    #[test]
+    fn layers_use_tile_blend_modes() {
+        let mut map: LayeredTilemap<_> = LayeredTilemap::new(1, 1, tileset(1));
+        map.add_layer();
+        map.add_layer();
+        map.set_tile(0, 0, 0, Tile::new(0));
+        map.set_tile(1, 0, 0, Tile::new(0).with_blend(crate::BlendMode::Additive));
+
+        let mut surface = Surface::new(2, 2);
+        map.render(&mut surface, 0, 0);
+        assert!(surface.at(0, 0).r > shade(0).r, "{:?}", surface.at(0, 0));
+
+        map.layer_mut(1).unwrap().opacity = 0;
+
+        let mut surface = Surface::new(2, 2);
+        map.render(&mut surface, 0, 0);
+        assert_eq!(surface.at(0, 0), shade(0));
+    }
+
+    #[test]
+    fn culled_layers_match_full_render() {
+        let mut map: LayeredTilemap<_> = LayeredTilemap::new(5, 3, tileset(4));
+        let mut reference = crate::Tilemap::new(5, 3, tileset(4));
+        map.add_layer();
+
+        for y in 0..3 {
+            for x in 0..5 {
+                let tile = Tile::new((x + y) % 4);
+                map.set_tile(0, x, y, tile);
+                reference.set_tile(x, y, tile);
+            }
+        }
+
+        for (x, y) in [(0, 0), (-3, -1), (-9, 2), (3, -5), (-12, -7), (20, 0)] {
+            let mut surface = Surface::new(4, 3);
+            map.render(&mut surface, x, y);
+
+            let mut expected = Surface::new(4, 3);
+            reference.render(&mut expected, x, y);
+            assert_eq!(surface.pixels, expected.pixels, "offset ({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn parallax() {
+        let mut map: LayeredTilemap<_> = LayeredTilemap::new(2, 1, tileset(2));
+        let layer = map.add_layer();
+        map.set_tile(layer, 0, 0, Tile::new(0));
+        map.set_tile(layer, 1, 0, Tile::new(1));
+        map.layer_mut(layer).unwrap().parallax = (0.5, 0.);
+
+        let mut surface = Surface::new(6, 4);
+        map.render_parallax(&mut surface, 4, 3);
+        let mut expected = Surface::new(6, 4);
+        map.render(&mut expected, -2, 0);
+        assert_eq!(surface.pixels, expected.pixels);
+
+        map.layer_mut(layer).unwrap().parallax = (1., 1.);
+
+        let mut surface = Surface::new(6, 4);
+        map.render_parallax(&mut surface, -1, 1);
+        let mut expected = Surface::new(6, 4);
+        map.render(&mut expected, 1, -1);
+        assert_eq!(surface.pixels, expected.pixels);
+    }
+
+    /// Positions and ids of the tiles of the 3x3 maps in the anchor tests.
+    const ANCHORED: [(u32, u32, u32); 4] = [(0, 0, 0), (1, 1, 1), (2, 2, 1), (2, 0, 1)];
+
+    /// Offsets at which the anchor tests render, some with only the tiles' overhang on the buffer.
+    const ANCHOR_OFFSETS: [(i32, i32); 6] = [(0, 0), (1, 1), (-2, 3), (-6, -5), (3, -5), (5, 0)];
+
+    fn anchored_reference(x: i32, y: i32) -> Vec<Color> {
+        let mut map = crate::Tilemap::new(3, 3, atlas());
+        map.fill_rect(0, 0, 3, 3, Tile::empty());
+        for (tx, ty, id) in ANCHORED {
+            map.set_tile(tx, ty, Tile::new(id));
+        }
+
+        let mut surface = Surface::new(5, 5);
+        map.render(&mut surface, x, y);
+        surface.pixels
+    }
+
+    #[test]
+    fn anchored_tiles_match_plain_map() {
+        let mut map: LayeredTilemap<_> = LayeredTilemap::new(3, 3, atlas());
+        map.add_layer();
+        for (x, y, id) in ANCHORED {
+            map.set_tile(0, x, y, Tile::new(id));
+        }
+
+        for (x, y) in ANCHOR_OFFSETS {
+            let mut surface = Surface::new(5, 5);
+            map.render(&mut surface, x, y);
+            assert_eq!(
+                surface.pixels,
+                anchored_reference(x, y),
+                "offset ({x}, {y})"
+            );
+        }
+    }
+
+    /// Tiles of the maps in the offset tests: one moved onto the buffer from outside it,
+    /// one moved off it and one in place.
+    fn offset_tiles() -> [(u32, u32, Tile); 3] {
+        [
+            (5, 0, Tile::new(1).with_offset(-7, -1)),
+            (0, 1, Tile::new(2).with_offset(0, -100)),
+            (1, 1, Tile::new(0)),
+        ]
+    }
+
+    fn offset_reference() -> Vec<Color> {
+        let mut map = crate::Tilemap::new(6, 2, tileset(3));
+        map.fill_rect(0, 0, 6, 2, Tile::empty());
+        for (x, y, tile) in offset_tiles() {
+            map.set_tile(x, y, tile);
+        }
+
+        let mut surface = Surface::new(4, 4);
+        map.render(&mut surface, 0, 0);
+        surface.pixels
+    }
+
+    #[test]
+    fn offset_tiles_match_plain_map() {
+        let mut map: LayeredTilemap<_> = LayeredTilemap::new(6, 2, tileset(3));
+        map.add_layer();
+        for (x, y, tile) in offset_tiles() {
+            map.set_tile(0, x, y, tile);
+        }
+
+        let mut surface = Surface::new(4, 4);
+        map.render(&mut surface, 0, 0);
+        assert_eq!(surface.pixels, offset_reference());
+        assert_eq!(surface.at(3, 0), shade(1));
+    }
+}
@@ -0,0 +1,740 @@
+use crate::{Tile, TileId, Tilemap, Tileset, TilesetOptions};
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use simple_blit::BlitOptions;
+
+/// Error that occurred while reading an LDtk project.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LdtkError {
+    /// The file isn't valid JSON.
+    InvalidJson,
+    /// A field is missing or has an invalid value.
+    InvalidField(&'static str),
+    /// Level data is stored in separate files, which aren't supported.
+    ExternalLevels,
+    /// A layer is too large or its IntGrid values don't match its size, see [`LdtkLayer::to_tilemap`].
+    InvalidLayerSize,
+}
+
+impl fmt::Display for LdtkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidJson => write!(f, "invalid JSON"),
+            Self::InvalidField(name) => write!(f, "missing or invalid field `{name}`"),
+            Self::ExternalLevels => write!(f, "external level files are not supported"),
+            Self::InvalidLayerSize => {
+                write!(f, "layer is too large or doesn't match its IntGrid values")
+            }
+        }
+    }
+}
+
+/// A tileset definition of an [`LdtkProject`].
+///
+/// The image itself isn't loaded: load [`rel_path`](Self::rel_path) yourself and create a [`Tileset`]
+/// from it with [`options`](Self::options).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LdtkTileset {
+    /// Unique id of the tileset, referenced by layers.
+    pub uid: i64,
+    /// Name of the tileset.
+    pub identifier: String,
+    /// Path of the tileset's image relative to the project, if any.
+    pub rel_path: Option<String>,
+    /// Width and height of a single tile.
+    pub tile_size: u32,
+    /// Distance between neighboring tiles.
+    pub spacing: u32,
+    /// Distance between the image edges and the tiles.
+    pub padding: u32,
+}
+
+impl LdtkTileset {
+    /// Options to create a [`Tileset`] from this tileset's image with.
+    #[inline]
+    pub fn options(&self) -> TilesetOptions {
+        TilesetOptions::new(self.tile_size, self.tile_size)
+            .with_offset(self.padding, self.padding)
+            .with_margin(self.spacing, self.spacing)
+    }
+}
+
+/// A tile placed on an [`LdtkLayer`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LdtkTile {
+    /// Column of the tile in the layer.
+    pub x: u32,
+    /// Row of the tile in the layer.
+    pub y: u32,
+    /// Id of the tile in the layer's tileset.
+    pub id: TileId,
+    /// Flips of the tile.
+    pub opts: BlitOptions,
+}
+
+/// A layer of an [`LdtkLevel`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LdtkLayer {
+    /// Name of the layer.
+    pub identifier: String,
+    /// Type of the layer: `IntGrid`, `Tiles`, `AutoLayer` or `Entities`.
+    pub layer_type: String,
+    /// Width of the layer in cells.
+    pub width: u32,
+    /// Height of the layer in cells.
+    pub height: u32,
+    /// Width and height of a cell in pixels.
+    pub grid_size: u32,
+    /// Uid of the tileset used by the layer, if any.
+    pub tileset_uid: Option<i64>,
+    /// Whether the layer is shown.
+    pub visible: bool,
+    /// Opacity of the layer.
+    pub opacity: u8,
+    /// IntGrid values, row by row. Empty for layers that aren't IntGrid layers.
+    pub int_grid: Vec<i32>,
+    /// Tiles of the layer, both placed manually and by auto-layer rules.
+    pub tiles: Vec<LdtkTile>,
+}
+
+impl LdtkLayer {
+    /// Convert the layer into a [`Tilemap`] using `tileset`, created from the image of the layer's tileset.
+    ///
+    /// Cells without tiles are filled with invisible [`Tile::empty`] tiles. If several tiles are stacked in a
+    /// cell, the last one is used. IntGrid values land in the tiles' user data (0 for layers without them).
+    ///
+    /// Fails with [`LdtkError::InvalidLayerSize`] if the layer is too large or has IntGrid values that
+    /// don't cover it exactly.
+    pub fn to_tilemap<C, M>(
+        &self,
+        tileset: Tileset<C, M>,
+    ) -> Result<Tilemap<C, i32, M>, LdtkError> {
+        let len = self
+            .width
+            .checked_mul(self.height)
+            .ok_or(LdtkError::InvalidLayerSize)?;
+        if !self.int_grid.is_empty() && self.int_grid.len() != len as usize {
+            return Err(LdtkError::InvalidLayerSize);
+        }
+
+        let mut map = Tilemap::new(self.width, self.height, tileset);
+        map.tiles_mut().fill(Tile::empty().with_visibility(false));
+
+        for (tile, &value) in map.tiles_mut().iter_mut().zip(&self.int_grid) {
+            tile.data = value;
+        }
+
+        for tile in &self.tiles {
+            if let Some(target) = map.get_mut_tile(tile.x, tile.y) {
+                *target = Tile::new(tile.id)
+                    .with_blit_options(tile.opts)
+                    .with_user_data(target.data);
+            }
+        }
+
+        Ok(map)
+    }
+}
+
+/// A level of an [`LdtkProject`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LdtkLevel {
+    /// Name of the level.
+    pub identifier: String,
+    /// Layers of the level, bottom to top.
+    pub layers: Vec<LdtkLayer>,
+}
+
+/// A project parsed from an LDtk JSON file.
+///
+/// Levels have to be stored in the project file itself.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LdtkProject {
+    /// Tileset definitions.
+    pub tilesets: Vec<LdtkTileset>,
+    /// Levels of the project.
+    pub levels: Vec<LdtkLevel>,
+}
+
+impl LdtkProject {
+    /// Parse an LDtk project.
+    pub fn parse(json: &str) -> Result<Self, LdtkError> {
+        let mut parser = JsonParser {
+            input: json.as_bytes(),
+            pos: 0,
+            depth: 0,
+        };
+        let root = parser.parse_value()?;
+
+        parser.skip_whitespace();
+
+        if parser.pos != parser.input.len() {
+            return Err(LdtkError::InvalidJson);
+        }
+
+        let tilesets = root
+            .get("defs")
+            .and_then(|defs| defs.get("tilesets"))
+            .and_then(Json::as_array)
+            .ok_or(LdtkError::InvalidField("defs.tilesets"))?
+            .iter()
+            .map(read_tileset)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let levels = root
+            .get("levels")
+            .and_then(Json::as_array)
+            .ok_or(LdtkError::InvalidField("levels"))?
+            .iter()
+            .map(read_level)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { tilesets, levels })
+    }
+
+    /// Get a tileset definition by its uid.
+    #[inline]
+    pub fn tileset(&self, uid: i64) -> Option<&LdtkTileset> {
+        self.tilesets.iter().find(|tileset| tileset.uid == uid)
+    }
+
+    /// Get a level by its name.
+    #[inline]
+    pub fn level(&self, identifier: &str) -> Option<&LdtkLevel> {
+        self.levels
+            .iter()
+            .find(|level| level.identifier == identifier)
+    }
+}
+
+fn read_tileset(json: &Json) -> Result<LdtkTileset, LdtkError> {
+    Ok(LdtkTileset {
+        uid: json.int("uid")?,
+        identifier: json.string("identifier")?,
+        rel_path: json.get("relPath").and_then(Json::as_str).map(String::from),
+        tile_size: json.int("tileGridSize")?,
+        spacing: json.int("spacing")?,
+        padding: json.int("padding")?,
+    })
+}
+
+fn read_level(json: &Json) -> Result<LdtkLevel, LdtkError> {
+    let layers = match json.get("layerInstances") {
+        Some(Json::Array(layers)) => layers
+            .iter()
+            // LDtk stores layers top to bottom.
+            .rev()
+            .map(read_layer)
+            .collect::<Result<Vec<_>, _>>()?,
+        _ => return Err(LdtkError::ExternalLevels),
+    };
+
+    Ok(LdtkLevel {
+        identifier: json.string("identifier")?,
+        layers,
+    })
+}
+
+fn read_layer(json: &Json) -> Result<LdtkLayer, LdtkError> {
+    let grid_size: u32 = json.int("__gridSize")?;
+
+    let int_grid = match json.get("intGridCsv").and_then(Json::as_array) {
+        Some(values) => values
+            .iter()
+            .map(|value| value.as_int().ok_or(LdtkError::InvalidField("intGridCsv")))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => Vec::new(),
+    };
+
+    let mut tiles = Vec::new();
+
+    for field in ["autoLayerTiles", "gridTiles"] {
+        for tile in json.get(field).and_then(Json::as_array).unwrap_or_default() {
+            let px = tile
+                .get("px")
+                .and_then(Json::as_array)
+                .filter(|px| px.len() == 2)
+                .ok_or(LdtkError::InvalidField("px"))?;
+
+            let pos = |i: usize| -> Result<u32, LdtkError> {
+                let value: u32 = px[i].as_int().ok_or(LdtkError::InvalidField("px"))?;
+                Ok(value / grid_size.max(1))
+            };
+
+            let flips: u32 = tile.int("f")?;
+
+            tiles.push(LdtkTile {
+                x: pos(0)?,
+                y: pos(1)?,
+                id: tile.int("t")?,
+                opts: match flips & 3 {
+                    1 => BlitOptions::FlipHorizontal,
+                    2 => BlitOptions::FlipVertical,
+                    3 => BlitOptions::FlipBoth,
+                    _ => BlitOptions::None,
+                },
+            });
+        }
+    }
+
+    let opacity = match json.get("__opacity") {
+        Some(Json::Number(opacity)) => *opacity,
+        _ => 1.,
+    };
+
+    Ok(LdtkLayer {
+        identifier: json.string("__identifier")?,
+        layer_type: json.string("__type")?,
+        width: json.int("__cWid")?,
+        height: json.int("__cHei")?,
+        grid_size,
+        tileset_uid: json.get("__tilesetDefUid").and_then(Json::as_int),
+        visible: !matches!(json.get("visible"), Some(Json::Bool(false))),
+        opacity: (opacity.clamp(0., 1.) * 255. + 0.5) as u8,
+        int_grid,
+        tiles,
+    })
+}
+
+/// JSON value.
+#[derive(Debug)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Self::Object(fields) => fields
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Self::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_int<T: TryFrom<i64>>(&self) -> Option<T> {
+        match *self {
+            Self::Number(n) if n == (n as i64) as f64 => T::try_from(n as i64).ok(),
+            _ => None,
+        }
+    }
+
+    fn int<T: TryFrom<i64>>(&self, key: &'static str) -> Result<T, LdtkError> {
+        self.get(key)
+            .and_then(Json::as_int)
+            .ok_or(LdtkError::InvalidField(key))
+    }
+
+    fn string(&self, key: &'static str) -> Result<String, LdtkError> {
+        self.get(key)
+            .and_then(Json::as_str)
+            .map(String::from)
+            .ok_or(LdtkError::InvalidField(key))
+    }
+}
+
+/// How deeply arrays and objects can be nested, so that malicious input can't overflow the stack.
+const MAX_DEPTH: u32 = 128;
+
+struct JsonParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    /// Amount of arrays and objects around the current position.
+    depth: u32,
+}
+
+impl JsonParser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self
+            .input
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.input.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), LdtkError> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(LdtkError::InvalidJson)
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Json) -> Result<Json, LdtkError> {
+        if self.input[self.pos..].starts_with(keyword.as_bytes()) {
+            self.pos += keyword.len();
+            Ok(value)
+        } else {
+            Err(LdtkError::InvalidJson)
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Json, LdtkError> {
+        match self.peek().ok_or(LdtkError::InvalidJson)? {
+            b'n' => self.keyword("null", Json::Null),
+            b't' => self.keyword("true", Json::Bool(true)),
+            b'f' => self.keyword("false", Json::Bool(false)),
+            b'"' => Ok(Json::String(self.parse_string()?)),
+            b'[' => self.parse_nested(Self::parse_array),
+            b'{' => self.parse_nested(Self::parse_object),
+            _ => self.parse_number(),
+        }
+    }
+
+    /// Parse an array or object with `parse`, failing if they're nested too deeply.
+    fn parse_nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<Json, LdtkError>,
+    ) -> Result<Json, LdtkError> {
+        if self.depth == MAX_DEPTH {
+            return Err(LdtkError::InvalidJson);
+        }
+
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+
+        value
+    }
+
+    fn parse_array(&mut self) -> Result<Json, LdtkError> {
+        self.pos += 1;
+        let mut values = Vec::new();
+
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Json::Array(values));
+        }
+
+        loop {
+            values.push(self.parse_value()?);
+
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Json::Array(values));
+                }
+                _ => return Err(LdtkError::InvalidJson),
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Json, LdtkError> {
+        self.pos += 1;
+        let mut fields = Vec::new();
+
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Json::Object(fields));
+        }
+
+        loop {
+            if self.peek() != Some(b'"') {
+                return Err(LdtkError::InvalidJson);
+            }
+
+            let name = self.parse_string()?;
+            self.expect(b':')?;
+            fields.push((name, self.parse_value()?));
+
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Json::Object(fields));
+                }
+                _ => return Err(LdtkError::InvalidJson),
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Json, LdtkError> {
+        let start = self.pos;
+
+        while self
+            .input
+            .get(self.pos)
+            .is_some_and(|c| matches!(c, b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E'))
+        {
+            self.pos += 1;
+        }
+
+        core::str::from_utf8(&self.input[start..self.pos])
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .map(Json::Number)
+            .ok_or(LdtkError::InvalidJson)
+    }
+
+    fn parse_string(&mut self) -> Result<String, LdtkError> {
+        self.expect(b'"')?;
+
+        let mut bytes = Vec::new();
+
+        loop {
+            let c = *self.input.get(self.pos).ok_or(LdtkError::InvalidJson)?;
+            self.pos += 1;
+
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = *self.input.get(self.pos).ok_or(LdtkError::InvalidJson)?;
+                    self.pos += 1;
+
+                    let c = match escaped {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.parse_unicode_escape()?,
+                        _ => return Err(LdtkError::InvalidJson),
+                    };
+
+                    bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                _ => bytes.push(c),
+            }
+        }
+
+        String::from_utf8(bytes).map_err(|_| LdtkError::InvalidJson)
+    }
+
+    /// Parse the code of a `\u` escape (after the `\u`), including surrogate pairs.
+    fn parse_unicode_escape(&mut self) -> Result<char, LdtkError> {
+        let read_code = |parser: &mut Self| -> Result<u32, LdtkError> {
+            let digits = parser
+                .input
+                .get(parser.pos..parser.pos + 4)
+                .and_then(|digits| core::str::from_utf8(digits).ok())
+                .ok_or(LdtkError::InvalidJson)?;
+            parser.pos += 4;
+
+            u32::from_str_radix(digits, 16).map_err(|_| LdtkError::InvalidJson)
+        };
+
+        let code = read_code(self)?;
+
+        let code = if (0xd800..0xdc00).contains(&code) {
+            if !self.input[self.pos..].starts_with(b"\\u") {
+                return Err(LdtkError::InvalidJson);
+            }
+
+            self.pos += 2;
+            let low = read_code(self)?;
+
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(LdtkError::InvalidJson);
+            }
+
+            0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            code
+        };
+
+        char::from_u32(code).ok_or(LdtkError::InvalidJson)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::tileset_from;
+    use crate::Color;
+    use alloc::string::ToString;
+
+    const PROJECT: &str = r#"{ "jsonVersion": "1.5.3",
+ "defs": { "tilesets": [
+   { "uid": 7, "identifier": "Té😀", "relPath": "a/b.png", "tileGridSize": 2, "spacing": 1, "padding": 0 },
+   { "uid": 8, "identifier": "x", "relPath": null, "tileGridSize": 4, "spacing": 0, "padding": 2 }
+ ] },
+ "levels": [ { "identifier": "L0", "layerInstances": [
+   { "__identifier": "Top", "__type": "Tiles", "__cWid": 2, "__cHei": 2, "__gridSize": 2, "__tilesetDefUid": 7,
+     "__opacity": 0.5, "visible": false,
+     "gridTiles": [ { "px": [2, 0], "src": [3, 0], "f": 1, "t": 1 }, { "px": [2, 0], "src": [0, 0], "f": 0, "t": 2 } ] },
+   { "__identifier": "Ground", "__type": "IntGrid", "__cWid": 2, "__cHei": 2, "__gridSize": 2, "__tilesetDefUid": 7,
+     "__opacity": 1, "visible": true, "intGridCsv": [1, 0, 2, 3],
+     "autoLayerTiles": [ { "px": [0, 2], "src": [0, 0], "f": 3, "t": 0, "d": [1] } ], "gridTiles": [] }
+ ] } ] }"#;
+
+    fn parse(json: &str) -> Result<Json, LdtkError> {
+        JsonParser {
+            input: json.as_bytes(),
+            pos: 0,
+            depth: 0,
+        }
+        .parse_value()
+    }
+
+    /// Blank 8x2 pixels tileset.
+    fn blank(opts: TilesetOptions) -> Tileset<Vec<u8>> {
+        tileset_from(&[Color::default(); 8 * 2], 8, opts)
+    }
+
+    #[test]
+    fn string_escapes() {
+        let string = parse(r#""a\"b\\c\/d\b\f\n\r\tAé😀 é""#).unwrap();
+        assert_eq!(string.as_str(), Some("a\"b\\c/d\u{8}\u{c}\n\r\tAé😀 é"));
+
+        for invalid in [
+            r#""\x""#,
+            r#""\u12""#,
+            r#""\u12g4""#,
+            r#""\ud83d""#,
+            r#""\ud83dA""#,
+            r#""unterminated"#,
+        ] {
+            assert!(parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn numbers() {
+        for (json, value) in [
+            ("0", 0.),
+            ("-12", -12.),
+            ("1.5", 1.5),
+            ("2e3", 2000.),
+            ("-1.25E-2", -0.0125),
+        ] {
+            assert!(
+                matches!(parse(json), Ok(Json::Number(n)) if n == value),
+                "{json}"
+            );
+        }
+
+        assert_eq!(parse("3").unwrap().as_int::<u32>(), Some(3));
+        assert_eq!(parse("-3").unwrap().as_int::<u32>(), None);
+        assert_eq!(parse("1.5").unwrap().as_int::<i32>(), None);
+
+        for invalid in ["-", "1..2", "e", "x"] {
+            assert!(parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn nesting_limit() {
+        let nested = |depth| "[".repeat(depth) + &"]".repeat(depth);
+
+        assert!(parse(&nested(MAX_DEPTH as usize)).is_ok());
+        assert_eq!(
+            parse(&nested(MAX_DEPTH as usize + 1)).err(),
+            Some(LdtkError::InvalidJson)
+        );
+
+        // deeply nested input fails instead of overflowing the stack
+        let objects = r#"{"a":"#.repeat(100_000);
+        assert_eq!(LdtkProject::parse(&objects), Err(LdtkError::InvalidJson));
+    }
+
+    #[test]
+    fn parse_project() {
+        let project = LdtkProject::parse(PROJECT).unwrap();
+
+        assert_eq!(project.tilesets[0].identifier, "Té😀");
+        assert_eq!(project.tilesets[0].rel_path.as_deref(), Some("a/b.png"));
+        assert_eq!(project.tileset(8).unwrap().rel_path, None);
+        assert_eq!(project.tileset(8).unwrap().options().offset, (2, 2));
+
+        let level = project.level("L0").unwrap();
+        assert_eq!(level.layers[0].identifier, "Ground");
+        assert_eq!(level.layers[0].int_grid, [1, 0, 2, 3]);
+        assert_eq!(level.layers[1].identifier, "Top");
+        assert_eq!(level.layers[1].opacity, 128);
+        assert!(!level.layers[1].visible);
+
+        let opts = project.tilesets[0].options();
+
+        let ground = level.layers[0].to_tilemap(blank(opts.clone())).unwrap();
+        let tile = ground.get_tile(0, 1).unwrap();
+        assert_eq!(
+            (tile.id, tile.visible, tile.opts, tile.data),
+            (0, true, BlitOptions::FlipBoth, 2)
+        );
+        let tile = ground.get_tile(1, 1).unwrap();
+        assert_eq!((tile.visible, tile.data), (false, 3));
+
+        // the last of the stacked tiles wins
+        let top = level.layers[1].to_tilemap(blank(opts)).unwrap();
+        let tile = top.get_tile(1, 0).unwrap();
+        assert_eq!((tile.id, tile.opts, tile.data), (2, BlitOptions::None, 0));
+        assert!(top.get_tile(0, 0).unwrap().is_empty());
+
+        let mut layer = level.layers[0].clone();
+        layer.int_grid.pop();
+        assert!(matches!(
+            layer.to_tilemap(blank(project.tilesets[0].options())),
+            Err(LdtkError::InvalidLayerSize)
+        ));
+        layer.int_grid.clear();
+        layer.width = u32::MAX;
+        assert!(matches!(
+            layer.to_tilemap(blank(project.tilesets[0].options())),
+            Err(LdtkError::InvalidLayerSize)
+        ));
+    }
+
+    #[test]
+    fn invalid_projects() {
+        let external = PROJECT.replace(
+            r#""levels": [ {"#,
+            r#""levels": [ { "identifier": "Ext", "layerInstances": null }, {"#,
+        );
+        assert_eq!(
+            LdtkProject::parse(&external),
+            Err(LdtkError::ExternalLevels)
+        );
+
+        assert_eq!(LdtkProject::parse("{"), Err(LdtkError::InvalidJson));
+        assert_eq!(LdtkProject::parse("{} x"), Err(LdtkError::InvalidJson));
+        assert_eq!(LdtkProject::parse("[1,]"), Err(LdtkError::InvalidJson));
+        assert_eq!(
+            LdtkProject::parse(r#"{"levels": []}"#),
+            Err(LdtkError::InvalidField("defs.tilesets"))
+        );
+
+        let missing = PROJECT.replace(r#""__cWid": 2,"#, "");
+        assert_eq!(
+            LdtkProject::parse(&missing),
+            Err(LdtkError::InvalidField("__cWid"))
+        );
+        assert_eq!(
+            LdtkError::ExternalLevels.to_string(),
+            "external level files are not supported"
+        );
+    }
+}
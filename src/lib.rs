@@ -8,6 +8,54 @@ mod tileset;
 pub use tileset::*;
 mod tilemap;
 pub use tilemap::*;
+mod accum;
+pub use accum::*;
+mod animation;
+pub use animation::*;
+mod autotile;
+pub use autotile::*;
+mod bitgrid;
+pub use bitgrid::*;
+mod blend;
+pub use blend::*;
+mod cached;
+pub use cached::*;
+mod camera;
+pub use camera::*;
+mod chunked;
+pub use chunked::*;
+mod flags;
+pub use flags::*;
+#[cfg(feature = "gen")]
+mod gen;
+#[cfg(feature = "gen")]
+pub use gen::*;
+mod hex;
+pub use hex::*;
+mod idgrid;
+pub use idgrid::*;
+mod iso;
+pub use iso::*;
+mod layered;
+pub use layered::*;
+#[cfg(feature = "ldtk")]
+mod ldtk;
+#[cfg(feature = "ldtk")]
+pub use ldtk::*;
+mod metric;
+pub use metric::*;
+mod rotation;
+pub use rotation::*;
+mod sparse;
+pub use sparse::*;
+mod terrain;
+pub use terrain::*;
+#[cfg(test)]
+mod test_util;
+#[cfg(feature = "tiled")]
+mod tiled;
+#[cfg(feature = "tiled")]
+pub use tiled::*;
 
 pub use rgb;
 #[doc(no_inline)]
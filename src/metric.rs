@@ -0,0 +1,184 @@
+use crate::{Tile, Tilemap};
+
+/// Distance metric between tiles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Metric {
+    /// Maximum of the distances along the axes. Diagonal neighbors are at distance 1.
+    #[default]
+    Chebyshev,
+    /// Sum of the distances along the axes. Diagonal neighbors are at distance 2.
+    Manhattan,
+}
+
+impl Metric {
+    /// Distance between tiles at (x1, y1) and (x2, y2).
+    #[inline]
+    pub fn distance(self, (x1, y1): (u32, u32), (x2, y2): (u32, u32)) -> u32 {
+        let (dx, dy) = (x1.abs_diff(x2), y1.abs_diff(y2));
+
+        match self {
+            Self::Chebyshev => dx.max(dy),
+            Self::Manhattan => dx + dy,
+        }
+    }
+}
+
+impl<C, U, M> Tilemap<C, U, M> {
+    /// Iterate over the tiles at exactly `radius` distance from (x, y), along with their positions, row by row.
+    ///
+    /// Positions outside of the map are skipped.
+    #[inline]
+    pub fn ring(
+        &self,
+        x: u32,
+        y: u32,
+        radius: u32,
+        metric: Metric,
+    ) -> impl Iterator<Item = (u32, u32, &Tile<U>)> {
+        self.around(x, y, radius)
+            .filter(move |&(tx, ty, _)| metric.distance((x, y), (tx, ty)) == radius)
+    }
+
+    /// Iterate over the tiles at `radius` distance or closer from (x, y), along with their positions, row by row.
+    ///
+    /// Positions outside of the map are skipped.
+    #[inline]
+    pub fn disc(
+        &self,
+        x: u32,
+        y: u32,
+        radius: u32,
+        metric: Metric,
+    ) -> impl Iterator<Item = (u32, u32, &Tile<U>)> {
+        self.around(x, y, radius)
+            .filter(move |&(tx, ty, _)| metric.distance((x, y), (tx, ty)) <= radius)
+    }
+
+    /// Find the closest tile (by [`Metric::Chebyshev`]) to (x, y) for which `pred` returns `true`.
+    ///
+    /// Searches in expanding rings around (x, y). Tiles at the same distance are checked row by row.
+    /// Returns `None` if no tile matches.
+    pub fn nearest<F>(&self, x: u32, y: u32, pred: F) -> Option<(u32, u32)>
+    where
+        F: Fn(&Tile<U>) -> bool,
+    {
+        if self.width() == 0 || self.height() == 0 {
+            return None;
+        }
+
+        let max_radius = x
+            .max(x.abs_diff(self.width() - 1))
+            .max(y)
+            .max(y.abs_diff(self.height() - 1));
+
+        (0..=max_radius).find_map(|radius| {
+            self.square_ring(x, y, radius)
+                .find(|(_, _, tile)| pred(tile))
+                .map(|(tx, ty, _)| (tx, ty))
+        })
+    }
+
+    /// Tiles on the edges of the square of side `radius * 2 + 1` centered at (x, y), row by row.
+    fn square_ring(
+        &self,
+        x: u32,
+        y: u32,
+        radius: u32,
+    ) -> impl Iterator<Item = (u32, u32, &Tile<U>)> {
+        let (x, y, radius) = (x as i64, y as i64, radius as i64);
+        let (width, height) = (self.width() as i64, self.height() as i64);
+
+        (y - radius..=y + radius)
+            .filter(move |&ty| ty >= 0 && ty < height)
+            .flat_map(move |ty| {
+                let step = if ty == y - radius || ty == y + radius {
+                    1
+                } else {
+                    radius * 2
+                };
+
+                (x - radius..=x + radius)
+                    .step_by(step as usize)
+                    .filter(move |&tx| tx >= 0 && tx < width)
+                    .map(move |tx| (tx as u32, ty as u32))
+            })
+            .map(|(tx, ty)| (tx, ty, &self.tiles()[(ty * self.width() + tx) as usize]))
+    }
+
+    /// Tiles in the square of side `radius * 2 + 1` centered at (x, y).
+    #[inline]
+    fn around(&self, x: u32, y: u32, radius: u32) -> impl Iterator<Item = (u32, u32, &Tile<U>)> {
+        let (x_start, y_start) = (x.saturating_sub(radius), y.saturating_sub(radius));
+        let (x_end, y_end) = (x.saturating_add(radius), y.saturating_add(radius));
+
+        self.iter_region(
+            x_start,
+            y_start,
+            (x_end - x_start).saturating_add(1),
+            (y_end - y_start).saturating_add(1),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::tileset;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    /// Map of the shared test [`tileset`], only ids matter here.
+    fn map() -> Tilemap<Vec<u8>> {
+        Tilemap::new(6, 6, tileset(1))
+    }
+
+    #[test]
+    fn ring() {
+        let map = map();
+
+        assert_eq!(map.ring(2, 2, 1, Metric::Chebyshev).count(), 8);
+        assert_eq!(map.ring(2, 2, 2, Metric::Chebyshev).count(), 16);
+        assert_eq!(map.ring(2, 2, 1, Metric::Manhattan).count(), 4);
+        assert_eq!(map.ring(0, 0, 0, Metric::Manhattan).count(), 1);
+    }
+
+    #[test]
+    fn ring_clipped_at_corner() {
+        let map = map();
+
+        let cells: Vec<_> = map
+            .ring(0, 0, 2, Metric::Chebyshev)
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        assert_eq!(cells, vec![(2, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+
+        assert_eq!(map.ring(5, 5, 2, Metric::Chebyshev).count(), 5);
+    }
+
+    #[test]
+    fn disc() {
+        let map = map();
+
+        assert_eq!(map.disc(2, 2, 1, Metric::Chebyshev).count(), 9);
+        assert_eq!(map.disc(3, 3, u32::MAX, Metric::Chebyshev).count(), 36);
+    }
+
+    #[test]
+    fn nearest() {
+        let mut map = map();
+        let is_wall = |tile: &Tile| tile.id == 1;
+        assert_eq!(map.nearest(3, 2, is_wall), None);
+
+        map.set_tile(5, 4, Tile::new(1));
+        assert_eq!(map.nearest(3, 2, is_wall), Some((5, 4)));
+        assert_eq!(map.nearest(20, 20, is_wall), Some((5, 4)));
+
+        // ties go to the first cell of the ring
+        map.set_tile(1, 1, Tile::new(1));
+        map.set_tile(5, 1, Tile::new(1));
+        assert_eq!(map.nearest(3, 2, is_wall), Some((1, 1)));
+
+        assert_eq!(map.nearest(0, 0, |tile| tile.id == 0), Some((0, 0)));
+    }
+}
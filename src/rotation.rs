@@ -0,0 +1,314 @@
+use crate::{draw_tile_pixel, saturate, Buffer, BufferMut, Color, Tilemap};
+
+/// Clockwise rotation by a multiple of 90 degrees.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Rotation {
+    /// No rotation.
+    #[default]
+    None,
+    /// Rotate by 90 degrees clockwise.
+    Rotate90,
+    /// Rotate by 180 degrees.
+    Rotate180,
+    /// Rotate by 270 degrees clockwise (90 degrees counter-clockwise).
+    Rotate270,
+}
+
+impl Rotation {
+    /// Size (width x height) of a `size` rectangle after the rotation.
+    #[inline]
+    pub const fn apply_size(self, (width, height): (u32, u32)) -> (u32, u32) {
+        match self {
+            Self::None | Self::Rotate180 => (width, height),
+            Self::Rotate90 | Self::Rotate270 => (height, width),
+        }
+    }
+
+    /// Rotation that undoes this one.
+    #[inline]
+    pub const fn inverse(self) -> Self {
+        match self {
+            Self::None => Self::None,
+            Self::Rotate90 => Self::Rotate270,
+            Self::Rotate180 => Self::Rotate180,
+            Self::Rotate270 => Self::Rotate90,
+        }
+    }
+
+    /// Position of the point (x, y) in a `size` rectangle after the rotation.
+    #[inline]
+    pub const fn apply(self, (x, y): (u32, u32), (width, height): (u32, u32)) -> (u32, u32) {
+        match self {
+            Self::None => (x, y),
+            Self::Rotate90 => (height - 1 - y, x),
+            Self::Rotate180 => (width - 1 - x, height - 1 - y),
+            Self::Rotate270 => (y, width - 1 - x),
+        }
+    }
+}
+
+impl<C, M> Tilemap<C, (), M>
+where
+    C: AsRef<[u8]>,
+{
+    /// Render the map onto a buffer at pixel offset `(offset_x, offset_y)`,
+    /// rotated around the center of the map.
+    ///
+    /// For [`Rotation::Rotate90`] and [`Rotation::Rotate270`] the width and height of the drawn area get swapped.
+    /// Tiles are blended onto the buffer the same as with [`render`](Self::render),
+    /// and only the ones that end up on it are drawn.
+    pub fn render_rotated(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+        angle: Rotation,
+    ) {
+        let size = self.pixel_size();
+        let rotated = angle.apply_size(size);
+
+        let origin = (
+            offset_x as i64 + (size.0 as i64 - rotated.0 as i64) / 2,
+            offset_y as i64 + (size.1 as i64 - rotated.1 as i64) / 2,
+        );
+
+        // the part of the rotated map that's on the surface
+        let visible = |origin: i64, len: u32, surface_len: u32| {
+            (
+                origin.max(0) - origin,
+                (origin + len as i64).min(surface_len as i64) - origin,
+            )
+        };
+        let (x0, x1) = visible(origin.0, rotated.0, surface.width());
+        let (y0, y1) = visible(origin.1, rotated.1, surface.height());
+
+        if x0 >= x1 || y0 >= y1 {
+            return;
+        }
+
+        // the same part of the map before the rotation
+        let inverse = angle.inverse();
+        let (ax, ay) = inverse.apply((x0 as u32, y0 as u32), rotated);
+        let (bx, by) = inverse.apply((x1 as u32 - 1, y1 as u32 - 1), rotated);
+        let (left, top) = (ax.min(bx), ay.min(by));
+
+        let mut view = RotatedView {
+            surface,
+            angle,
+            size,
+            origin,
+            left,
+            top,
+            width: ax.max(bx) - left + 1,
+            height: ay.max(by) - top + 1,
+        };
+
+        self.render_tiles(
+            &mut view,
+            saturate(-(left as i64)),
+            saturate(-(top as i64)),
+            None,
+            draw_tile_pixel,
+        );
+    }
+}
+
+/// Part of a map's pixels, drawn onto a surface rotated around the map's center.
+struct RotatedView<'a, B: ?Sized> {
+    surface: &'a mut B,
+    angle: Rotation,
+    /// Size of the whole map in pixels.
+    size: (u32, u32),
+    /// Position of the rotated map on the surface.
+    origin: (i64, i64),
+    left: u32,
+    top: u32,
+    width: u32,
+    height: u32,
+}
+
+impl<B> RotatedView<'_, B>
+where
+    B: Buffer<Color> + ?Sized,
+{
+    /// Position on the surface of the view's pixel (x, y).
+    #[inline]
+    fn surface_pos(&self, x: u32, y: u32) -> (u32, u32) {
+        let (rx, ry) = self.angle.apply((self.left + x, self.top + y), self.size);
+        (
+            (self.origin.0 + rx as i64) as u32,
+            (self.origin.1 + ry as i64) as u32,
+        )
+    }
+}
+
+impl<B> Buffer<Color> for RotatedView<'_, B>
+where
+    B: Buffer<Color> + ?Sized,
+{
+    #[inline]
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[inline]
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[inline]
+    fn get(&self, x: u32, y: u32) -> &Color {
+        let (x, y) = self.surface_pos(x, y);
+        self.surface.get(x, y)
+    }
+}
+
+impl<B> BufferMut<Color> for RotatedView<'_, B>
+where
+    B: BufferMut<Color> + ?Sized,
+{
+    #[inline]
+    fn get_mut(&mut self, x: u32, y: u32) -> &mut Color {
+        let (x, y) = self.surface_pos(x, y);
+        self.surface.get_mut(x, y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{shade, tileset, tileset_from, Surface};
+    use crate::{BlendMode, Tile, Tileset, TilesetOptions};
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    /// 2x1 map of two distinct 2x2 tiles.
+    fn map() -> Tilemap<Vec<u8>> {
+        let mut map = Tilemap::new(2, 1, tileset(2));
+        map.set_tile(0, 0, Tile::new(0));
+        map.set_tile(1, 0, Tile::new(1));
+        map
+    }
+
+    #[test]
+    fn render_rotated_quarter_turns() {
+        let map = map();
+
+        // the 4x2 map at (2, 2) has its center at (4, 3), so a quarter turn draws it 2x4 at (3, 1)
+        let mut surface = Surface::new(8, 8);
+        map.render_rotated(&mut surface, 2, 2, Rotation::Rotate90);
+        assert_eq!(surface.at(3, 1), shade(0));
+        assert_eq!(surface.at(4, 2), shade(0));
+        assert_eq!(surface.at(3, 3), shade(1));
+        assert_eq!(surface.at(4, 4), shade(1));
+        assert_eq!(surface.at(2, 2), Color::default());
+        assert_eq!(surface.at(3, 5), Color::default());
+
+        let mut surface = Surface::new(8, 8);
+        map.render_rotated(&mut surface, 2, 2, Rotation::Rotate270);
+        assert_eq!(surface.at(3, 1), shade(1));
+        assert_eq!(surface.at(3, 4), shade(0));
+    }
+
+    #[test]
+    fn render_rotated_half_turn_and_none() {
+        let map = map();
+
+        let mut surface = Surface::new(8, 8);
+        map.render_rotated(&mut surface, 2, 2, Rotation::Rotate180);
+        assert_eq!(surface.at(2, 2), shade(1));
+        assert_eq!(surface.at(5, 3), shade(0));
+
+        let mut expected = Surface::new(8, 8);
+        map.render(&mut expected, 2, 2);
+        let mut surface = Surface::new(8, 8);
+        map.render_rotated(&mut surface, 2, 2, Rotation::None);
+        assert_eq!(surface.pixels, expected.pixels);
+
+        // entirely off the surface
+        let mut surface = Surface::new(3, 3);
+        map.render_rotated(&mut surface, -5, -5, Rotation::Rotate90);
+        assert_eq!(surface.pixels, vec![Color::default(); 9]);
+    }
+
+    #[test]
+    fn render_rotated_blends() {
+        let mut map = map();
+        map.get_mut_tile(1, 0).unwrap().blend = BlendMode::Additive;
+
+        let background = Color::new(0, 100, 0, 255);
+        let mut surface = Surface::filled(8, 8, background);
+        map.render_rotated(&mut surface, 2, 2, Rotation::Rotate180);
+        assert_eq!(
+            surface.at(2, 2),
+            BlendMode::Additive.blend(background, shade(1))
+        );
+        assert_eq!(surface.at(5, 3), shade(0));
+
+        // partly off the surface: the quarter turn draws the map 2x4 at (0, -2)
+        let mut surface = Surface::filled(3, 3, background);
+        map.render_rotated(&mut surface, -1, -1, Rotation::Rotate90);
+        let added = BlendMode::Additive.blend(background, shade(1));
+        assert_eq!([surface.at(0, 0), surface.at(1, 1)], [added; 2]);
+        assert_eq!([surface.at(0, 2), surface.at(2, 0)], [background; 2]);
+    }
+
+    /// Tileset with one 2x2 tile whose pixels have reds 1, 2 (top row) and 3, 4 (bottom row).
+    fn corners() -> Tileset<Vec<u8>> {
+        let pixels: Vec<_> = (0..3u8)
+            .flat_map(|y| (0..3u8).map(move |x| (x, y)))
+            .map(|(x, y)| Color::new(if x < 2 && y < 2 { 1 + x + 2 * y } else { 0 }, 0, 0, 255))
+            .collect();
+
+        tileset_from(&pixels, 3, TilesetOptions::new(2, 2))
+    }
+
+    fn draw(tile: Tile) -> [u8; 4] {
+        let mut map = Tilemap::new(1, 1, corners());
+        map.set_tile(0, 0, tile);
+
+        let mut surface = Surface::new(2, 2);
+        map.render(&mut surface, 0, 0);
+        [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(x, y)| surface.at(x, y).r)
+    }
+
+    #[test]
+    fn tile_rotations() {
+        let tile = Tile::new(0);
+        assert_eq!(draw(tile), [1, 2, 3, 4]);
+        assert_eq!(draw(tile.with_rotation(Rotation::Rotate90)), [3, 1, 4, 2]);
+        assert_eq!(draw(tile.with_rotation(Rotation::Rotate180)), [4, 3, 2, 1]);
+        assert_eq!(draw(tile.with_rotation(Rotation::Rotate270)), [2, 4, 1, 3]);
+
+        // flipped before being rotated
+        let flipped = tile.with_blit_options(crate::BlitOptions::FlipHorizontal);
+        assert_eq!(
+            draw(flipped.with_rotation(Rotation::Rotate270)),
+            [1, 3, 2, 4]
+        );
+
+        let mut map = Tilemap::new(1, 1, corners());
+        map.set_tile(0, 0, tile.with_rotation(Rotation::Rotate90));
+
+        let mut scaled = Surface::new(2, 2);
+        map.render_scaled(&mut scaled, 0, 0, 1);
+        assert_eq!(
+            scaled
+                .pixels
+                .iter()
+                .map(|pixel| pixel.r)
+                .collect::<Vec<_>>(),
+            [3, 1, 4, 2]
+        );
+
+        let mut constant = Surface::new(2, 2);
+        map.render_const::<2, 2>(&mut constant, 0, 0);
+        assert_eq!(constant.pixels, scaled.pixels);
+
+        let grid = map.to_id_grid();
+        assert_eq!(grid.rotations, Some(vec![Rotation::Rotate90]));
+        let map = Tilemap::<_>::from_id_grid(grid, corners()).unwrap();
+        assert_eq!(map.get_tile(0, 0).unwrap().rotation, Rotation::Rotate90);
+    }
+}
@@ -0,0 +1,272 @@
+use crate::{draw_tile_pixel, BufferMut, Color, Tile, Tilemap, Tileset, OFFSET_MARGIN};
+
+use alloc::collections::BTreeMap;
+
+/// A map that only stores the tiles that were set, for mostly empty maps.
+///
+/// Has the same size semantics as a [`Tilemap`], but empty positions take no memory
+/// and aren't drawn.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SparseTilemap<C, U = ()> {
+    tileset: Tileset<C>,
+    // keyed by (y, x) so that tiles are ordered row by row
+    tiles: BTreeMap<(u32, u32), Tile<U>>,
+    width: u32,
+    height: u32,
+}
+
+impl<C, U> SparseTilemap<C, U> {
+    /// Construct a new empty map.
+    /// `width` and `height` are map's size in tiles.
+    #[inline]
+    pub fn new(width: u32, height: u32, tileset: Tileset<C>) -> Self {
+        Self {
+            tileset,
+            tiles: BTreeMap::new(),
+            width,
+            height,
+        }
+    }
+
+    /// Map's width in tiles.
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Map's height in tiles.
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Map's tileset.
+    #[inline]
+    pub fn tileset(&self) -> &Tileset<C> {
+        &self.tileset
+    }
+
+    /// Amount of tiles that are set.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    /// Whether no tiles are set.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    /// Get a tile at (x, y).
+    ///
+    /// Returns `None` if the position is empty or out of bounds.
+    #[inline]
+    pub fn get_tile(&self, x: u32, y: u32) -> Option<&Tile<U>> {
+        self.tiles.get(&(y, x))
+    }
+
+    /// Get a mutable ref to a tile at (x, y).
+    ///
+    /// Returns `None` if the position is empty or out of bounds.
+    #[inline]
+    pub fn get_mut_tile(&mut self, x: u32, y: u32) -> Option<&mut Tile<U>> {
+        self.tiles.get_mut(&(y, x))
+    }
+
+    /// Set a tile at (x, y).
+    ///
+    /// Does nothing if the position is out of bounds.
+    #[inline]
+    pub fn set_tile(&mut self, x: u32, y: u32, tile: Tile<U>) {
+        if x < self.width && y < self.height {
+            self.tiles.insert((y, x), tile);
+        }
+    }
+
+    /// Empty the position (x, y), returning the tile that was there.
+    #[inline]
+    pub fn remove_tile(&mut self, x: u32, y: u32) -> Option<Tile<U>> {
+        self.tiles.remove(&(y, x))
+    }
+
+    /// Empty all the positions.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.tiles.clear();
+    }
+
+    /// Iterate over the tiles that are set along with their positions, row by row.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (u32, u32, &Tile<U>)> {
+        self.tiles.iter().map(|(&(y, x), tile)| (x, y, tile))
+    }
+}
+
+impl<C, U> SparseTilemap<C, U>
+where
+    C: Clone,
+    U: Default + Clone,
+{
+    /// Convert into a regular [`Tilemap`], with empty positions filled with [`Tile::empty`].
+    pub fn to_tilemap(&self) -> Tilemap<C, U> {
+        let mut map = Tilemap::new(self.width, self.height, self.tileset.clone());
+        map.tiles_mut().fill(Tile::empty());
+
+        for (x, y, tile) in self.iter() {
+            map.set_tile(x, y, tile.clone());
+        }
+
+        map
+    }
+}
+
+impl<C, U> SparseTilemap<C, U>
+where
+    C: AsRef<[u8]>,
+{
+    /// Render the map onto a buffer at pixel offset `(offset_x, offset_y)`.
+    ///
+    /// Only the tiles that are set and intersect the buffer are drawn.
+    pub fn render(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+    ) {
+        let (tile_width, tile_height) = self.tileset.opts.tile_size;
+        let offset = (offset_x, offset_y);
+        let surface_size = (surface.width(), surface.height());
+        let map_size = (self.width, self.height);
+
+        let (columns, rows) =
+            self.tileset
+                .map_visible_range(offset, surface_size, map_size, (0, 0));
+
+        // offset tiles around those may reach into the buffer as well
+        let (outer_columns, outer_rows) =
+            self.tileset
+                .map_visible_range(offset, surface_size, map_size, OFFSET_MARGIN);
+
+        if outer_columns.is_empty() || outer_rows.is_empty() {
+            return;
+        }
+
+        for (&(ty, tx), tile) in self.tiles.range((outer_rows.start, 0)..(outer_rows.end, 0)) {
+            let inside = columns.contains(&tx) && rows.contains(&ty);
+
+            if !tile.visible || !outer_columns.contains(&tx) || !(inside || tile.offset != (0, 0)) {
+                continue;
+            }
+
+            self.tileset.render_map_tile(
+                surface,
+                tile,
+                (
+                    offset_x + (tx * tile_width) as i32,
+                    offset_y + (ty * tile_height) as i32,
+                ),
+                None,
+                &mut draw_tile_pixel,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{atlas, shade, tileset, Surface};
+    use alloc::vec::Vec;
+
+    #[test]
+    fn set_and_iterate() {
+        let mut map: SparseTilemap<_> = SparseTilemap::new(1000, 1000, tileset(3));
+        assert!(map.is_empty());
+
+        map.set_tile(2, 1, Tile::new(1));
+        map.set_tile(0, 3, Tile::new(2));
+        map.set_tile(1000, 0, Tile::new(2));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get_tile(2, 1).unwrap().id, 1);
+        assert!(map.get_tile(1, 1).is_none());
+
+        map.get_mut_tile(2, 1).unwrap().id = 0;
+
+        let tiles: Vec<_> = map.iter().map(|(x, y, tile)| (x, y, tile.id)).collect();
+        assert_eq!(tiles, [(2, 1, 0), (0, 3, 2)]);
+
+        assert_eq!(map.to_tilemap().get_tile(0, 3).unwrap().id, 2);
+        assert!(map.to_tilemap().get_tile(1, 1).unwrap().is_empty());
+        assert_eq!(map.remove_tile(0, 3).unwrap().id, 2);
+
+        map.clear();
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn render() {
+        let mut map: SparseTilemap<_> = SparseTilemap::new(1000, 1000, tileset(3));
+        map.set_tile(2, 1, Tile::new(0));
+        map.set_tile(0, 3, Tile::new(2));
+
+        let mut surface = Surface::new(6, 8);
+        map.render(&mut surface, 0, 0);
+        assert_eq!(surface.at(4, 2), shade(0));
+        assert_eq!(surface.at(0, 6), shade(2));
+        assert_eq!(surface.at(0, 0).a, 0);
+
+        let mut surface = Surface::new(2, 2);
+        map.render(&mut surface, -4, -2);
+        assert_eq!(surface.at(0, 0), shade(0));
+    }
+
+    /// Positions and ids of the tiles of the 3x3 maps in the anchor tests.
+    const ANCHORED: [(u32, u32, u32); 4] = [(0, 0, 0), (1, 1, 1), (2, 2, 1), (2, 0, 1)];
+
+    /// Offsets at which the anchor tests render, some with only the tiles' overhang on the buffer.
+    const ANCHOR_OFFSETS: [(i32, i32); 6] = [(0, 0), (1, 1), (-2, 3), (-6, -5), (3, -5), (5, 0)];
+
+    #[test]
+    fn anchored_tiles_match_plain_map() {
+        let mut map: SparseTilemap<_> = SparseTilemap::new(3, 3, atlas());
+        for (x, y, id) in ANCHORED {
+            map.set_tile(x, y, Tile::new(id));
+        }
+        let plain = map.to_tilemap();
+
+        for (x, y) in ANCHOR_OFFSETS {
+            let mut surface = Surface::new(5, 5);
+            map.render(&mut surface, x, y);
+            let mut reference = Surface::new(5, 5);
+            plain.render(&mut reference, x, y);
+            assert_eq!(surface.pixels, reference.pixels, "offset ({x}, {y})");
+        }
+    }
+
+    /// Tiles of the maps in the offset tests: one moved onto the buffer from outside it,
+    /// one moved off it and one in place.
+    fn offset_tiles() -> [(u32, u32, Tile); 3] {
+        [
+            (5, 0, Tile::new(1).with_offset(-7, -1)),
+            (0, 1, Tile::new(2).with_offset(0, -100)),
+            (1, 1, Tile::new(0)),
+        ]
+    }
+
+    #[test]
+    fn offset_tiles_match_plain_map() {
+        let mut map: SparseTilemap<_> = SparseTilemap::new(6, 2, tileset(3));
+        for (x, y, tile) in offset_tiles() {
+            map.set_tile(x, y, tile);
+        }
+
+        let mut surface = Surface::new(4, 4);
+        map.render(&mut surface, 0, 0);
+        let mut reference = Surface::new(4, 4);
+        map.to_tilemap().render(&mut reference, 0, 0);
+        assert_eq!(surface.pixels, reference.pixels);
+        assert_eq!(surface.at(3, 0), shade(1));
+    }
+}
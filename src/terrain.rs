@@ -0,0 +1,253 @@
+use crate::{TileId, Tilemap, Tileset};
+
+/// Id of a terrain type used to label tiles, see [`Tileset::set_terrain`].
+pub type TerrainId = u16;
+
+/// Which parts of tiles are labeled with terrains.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TerrainKind {
+    /// Corners, in order top-left, top-right, bottom-right, bottom-left.
+    ///
+    /// Each corner is shared by 4 map tiles.
+    #[default]
+    Corners,
+    /// Edges, in order top, right, bottom, left.
+    ///
+    /// Each edge is shared by 2 map tiles.
+    Edges,
+}
+
+/// Terrain labels of a tile in a tileset (a Wang tile).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TileTerrain {
+    /// Which parts of the tile are labeled.
+    pub kind: TerrainKind,
+    /// Terrains of the tile's corners or edges, clockwise from the top(-left).
+    pub labels: [TerrainId; 4],
+}
+
+impl TileTerrain {
+    /// Label the corners of a tile: top-left, top-right, bottom-right, bottom-left.
+    #[inline]
+    pub const fn corners(labels: [TerrainId; 4]) -> Self {
+        Self {
+            kind: TerrainKind::Corners,
+            labels,
+        }
+    }
+
+    /// Label the edges of a tile: top, right, bottom, left.
+    #[inline]
+    pub const fn edges(labels: [TerrainId; 4]) -> Self {
+        Self {
+            kind: TerrainKind::Edges,
+            labels,
+        }
+    }
+}
+
+impl<C, M> Tileset<C, M> {
+    /// Label the corners or edges of tile `id` with terrains.
+    ///
+    /// Used by [`Tilemap::paint_terrain`] to pick transition tiles.
+    /// Replaces the previous labels of the tile, if any.
+    #[inline]
+    pub fn set_terrain(&mut self, id: TileId, terrain: TileTerrain) {
+        self.terrains.insert(id, terrain);
+    }
+
+    /// Get the terrain labels of tile `id`.
+    #[inline]
+    pub fn terrain(&self, id: TileId) -> Option<TileTerrain> {
+        self.terrains.get(&id).copied()
+    }
+
+    /// Remove the terrain labels of tile `id`, returning them.
+    #[inline]
+    pub fn remove_terrain(&mut self, id: TileId) -> Option<TileTerrain> {
+        self.terrains.remove(&id)
+    }
+
+    /// Find the tile with the smallest id labeled exactly with `terrain`.
+    #[inline]
+    pub fn terrain_tile(&self, terrain: TileTerrain) -> Option<TileId> {
+        self.terrains
+            .iter()
+            .find(|(_, &labels)| labels == terrain)
+            .map(|(&id, _)| id)
+    }
+
+    /// Find the tile of the same kind that has the same labels as `terrain` in all the `required` positions
+    /// and the most matching labels in the rest.
+    fn closest_terrain_tile(&self, terrain: TileTerrain, required: [bool; 4]) -> Option<TileId> {
+        let mut best: Option<(TileId, usize)> = None;
+
+        for (&id, candidate) in &self.terrains {
+            if candidate.kind != terrain.kind {
+                continue;
+            }
+
+            let matching = |i: usize| candidate.labels[i] == terrain.labels[i];
+
+            if (0..4).any(|i| required[i] && !matching(i)) {
+                continue;
+            }
+
+            let score = (0..4).filter(|&i| matching(i)).count();
+
+            if best.map_or(true, |(_, best_score)| score > best_score) {
+                best = Some((id, score));
+            }
+        }
+
+        best.map(|(id, _)| id)
+    }
+}
+
+impl<C, U, M> Tilemap<C, U, M> {
+    /// Paint terrain `terrain` at (x, y), replacing the tile with one fully labeled with it,
+    /// then replace the neighbors sharing a corner (or an edge) with it with matching transition tiles.
+    ///
+    /// Only the ids of tiles change. Neighbors without labels of the same kind are left alone,
+    /// and so are those for which the tileset has no tile with the painted labels.
+    /// Returns `false` if the position is out of bounds or the tileset has no tile fully labeled with `terrain`.
+    pub fn paint_terrain(&mut self, x: u32, y: u32, kind: TerrainKind, terrain: TerrainId) -> bool {
+        let full = TileTerrain {
+            kind,
+            labels: [terrain; 4],
+        };
+
+        let Some(id) = self.tileset().terrain_tile(full) else {
+            return false;
+        };
+
+        let Some(tile) = self.get_mut_tile(x, y) else {
+            return false;
+        };
+
+        tile.id = id;
+
+        for dy in -1..=1i64 {
+            for dx in -1..=1i64 {
+                let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+
+                if (dx, dy) == (0, 0) || nx < 0 || ny < 0 {
+                    continue;
+                }
+
+                let Some(neighbor) = self.get_tile(nx as u32, ny as u32) else {
+                    continue;
+                };
+
+                let Some(mut labels) = self
+                    .tileset()
+                    .terrain(neighbor.id)
+                    .filter(|labels| labels.kind == kind)
+                else {
+                    continue;
+                };
+
+                // positions of the neighbor's labels shared with the painted tile
+                let shared = match kind {
+                    TerrainKind::Corners => [(0, 0), (1, 0), (1, 1), (0, 1)].map(|(cx, cy)| {
+                        (0..=1).contains(&(dx + cx)) && (0..=1).contains(&(dy + cy))
+                    }),
+                    TerrainKind::Edges => [
+                        (dx, dy) == (0, 1),
+                        (dx, dy) == (-1, 0),
+                        (dx, dy) == (0, -1),
+                        (dx, dy) == (1, 0),
+                    ],
+                };
+
+                if !shared.contains(&true) {
+                    continue;
+                }
+
+                for (label, _) in labels.labels.iter_mut().zip(shared).filter(|(_, s)| *s) {
+                    *label = terrain;
+                }
+
+                if let Some(id) = self.tileset().closest_terrain_tile(labels, shared) {
+                    if let Some(tile) = self.get_mut_tile(nx as u32, ny as u32) {
+                        tile.id = id;
+                    }
+                }
+            }
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::tileset;
+    use crate::Tile;
+
+    #[test]
+    fn paint_corners() {
+        // every combination of terrains 0 and 1, with the corners as the bits of the id
+        let mut tileset = tileset(16);
+        for id in 0..16 {
+            let corner = |bit: u32| ((id >> bit) & 1) as TerrainId;
+            tileset.set_terrain(
+                id,
+                TileTerrain::corners([corner(0), corner(1), corner(2), corner(3)]),
+            );
+        }
+
+        assert_eq!(tileset.terrain_tile(TileTerrain::corners([1; 4])), Some(15));
+        assert_eq!(tileset.terrain(3), Some(TileTerrain::corners([1, 1, 0, 0])));
+
+        let mut map = Tilemap::new(3, 3, tileset);
+        map.fill_rect(0, 0, 3, 3, Tile::<()>::new(0));
+
+        assert!(map.paint_terrain(1, 1, TerrainKind::Corners, 1));
+        assert_eq!(map.get_tile(1, 1).unwrap().id, 15);
+
+        // neighbors get the corners they share with the painted tile
+        assert_eq!(map.get_tile(0, 0).unwrap().id, 4);
+        assert_eq!(map.get_tile(1, 0).unwrap().id, 4 | 8);
+        assert_eq!(map.get_tile(2, 0).unwrap().id, 8);
+        assert_eq!(map.get_tile(2, 1).unwrap().id, 1 | 8);
+        assert_eq!(map.get_tile(2, 2).unwrap().id, 1);
+
+        assert!(!map.paint_terrain(3, 1, TerrainKind::Corners, 1));
+        assert!(!map.paint_terrain(0, 0, TerrainKind::Corners, 5));
+        assert!(!map.paint_terrain(0, 0, TerrainKind::Edges, 1));
+
+        assert!(map.paint_terrain(0, 0, TerrainKind::Corners, 1));
+        assert_eq!(map.get_tile(1, 0).unwrap().id, 1 | 4 | 8);
+
+        let mut tileset = map.tileset().clone();
+        assert_eq!(
+            tileset.remove_terrain(0),
+            Some(TileTerrain::corners([0; 4]))
+        );
+        assert_eq!(tileset.terrain(0), None);
+    }
+
+    #[test]
+    fn paint_edges() {
+        let mut tileset = tileset(3);
+        tileset.set_terrain(0, TileTerrain::edges([0; 4]));
+        tileset.set_terrain(1, TileTerrain::edges([1; 4]));
+        tileset.set_terrain(2, TileTerrain::edges([0, 0, 1, 0]));
+
+        let mut map = Tilemap::new(3, 3, tileset);
+        map.fill_rect(0, 0, 3, 3, Tile::<()>::new(0));
+
+        assert!(map.paint_terrain(1, 1, TerrainKind::Edges, 1));
+        assert_eq!(map.get_tile(1, 0).unwrap().id, 2);
+
+        // no tile has terrain 1 on only its left edge, so the closest one is used
+        assert_eq!(map.get_tile(2, 1).unwrap().id, 1);
+
+        // diagonal neighbors don't share an edge
+        assert_eq!(map.get_tile(0, 0).unwrap().id, 0);
+    }
+}
@@ -0,0 +1,89 @@
+//! Helpers shared by the unit tests.
+
+use crate::{Buffer, BufferMut, Color, TileAnchor, TileId, Tileset, TilesetOptions};
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Buffer the tests render onto, initially fully transparent.
+pub(crate) struct Surface {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) pixels: Vec<Color>,
+}
+
+impl Surface {
+    pub(crate) fn new(width: u32, height: u32) -> Self {
+        Self::filled(width, height, Color::default())
+    }
+
+    pub(crate) fn filled(width: u32, height: u32, color: Color) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![color; (width * height) as usize],
+        }
+    }
+
+    pub(crate) fn at(&self, x: u32, y: u32) -> Color {
+        self.pixels[(y * self.width + x) as usize]
+    }
+}
+
+impl Buffer<Color> for Surface {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn get(&self, x: u32, y: u32) -> &Color {
+        &self.pixels[(y * self.width + x) as usize]
+    }
+}
+
+impl BufferMut<Color> for Surface {
+    fn get_mut(&mut self, x: u32, y: u32) -> &mut Color {
+        &mut self.pixels[(y * self.width + x) as usize]
+    }
+}
+
+/// Opaque color of tile `id` of [`tileset`], with its red channel depending on the id.
+pub(crate) fn shade(id: TileId) -> Color {
+    Color::new(10 + id as u8 * 10, 0, 0, 255)
+}
+
+/// Tileset of `count` 2x2 tiles in a row, each filled with [`shade`] of its id.
+pub(crate) fn tileset(count: u32) -> Tileset<Vec<u8>> {
+    let pixels: Vec<_> = (0..2)
+        .flat_map(|_| (0..count * 2).map(|x| shade(x / 2)))
+        .collect();
+
+    tileset_from(&pixels, count * 2, TilesetOptions::new(2, 2))
+}
+
+/// Tileset made from `width` pixels wide `pixels`.
+pub(crate) fn tileset_from(pixels: &[Color], width: u32, opts: TilesetOptions) -> Tileset<Vec<u8>> {
+    let height = pixels.len() as u32 / width;
+    let data = pixels
+        .iter()
+        .flat_map(|color| [color.r, color.g, color.b, color.a])
+        .collect();
+
+    Tileset::new(data, width, height, opts).unwrap()
+}
+
+/// Atlas with a 2x2 tile filled with [`shade`] of 0 and a 4x3 one filled with [`shade`] of 1,
+/// anchored to the bottom center of 2x2 cells.
+pub(crate) fn atlas() -> Tileset<Vec<u8>> {
+    let pixels: Vec<_> = (0..3)
+        .flat_map(|_| (0..6).map(|x| shade((x >= 2) as TileId)))
+        .collect();
+    let opts = TilesetOptions::new(2, 2).with_anchor(TileAnchor::BottomCenter);
+
+    tileset_from(&pixels, 6, opts)
+        .with_regions(vec![(0, 0, 2, 2), (2, 0, 4, 3)])
+        .unwrap()
+}
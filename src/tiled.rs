@@ -0,0 +1,894 @@
+use crate::{LayeredTilemap, Rotation, Tile, TileId, Tileset, TilesetOptions};
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use simple_blit::BlitOptions;
+
+/// Custom properties of a Tiled tile or layer, by name.
+pub type TiledProperties = BTreeMap<String, String>;
+
+/// Bit set in a global tile id when the tile is flipped horizontally.
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+/// Bit set in a global tile id when the tile is flipped vertically.
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+/// Bit set in a global tile id when the tile is flipped diagonally (its x and y axes are swapped).
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+/// All the flag bits of a global tile id (including the unsupported hexagonal rotation).
+const FLAGS: u32 = 0xf000_0000;
+
+/// Error that occurred while reading a Tiled file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TiledError {
+    /// The file isn't well-formed XML.
+    InvalidXml,
+    /// An element is missing.
+    MissingElement(&'static str),
+    /// An attribute is missing or has an invalid value.
+    InvalidAttribute(&'static str),
+    /// Layer data is compressed or uses an unknown encoding.
+    UnsupportedEncoding(String),
+    /// Infinite maps aren't supported.
+    InfiniteMap,
+    /// Layer data doesn't match the layer's size.
+    InvalidLayerData,
+    /// There's no tileset at the index.
+    InvalidTilesetIndex(usize),
+    /// A layer has a tile (with this global id, without the flip flags) from a different tileset
+    /// than the one the map is converted with.
+    ForeignTile(u32),
+    /// The map is too large or a layer's size differs from the map's.
+    InvalidMapSize,
+}
+
+impl fmt::Display for TiledError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidXml => write!(f, "invalid XML"),
+            Self::MissingElement(name) => write!(f, "missing element `{name}`"),
+            Self::InvalidAttribute(name) => write!(f, "missing or invalid attribute `{name}`"),
+            Self::UnsupportedEncoding(encoding) => {
+                write!(f, "unsupported layer data encoding `{encoding}`")
+            }
+            Self::InfiniteMap => write!(f, "infinite maps are not supported"),
+            Self::InvalidLayerData => write!(f, "layer data doesn't match the layer size"),
+            Self::InvalidTilesetIndex(index) => write!(f, "no tileset at index {index}"),
+            Self::ForeignTile(gid) => {
+                write!(f, "tile {gid} belongs to a different tileset")
+            }
+            Self::InvalidMapSize => write!(f, "map is too large or a layer doesn't match its size"),
+        }
+    }
+}
+
+/// A tileset from a TSX file or embedded into a TMX map.
+///
+/// The image itself isn't loaded: load [`image`](Self::image) yourself and create a [`Tileset`]
+/// from it with [`options`](Self::options).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TiledTileset {
+    /// Global id of the tileset's first tile. Always 1 for tilesets parsed from TSX files.
+    pub first_gid: u32,
+    /// Path of the external TSX file, if the tileset isn't embedded into the map.
+    ///
+    /// External tilesets have to be loaded with [`TiledTileset::parse_tsx`]
+    /// and put in place with [`TiledMap::resolve_tileset`].
+    pub source: Option<String>,
+    /// Name of the tileset.
+    pub name: String,
+    /// Size (width x height) of a single tile.
+    pub tile_size: (u32, u32),
+    /// Distance between the image edges and the tiles.
+    pub margin: u32,
+    /// Distance between neighboring tiles.
+    pub spacing: u32,
+    /// Amount of tiles in the tileset.
+    pub tile_count: u32,
+    /// Path of the tileset's image.
+    pub image: Option<String>,
+    /// Transparent color of the image, if any.
+    pub transparent_color: Option<crate::Color>,
+    /// Custom properties of the tiles, by tile id.
+    pub tile_properties: BTreeMap<TileId, TiledProperties>,
+}
+
+impl TiledTileset {
+    /// Parse a TSX tileset.
+    pub fn parse_tsx(xml: &str) -> Result<Self, TiledError> {
+        let root = parse_xml(xml)?;
+
+        if root.name != "tileset" {
+            return Err(TiledError::MissingElement("tileset"));
+        }
+
+        let mut tileset = Self::from_element(&root)?;
+        tileset.first_gid = 1;
+
+        Ok(tileset)
+    }
+
+    /// Options to create a [`Tileset`] from this tileset's image with.
+    pub fn options(&self) -> TilesetOptions {
+        let mut opts = TilesetOptions::new(self.tile_size.0, self.tile_size.1)
+            .with_offset(self.margin, self.margin)
+            .with_margin(self.spacing, self.spacing);
+
+        opts.key_color = self.transparent_color;
+        opts
+    }
+
+    /// Whether global tile id `gid` (without the flip flags) belongs to this tileset.
+    #[inline]
+    pub fn contains_gid(&self, gid: u32) -> bool {
+        gid >= self.first_gid && gid - self.first_gid < self.tile_count
+    }
+
+    fn from_element(element: &Element) -> Result<Self, TiledError> {
+        let first_gid = element.attr_or("firstgid", 1)?;
+
+        if let Some(source) = element.attr("source") {
+            return Ok(Self {
+                first_gid,
+                source: Some(source.to_string()),
+                ..Self::default()
+            });
+        }
+
+        let image = element.child("image");
+
+        let mut tile_properties = BTreeMap::new();
+
+        for tile in element.children("tile") {
+            let properties = read_properties(tile);
+
+            if !properties.is_empty() {
+                tile_properties.insert(tile.parse_attr("id")?, properties);
+            }
+        }
+
+        Ok(Self {
+            first_gid,
+            source: None,
+            name: element.attr("name").unwrap_or_default().to_string(),
+            tile_size: (
+                element.parse_attr("tilewidth")?,
+                element.parse_attr("tileheight")?,
+            ),
+            margin: element.attr_or("margin", 0)?,
+            spacing: element.attr_or("spacing", 0)?,
+            tile_count: element.attr_or("tilecount", 0)?,
+            image: image.and_then(|image| Some(image.attr("source")?.to_string())),
+            transparent_color: match image.and_then(|image| image.attr("trans")) {
+                Some(trans) => {
+                    Some(parse_color(trans).ok_or(TiledError::InvalidAttribute("trans"))?)
+                }
+                None => None,
+            },
+            tile_properties,
+        })
+    }
+}
+
+/// A tile layer of a [`TiledMap`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TiledLayer {
+    /// Name of the layer.
+    pub name: String,
+    /// Width of the layer in tiles.
+    pub width: u32,
+    /// Height of the layer in tiles.
+    pub height: u32,
+    /// Whether the layer is shown.
+    pub visible: bool,
+    /// Opacity of the layer.
+    pub opacity: u8,
+    /// Global tile ids (including the flip flags), row by row. 0 means no tile.
+    pub gids: Vec<u32>,
+    /// Custom properties of the layer.
+    pub properties: TiledProperties,
+}
+
+/// A map parsed from a TMX file.
+///
+/// Supports orthogonal, finite maps with layer data encoded as CSV, uncompressed base64 or XML.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TiledMap {
+    /// Width of the map in tiles.
+    pub width: u32,
+    /// Height of the map in tiles.
+    pub height: u32,
+    /// Size (width x height) of a single tile.
+    pub tile_size: (u32, u32),
+    /// Tilesets used by the map, ordered by their first global id.
+    pub tilesets: Vec<TiledTileset>,
+    /// Tile layers, bottom to top.
+    pub layers: Vec<TiledLayer>,
+    /// Custom properties of the map.
+    pub properties: TiledProperties,
+}
+
+impl TiledMap {
+    /// Parse a TMX map.
+    pub fn parse(xml: &str) -> Result<Self, TiledError> {
+        let root = parse_xml(xml)?;
+
+        if root.name != "map" {
+            return Err(TiledError::MissingElement("map"));
+        }
+
+        if root.attr("infinite") == Some("1") {
+            return Err(TiledError::InfiniteMap);
+        }
+
+        let mut tilesets = root
+            .children("tileset")
+            .map(TiledTileset::from_element)
+            .collect::<Result<Vec<_>, _>>()?;
+        tilesets.sort_by_key(|tileset| tileset.first_gid);
+
+        let layers = root
+            .children("layer")
+            .map(read_layer)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            width: root.parse_attr("width")?,
+            height: root.parse_attr("height")?,
+            tile_size: (
+                root.parse_attr("tilewidth")?,
+                root.parse_attr("tileheight")?,
+            ),
+            tilesets,
+            layers,
+            properties: read_properties(&root),
+        })
+    }
+
+    /// Replace the external tileset at index `index` with `tileset` (parsed with [`TiledTileset::parse_tsx`]),
+    /// keeping its first global id and source.
+    ///
+    /// Does nothing if the index is out of bounds.
+    pub fn resolve_tileset(&mut self, index: usize, tileset: TiledTileset) {
+        if let Some(old) = self.tilesets.get_mut(index) {
+            *old = TiledTileset {
+                first_gid: old.first_gid,
+                source: old.source.take(),
+                ..tileset
+            };
+        }
+    }
+
+    /// Index of the tileset the tile with global id `gid` belongs to. The flip flags are ignored.
+    pub fn tileset_index(&self, gid: u32) -> Option<usize> {
+        let gid = gid & !FLAGS;
+
+        self.tilesets
+            .iter()
+            .rposition(|tileset| gid != 0 && tileset.first_gid <= gid)
+    }
+
+    /// Convert the map into a [`LayeredTilemap`] using `tileset`, created from the image
+    /// of the map's tileset at index `tileset_index`.
+    ///
+    /// Every Tiled layer becomes a layer, keeping its visibility and opacity.
+    /// Tiles keep their flips (diagonal ones become rotations) and get the custom properties of their tileset tile
+    /// as user data. Empty tiles become invisible [empty](Tile::empty) tiles.
+    ///
+    /// A [`LayeredTilemap`] only has one tileset, so maps using several tilesets have to be split up
+    /// into one map per tileset, e.g. by layer.
+    /// Returns an error if there's no tileset at `tileset_index`, a layer has tiles from any other tileset,
+    /// the amount of tiles doesn't fit into a `u32` or a layer isn't as large as the map.
+    pub fn to_layered<C>(
+        &self,
+        tileset_index: usize,
+        tileset: Tileset<C>,
+    ) -> Result<LayeredTilemap<C, TiledProperties>, TiledError> {
+        let tiled_tileset = self
+            .tilesets
+            .get(tileset_index)
+            .ok_or(TiledError::InvalidTilesetIndex(tileset_index))?;
+
+        if self.width.checked_mul(self.height).is_none()
+            || self
+                .layers
+                .iter()
+                .any(|layer| (layer.width, layer.height) != (self.width, self.height))
+        {
+            return Err(TiledError::InvalidMapSize);
+        }
+
+        let mut map = LayeredTilemap::new(self.width, self.height, tileset);
+
+        for layer in &self.layers {
+            let index = map.add_layer();
+
+            let target = map.layer_mut(index).expect("layer was just added");
+            target.visible = layer.visible;
+            target.opacity = layer.opacity;
+
+            for (tile, &gid) in target.tiles_mut().iter_mut().zip(&layer.gids) {
+                let id = gid & !FLAGS;
+
+                if id == 0 {
+                    *tile = Tile::empty().with_visibility(false);
+                    continue;
+                }
+
+                if !tiled_tileset.contains_gid(id) {
+                    return Err(TiledError::ForeignTile(id));
+                }
+
+                let id = id - tiled_tileset.first_gid;
+
+                let (opts, rotation) = flip_options(gid);
+
+                *tile = Tile::new(id)
+                    .with_blit_options(opts)
+                    .with_rotation(rotation)
+                    .with_user_data(
+                        tiled_tileset
+                            .tile_properties
+                            .get(&id)
+                            .cloned()
+                            .unwrap_or_default(),
+                    );
+            }
+        }
+
+        Ok(map)
+    }
+}
+
+/// Blit options and rotation matching the flip flags of a global tile id.
+///
+/// Tiled flips diagonally first, which is the same as some flip followed by a rotation.
+#[inline]
+fn flip_options(gid: u32) -> (BlitOptions, Rotation) {
+    match (
+        gid & FLIPPED_HORIZONTALLY != 0,
+        gid & FLIPPED_VERTICALLY != 0,
+        gid & FLIPPED_DIAGONALLY != 0,
+    ) {
+        (false, false, false) => (BlitOptions::None, Rotation::None),
+        (true, false, false) => (BlitOptions::FlipHorizontal, Rotation::None),
+        (false, true, false) => (BlitOptions::FlipVertical, Rotation::None),
+        (true, true, false) => (BlitOptions::FlipBoth, Rotation::None),
+        (false, false, true) => (BlitOptions::FlipHorizontal, Rotation::Rotate270),
+        (true, false, true) => (BlitOptions::None, Rotation::Rotate90),
+        (false, true, true) => (BlitOptions::None, Rotation::Rotate270),
+        (true, true, true) => (BlitOptions::FlipHorizontal, Rotation::Rotate90),
+    }
+}
+
+fn read_layer(element: &Element) -> Result<TiledLayer, TiledError> {
+    let width: u32 = element.parse_attr("width")?;
+    let height: u32 = element.parse_attr("height")?;
+    let data = element
+        .child("data")
+        .ok_or(TiledError::MissingElement("data"))?;
+
+    if data.attr("compression").is_some() {
+        return Err(TiledError::UnsupportedEncoding(
+            data.attr("compression").unwrap_or_default().to_string(),
+        ));
+    }
+
+    let gids = match data.attr("encoding") {
+        Some("csv") => data
+            .text
+            .split(',')
+            .map(|gid| gid.trim().parse().map_err(|_| TiledError::InvalidLayerData))
+            .collect::<Result<Vec<u32>, _>>()?,
+        Some("base64") => decode_base64(&data.text)
+            .ok_or(TiledError::InvalidLayerData)?
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect(),
+        None => data
+            .children("tile")
+            .map(|tile| tile.attr_or("gid", 0))
+            .collect::<Result<Vec<u32>, _>>()?,
+        Some(encoding) => return Err(TiledError::UnsupportedEncoding(encoding.to_string())),
+    };
+
+    if gids.len() != (width as usize) * (height as usize) {
+        return Err(TiledError::InvalidLayerData);
+    }
+
+    let opacity: f32 = element.attr_or("opacity", 1.)?;
+
+    Ok(TiledLayer {
+        name: element.attr("name").unwrap_or_default().to_string(),
+        width,
+        height,
+        visible: element.attr("visible") != Some("0"),
+        opacity: (opacity.clamp(0., 1.) * 255. + 0.5) as u8,
+        gids,
+        properties: read_properties(element),
+    })
+}
+
+fn read_properties(element: &Element) -> TiledProperties {
+    element
+        .children("properties")
+        .flat_map(|properties| properties.children("property"))
+        .filter_map(|property| {
+            let value = property.attr("value").unwrap_or(&property.text);
+            Some((property.attr("name")?.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Parse a `#rrggbb` or `rrggbb` color.
+fn parse_color(s: &str) -> Option<crate::Color> {
+    let s = s.strip_prefix('#').unwrap_or(s);
+
+    if s.len() != 6 {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(s.get(i..i + 2)?, 16).ok();
+
+    Some(crate::Color::new(
+        channel(0)?,
+        channel(2)?,
+        channel(4)?,
+        255,
+    ))
+}
+
+fn decode_base64(s: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for c in s.bytes().filter(|c| !c.is_ascii_whitespace() && *c != b'=') {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(bytes)
+}
+
+/// XML element, with just enough information for reading Tiled files.
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attrs: Vec<(String, String)>,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(attr, _)| attr == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn parse_attr<T: core::str::FromStr>(&self, name: &'static str) -> Result<T, TiledError> {
+        self.attr(name)
+            .and_then(|value| value.parse().ok())
+            .ok_or(TiledError::InvalidAttribute(name))
+    }
+
+    fn attr_or<T: core::str::FromStr>(
+        &self,
+        name: &'static str,
+        default: T,
+    ) -> Result<T, TiledError> {
+        match self.attr(name) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| TiledError::InvalidAttribute(name)),
+            None => Ok(default),
+        }
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+}
+
+/// Parse an XML document into its root element.
+///
+/// Only supports what Tiled writes: no DTDs and only the predefined entities.
+fn parse_xml(xml: &str) -> Result<Element, TiledError> {
+    let mut stack: Vec<Element> = Vec::new();
+    let mut root = None;
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        if let Some(parent) = stack.last_mut() {
+            parent.text.push_str(&unescape(&rest[..start])?);
+        }
+
+        rest = &rest[start..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = &comment[comment.find("-->").ok_or(TiledError::InvalidXml)? + 3..];
+        } else if rest.starts_with("<?") || rest.starts_with("<!") {
+            rest = &rest[rest.find('>').ok_or(TiledError::InvalidXml)? + 1..];
+        } else if let Some(end_tag) = rest.strip_prefix("</") {
+            let end = end_tag.find('>').ok_or(TiledError::InvalidXml)?;
+            let element = stack.pop().ok_or(TiledError::InvalidXml)?;
+
+            if element.name != end_tag[..end].trim() {
+                return Err(TiledError::InvalidXml);
+            }
+
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(element),
+                None => root = Some(element),
+            }
+
+            rest = &end_tag[end + 1..];
+        } else {
+            let (element, closed, tail) = parse_start_tag(&rest[1..])?;
+
+            if closed {
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => root = Some(element),
+                }
+            } else {
+                stack.push(element);
+            }
+
+            rest = tail;
+        }
+
+        if root.is_some() {
+            break;
+        }
+    }
+
+    root.ok_or(TiledError::InvalidXml)
+}
+
+/// Parse a start tag (after the `<`), returning the element, whether it's self-closing and the rest of the input.
+fn parse_start_tag(s: &str) -> Result<(Element, bool, &str), TiledError> {
+    let name_end = s
+        .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .ok_or(TiledError::InvalidXml)?;
+
+    let mut element = Element {
+        name: s[..name_end].to_string(),
+        ..Element::default()
+    };
+    let mut rest = s[name_end..].trim_start();
+
+    loop {
+        if let Some(tail) = rest.strip_prefix("/>") {
+            return Ok((element, true, tail));
+        }
+
+        if let Some(tail) = rest.strip_prefix('>') {
+            return Ok((element, false, tail));
+        }
+
+        let eq = rest.find('=').ok_or(TiledError::InvalidXml)?;
+        let name = rest[..eq].trim().to_string();
+        let value = rest[eq + 1..].trim_start();
+
+        let quote = value.chars().next().ok_or(TiledError::InvalidXml)?;
+
+        if quote != '"' && quote != '\'' {
+            return Err(TiledError::InvalidXml);
+        }
+
+        let end = value[1..].find(quote).ok_or(TiledError::InvalidXml)?;
+
+        element
+            .attrs
+            .push((name, unescape(&value[1..end + 1])?.into_owned()));
+
+        rest = value[end + 2..].trim_start();
+    }
+}
+
+/// Replace the predefined and numeric entities in a piece of XML text.
+fn unescape(s: &str) -> Result<alloc::borrow::Cow<'_, str>, TiledError> {
+    if !s.contains('&') {
+        return Ok(alloc::borrow::Cow::Borrowed(s));
+    }
+
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        let end = rest.find(';').ok_or(TiledError::InvalidXml)?;
+        let entity = &rest[..end];
+
+        let c = match entity {
+            "lt" => '<',
+            "gt" => '>',
+            "amp" => '&',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = if let Some(hex) = entity.strip_prefix("#x") {
+                    u32::from_str_radix(hex, 16).ok()
+                } else if let Some(dec) = entity.strip_prefix('#') {
+                    dec.parse().ok()
+                } else {
+                    None
+                };
+
+                code.and_then(char::from_u32)
+                    .ok_or(TiledError::InvalidXml)?
+            }
+        };
+
+        result.push(c);
+        rest = &rest[end + 1..];
+    }
+
+    result.push_str(rest);
+
+    Ok(alloc::borrow::Cow::Owned(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{tileset_from, Surface};
+    use crate::Color;
+    use alloc::vec;
+
+    const TMX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- comment with <tags> -->
+<map version="1.10" orientation="orthogonal" width="3" height="2" tilewidth="2" tileheight="2" infinite="0">
+ <properties><property name="title" value="A &amp; B &#65;&#x42;"/></properties>
+ <tileset firstgid="1" name="t" tilewidth="2" tileheight="2" spacing="1" margin="0" tilecount="3" columns="3">
+  <image source="t.png" width="8" height="2" trans="ff00ff"/>
+  <tile id="1"><properties><property name="solid" type="bool" value="true"/></properties></tile>
+ </tileset>
+ <tileset firstgid="4" source="other.tsx"/>
+ <layer id="1" name="ground" width="3" height="2">
+  <data encoding="csv">
+1,2,3,
+0,2147483650,1
+</data>
+ </layer>
+ <layer id="2" name="top" width="3" height="2" visible="0" opacity="0.5">
+  <properties><property name="note">&lt;multi&gt;</property></properties>
+  <data encoding="base64">
+   AgAAAAAAAAAAAAAAAAAAAAAAAAAAAABA
+  </data>
+ </layer>
+ <layer id="3" name="x" width="3" height="2"><data><tile gid="3"/><tile/><tile/><tile/><tile/><tile gid="5"/></data></layer>
+</map>"#;
+
+    /// Tileset of `width` by `height` transparent pixels.
+    fn blank(width: u32, height: u32, opts: TilesetOptions) -> Tileset<Vec<u8>> {
+        tileset_from(
+            &vec![Color::default(); (width * height) as usize],
+            width,
+            opts,
+        )
+    }
+
+    #[test]
+    fn parse_tmx() {
+        let map = TiledMap::parse(TMX).unwrap();
+        assert_eq!((map.width, map.height, map.tile_size), (3, 2, (2, 2)));
+        assert_eq!(map.properties["title"], "A & B AB");
+
+        assert_eq!(map.tilesets.len(), 2);
+        assert_eq!(map.tilesets[1].source.as_deref(), Some("other.tsx"));
+
+        let tileset = &map.tilesets[0];
+        assert_eq!(tileset.image.as_deref(), Some("t.png"));
+        assert_eq!(
+            tileset.transparent_color,
+            Some(Color::new(255, 0, 255, 255))
+        );
+        assert_eq!(tileset.options().spacing, (1, 1));
+        assert_eq!(tileset.tile_properties[&1]["solid"], "true");
+
+        assert_eq!(map.layers.len(), 3);
+        assert_eq!(map.layers[0].gids, [1, 2, 3, 0, 0x8000_0002, 1]);
+        assert!(map.layers[0].visible);
+        assert_eq!(map.layers[0].opacity, 255);
+
+        assert_eq!(map.layers[1].gids, [2, 0, 0, 0, 0, 0x4000_0000]);
+        assert!(!map.layers[1].visible);
+        assert_eq!(map.layers[1].opacity, 128);
+        assert_eq!(map.layers[1].properties["note"], "<multi>");
+
+        assert_eq!(map.layers[2].gids, [3, 0, 0, 0, 0, 5]);
+
+        assert_eq!(map.tileset_index(5), Some(1));
+        assert_eq!(map.tileset_index(0x8000_0002), Some(0));
+        assert_eq!(map.tileset_index(0), None);
+    }
+
+    #[test]
+    fn resolve_external_tileset() {
+        let mut map = TiledMap::parse(TMX).unwrap();
+        let tsx = TiledTileset::parse_tsx(
+            r#"<tileset name="o" tilewidth="2" tileheight="2" tilecount="4" margin="1"><image source="o.png"/></tileset>"#,
+        )
+        .unwrap();
+
+        map.resolve_tileset(1, tsx);
+
+        let tileset = &map.tilesets[1];
+        assert_eq!(tileset.first_gid, 4);
+        assert_eq!(tileset.source.as_deref(), Some("other.tsx"));
+        assert_eq!(tileset.tile_count, 4);
+        assert_eq!(tileset.options().offset, (1, 1));
+    }
+
+    #[test]
+    fn to_layered() {
+        let mut map = TiledMap::parse(TMX).unwrap();
+        let opts = map.tilesets[0].options();
+
+        // the last layer has a tile from the external tileset
+        assert_eq!(
+            map.to_layered(0, blank(8, 2, opts.clone())).err(),
+            Some(TiledError::ForeignTile(5))
+        );
+        assert_eq!(
+            map.to_layered(2, blank(8, 2, opts.clone())).err(),
+            Some(TiledError::InvalidTilesetIndex(2))
+        );
+
+        map.layers.pop();
+
+        let mut resized = map.clone();
+        resized.layers[0].width = 2;
+        assert_eq!(
+            resized.to_layered(0, blank(8, 2, opts.clone())).err(),
+            Some(TiledError::InvalidMapSize)
+        );
+        resized.width = u32::MAX;
+        resized.height = 2;
+        assert_eq!(
+            resized.to_layered(0, blank(8, 2, opts.clone())).err(),
+            Some(TiledError::InvalidMapSize)
+        );
+
+        let layered = map.to_layered(0, blank(8, 2, opts)).unwrap();
+        assert_eq!(layered.layer_count(), 2);
+        assert!(!layered.layer(1).unwrap().visible);
+
+        assert_eq!(layered.get_tile(0, 0, 0).unwrap().id, 0);
+        let empty = layered.get_tile(0, 0, 1).unwrap();
+        assert!(empty.is_empty() && !empty.visible);
+
+        let flipped = layered.get_tile(0, 1, 1).unwrap();
+        assert_eq!(flipped.id, 1);
+        assert_eq!(flipped.opts, BlitOptions::FlipHorizontal);
+        assert_eq!(flipped.data["solid"], "true");
+    }
+
+    #[test]
+    fn flip_flags_match_tiled() {
+        let [a, b, c, d] = [1, 2, 3, 4].map(|n| Color::new(n, 0, 0, 255));
+
+        // every combination of horizontal, vertical and diagonal flips, in that order of the bits
+        let tmx = r#"<map width="8" height="1" tilewidth="2" tileheight="2">
+ <tileset firstgid="1" tilewidth="2" tileheight="2" tilecount="1"/>
+ <layer width="8" height="1"><data encoding="csv">
+1,2147483649,1073741825,3221225473,536870913,2684354561,1610612737,3758096385
+</data></layer>
+</map>"#;
+        let map = TiledMap::parse(tmx).unwrap();
+
+        let tileset = tileset_from(&[a, b, c, d], 2, map.tilesets[0].options());
+        let layered = map.to_layered(0, tileset).unwrap();
+
+        let mut surface = Surface::new(16, 2);
+        layered.render(&mut surface, 0, 0);
+
+        let expected = [
+            [a, b, c, d],
+            [b, a, d, c],
+            [c, d, a, b],
+            [d, c, b, a],
+            // diagonal flips swap the x and y axes before the other flips
+            [a, c, b, d],
+            [c, a, d, b],
+            [b, d, a, c],
+            [d, b, c, a],
+        ];
+
+        for (i, pixels) in expected.iter().enumerate() {
+            let x = i as u32 * 2;
+            let rendered = [(x, 0), (x + 1, 0), (x, 1), (x + 1, 1)].map(|(x, y)| surface.at(x, y));
+
+            assert_eq!(&rendered, pixels, "tile {i}");
+        }
+    }
+
+    #[test]
+    fn malformed_input() {
+        assert_eq!(
+            TiledMap::parse(r#"<map infinite="1"></map>"#),
+            Err(TiledError::InfiniteMap)
+        );
+        assert_eq!(
+            TiledMap::parse("<map><layer></map>"),
+            Err(TiledError::InvalidXml)
+        );
+        assert_eq!(TiledMap::parse("<map>"), Err(TiledError::InvalidXml));
+        assert_eq!(
+            TiledMap::parse("<map width=1>"),
+            Err(TiledError::InvalidXml)
+        );
+        assert_eq!(
+            TiledMap::parse(r#"<map a="&bogus;"/>"#),
+            Err(TiledError::InvalidXml)
+        );
+        assert_eq!(
+            TiledMap::parse(r#"<map a="&amp"/>"#),
+            Err(TiledError::InvalidXml)
+        );
+        assert_eq!(
+            TiledMap::parse("<!-- unclosed"),
+            Err(TiledError::InvalidXml)
+        );
+        assert_eq!(
+            TiledMap::parse("<tileset/>"),
+            Err(TiledError::MissingElement("map"))
+        );
+        assert_eq!(
+            TiledMap::parse(r#"<map width="x" height="1" tilewidth="1" tileheight="1"/>"#),
+            Err(TiledError::InvalidAttribute("width"))
+        );
+
+        let map = |data: &str| {
+            TiledMap::parse(&alloc::format!(
+                r#"<map width="1" height="1" tilewidth="1" tileheight="1"><layer width="1" height="1">{data}</layer></map>"#
+            ))
+        };
+
+        assert_eq!(map(""), Err(TiledError::MissingElement("data")));
+        assert_eq!(
+            map(r#"<data encoding="csv">1,2</data>"#),
+            Err(TiledError::InvalidLayerData)
+        );
+        assert_eq!(
+            map(r#"<data encoding="csv">x</data>"#),
+            Err(TiledError::InvalidLayerData)
+        );
+        assert_eq!(
+            map(r#"<data encoding="base64">A*==</data>"#),
+            Err(TiledError::InvalidLayerData)
+        );
+        assert_eq!(
+            map(r#"<data encoding="base64" compression="zlib">x</data>"#),
+            Err(TiledError::UnsupportedEncoding("zlib".to_string()))
+        );
+        assert_eq!(
+            map(r#"<data encoding="hex">x</data>"#),
+            Err(TiledError::UnsupportedEncoding("hex".to_string()))
+        );
+    }
+}
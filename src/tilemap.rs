@@ -1,13 +1,19 @@
-use crate::{Buffer, BufferMut, Color, TileId, Tileset};
+use crate::{
+    blend_over, draw_layer_pixel, draw_tile_pixel, saturate, BitGrid, BlendMode, Buffer, BufferMut,
+    Color, HexLayout, HexOrientation, IsoLayout, RenderOptions, Rotation, TileFlags, TileId,
+    Tileset, EMPTY_TILE,
+};
 
 use alloc::vec;
 use alloc::vec::Vec;
-use core::ops::{Index, IndexMut};
+use core::cmp::Ordering;
+use core::iter;
+use core::ops::{Index, IndexMut, Range};
 use fast_srgb8::{f32x4_to_srgb8, srgb8_to_f32};
-use simple_blit::{blit_with, BlitOptions};
+use simple_blit::BlitOptions;
 
 /// Tile in a [`Tilemap`].
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Tile<U = ()> {
     /// Id of the tile.
@@ -20,14 +26,89 @@ pub struct Tile<U = ()> {
     /// Blit options.
     pub opts: BlitOptions,
 
+    /// Rotation of the tile, applied after the flips of its blit options.
+    ///
+    /// The tile is drawn with its top-left corner at the same position regardless of the rotation,
+    /// so rotating non-square tiles changes which neighbors they overlap.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rotation: Rotation,
+
+    /// Offset (x, y) in pixels of the tile from its cell when rendering a [`Tilemap`],
+    /// e.g. to jitter decorations off the grid.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub offset: (i8, i8),
+
+    /// Whether the tile gets rendered.
+    #[cfg_attr(feature = "serde", serde(default = "default_visible"))]
+    pub visible: bool,
+
+    /// Offset (in frames) into the tile's animation, so that tiles sharing an animation don't have to be in sync.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub anim_phase: u32,
+
+    /// Draw order of the tile for [`Tilemap::render_sorted`]. Tiles with higher values are drawn on top.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub z: i16,
+
+    /// Key color to use for this tile instead of the tileset's one.
+    /// `None` uses the tileset's key color, `Some(None)` disables masking.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub key_color_override: Option<Option<Color>>,
+
+    /// How the tile's pixels are combined with the buffer's when rendering.
+    /// Use [`BlendMode::Alpha`] for tiles with translucent pixels.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub blend: BlendMode,
+
+    /// Built-in flags of the tile for the game's logic, e.g. whether it's solid.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub flags: TileFlags,
+
     /// Custom user data.
     pub data: U,
 }
 
+impl<U> Default for Tile<U>
+where
+    U: Default,
+{
+    #[inline]
+    fn default() -> Self {
+        Self {
+            id: 0,
+            color: Color::default(),
+            opts: BlitOptions::default(),
+            rotation: Rotation::None,
+            offset: (0, 0),
+            visible: true,
+            anim_phase: 0,
+            z: 0,
+            key_color_override: None,
+            blend: BlendMode::Normal,
+            flags: TileFlags::NONE,
+            data: U::default(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[inline]
+const fn default_visible() -> bool {
+    true
+}
+
 impl<U> Tile<U>
 where
     U: Default,
 {
+    /// Construct an empty tile, which is never drawn.
+    ///
+    /// Unlike [`Tile::default`] (which is tile 0) it doesn't refer to any tile in the tileset.
+    #[inline]
+    pub fn empty() -> Self {
+        Self::new(EMPTY_TILE)
+    }
+
     /// Contruct a new tile.
     #[inline]
     pub fn new(id: TileId) -> Self {
@@ -35,12 +116,26 @@ where
             id,
             color: Color::new(255, 255, 255, 255),
             opts: BlitOptions::None,
+            rotation: Rotation::None,
+            offset: (0, 0),
+            visible: true,
+            anim_phase: 0,
+            z: 0,
+            key_color_override: None,
+            blend: BlendMode::Normal,
+            flags: TileFlags::NONE,
             data: U::default(),
         }
     }
 }
 
 impl<U> Tile<U> {
+    /// Whether the tile is [empty](Tile::empty), i.e. its id is [`EMPTY_TILE`].
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.id == EMPTY_TILE
+    }
+
     /// Set tile color.
     ///
     /// Allows for chaining tile creation like `Tile::new(4).with_color(Color::new(255, 0, 255, 255)).solid()` etc.
@@ -59,6 +154,100 @@ impl<U> Tile<U> {
         self
     }
 
+    /// Linearly interpolate tile color towards `target` by factor `t`.
+    ///
+    /// `t` is clamped to `[0, 1]`. Interpolation is done in linear space.
+    #[inline]
+    pub fn lerp_color(&mut self, target: Color, t: f32) -> &mut Self {
+        let t = t.clamp(0.0, 1.0);
+        let lerp = |from: u8, to: u8| {
+            let from = srgb8_to_f32(from);
+            from + (srgb8_to_f32(to) - from) * t
+        };
+
+        let [r, g, b, a] = f32x4_to_srgb8([
+            lerp(self.color.r, target.r),
+            lerp(self.color.g, target.g),
+            lerp(self.color.b, target.b),
+            lerp(self.color.a, target.a),
+        ]);
+
+        self.color = Color::new(r, g, b, a);
+        self
+    }
+
+    /// Set tile visibility.
+    ///
+    /// Allows for chaining tile creation like `Tile::new(4).with_visibility(false)` etc.
+    #[inline]
+    pub fn with_visibility(mut self, visible: bool) -> Self {
+        self.visible = visible;
+        self
+    }
+
+    /// Set tile visibility.
+    ///
+    /// Allows for chaining tile modifying like `tilemap.get_mut_tile(1, 1).unwrap().set_visibility(false)` etc.
+    #[inline]
+    pub fn set_visibility(&mut self, visible: bool) -> &mut Self {
+        self.visible = visible;
+        self
+    }
+
+    /// Set tile animation phase.
+    ///
+    /// Allows for chaining tile creation like `Tile::new(4).with_anim_phase(2)` etc.
+    #[inline]
+    pub fn with_anim_phase(mut self, anim_phase: u32) -> Self {
+        self.anim_phase = anim_phase;
+        self
+    }
+
+    /// Set tile animation phase.
+    ///
+    /// Allows for chaining tile modifying like `tilemap.get_mut_tile(1, 1).unwrap().set_anim_phase(2)` etc.
+    #[inline]
+    pub fn set_anim_phase(&mut self, anim_phase: u32) -> &mut Self {
+        self.anim_phase = anim_phase;
+        self
+    }
+
+    /// Specify tile draw order.
+    ///
+    /// Allows for chaining tile creation like `Tile::new(4).with_z(1)` etc.
+    #[inline]
+    pub fn with_z(mut self, z: i16) -> Self {
+        self.z = z;
+        self
+    }
+
+    /// Set tile draw order.
+    ///
+    /// Allows for chaining tile modifying like `tilemap.get_mut_tile(1, 1).unwrap().set_z(1)` etc.
+    #[inline]
+    pub fn set_z(&mut self, z: i16) -> &mut Self {
+        self.z = z;
+        self
+    }
+
+    /// Override the key color for this tile. `None` disables masking.
+    ///
+    /// Allows for chaining tile creation like `Tile::new(4).with_key_color(None)` etc.
+    #[inline]
+    pub fn with_key_color(mut self, key_color: Option<Color>) -> Self {
+        self.key_color_override = Some(key_color);
+        self
+    }
+
+    /// Override the key color for this tile. `None` disables masking.
+    ///
+    /// Allows for chaining tile modifying like `tilemap.get_mut_tile(1, 1).unwrap().set_key_color(None)` etc.
+    #[inline]
+    pub fn set_key_color(&mut self, key_color: Option<Color>) -> &mut Self {
+        self.key_color_override = Some(key_color);
+        self
+    }
+
     /// Set tile user data.
     ///
     /// Allows for chaining tile creation like `Tile::new(4).with_color(Color::new(255, 0, 255, 255))` etc.
@@ -94,36 +283,378 @@ impl<U> Tile<U> {
         self.opts = opts;
         self
     }
+
+    /// Set the rotation.
+    #[inline]
+    pub fn with_rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Set the rotation.
+    #[inline]
+    pub fn set_rotation(&mut self, rotation: Rotation) -> &mut Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Set the pixel offset from the tile's cell.
+    #[inline]
+    pub fn with_offset(mut self, x: i8, y: i8) -> Self {
+        self.offset = (x, y);
+        self
+    }
+
+    /// Set the pixel offset from the tile's cell.
+    #[inline]
+    pub fn set_offset(&mut self, x: i8, y: i8) -> &mut Self {
+        self.offset = (x, y);
+        self
+    }
+
+    /// Set the blend mode.
+    #[inline]
+    pub fn with_blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    /// Set the blend mode.
+    #[inline]
+    pub fn set_blend(&mut self, blend: BlendMode) -> &mut Self {
+        self.blend = blend;
+        self
+    }
+
+    /// Set the flags.
+    #[inline]
+    pub fn with_flags(mut self, flags: TileFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Set the flags.
+    #[inline]
+    pub fn set_flags(&mut self, flags: TileFlags) -> &mut Self {
+        self.flags = flags;
+        self
+    }
+}
+
+/// How the tiles of a [`Tilemap`] are arranged on screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MapLayout {
+    /// Rectangular grid, every tile is drawn right next to its neighbors.
+    #[default]
+    Orthogonal,
+    /// Hexagonal grid with every other row (or column) shifted by half a tile.
+    Hex(HexLayout),
+    /// Isometric grid of diamonds.
+    ///
+    /// Tiles are drawn row by row, so tiles in front are drawn over the ones behind them
+    /// as long as they're no wider than the diamond.
+    Isometric(IsoLayout),
 }
 
 /// A map that holds a tileset and a collection of tiles.
+///
+/// With the `serde` feature the map is (de)serialized through [`SerializedTilemap`],
+/// which records the format version so that older saves keep loading.
 #[derive(Clone, Debug)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-pub struct Tilemap<C, U = ()> {
-    tileset: Tileset<C>,
+pub struct Tilemap<C, U = (), M = ()> {
+    tileset: Tileset<C, M>,
+    extra_tilesets: Vec<(TileId, Tileset<C, M>)>,
     tiles: Vec<Tile<U>>,
     width: u32,
     height: u32,
+    bits: Option<BitGrid>,
+    dirty: Option<BitGrid>,
+    layout: MapLayout,
 }
 
-impl<C, U> Tilemap<C, U>
+impl<C, U, M> Tilemap<C, U, M>
 where
     U: Default + Clone,
 {
     /// Construct a new tilemap.
     /// `width` and `height` are map's size in tiles.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the total amount of tiles doesn't fit into a `u32`.
     #[inline]
-    pub fn new(width: u32, height: u32, tileset: Tileset<C>) -> Self {
+    pub fn new(width: u32, height: u32, tileset: Tileset<C, M>) -> Self {
+        let len = width.checked_mul(height).expect("map too large");
+
         Self {
             width,
             height,
-            tiles: vec![Tile::default(); (width * height) as usize],
+            tiles: vec![Tile::default(); len as usize],
             tileset,
+            extra_tilesets: Vec::new(),
+            bits: None,
+            dirty: None,
+            layout: MapLayout::Orthogonal,
+        }
+    }
+
+    /// Set every tile in a rectangle to a new tile with an id picked randomly by weight from `weights`.
+    ///
+    /// `rng` should return uniformly distributed random numbers.
+    /// Does nothing if the total weight is 0. The rectangle gets clipped to the map's bounds.
+    pub fn scatter<R>(
+        &mut self,
+        x: u32,
+        y: u32,
+        w: u32,
+        h: u32,
+        weights: &[(TileId, u32)],
+        mut rng: R,
+    ) where
+        R: FnMut() -> u32,
+    {
+        let total: u64 = weights.iter().map(|&(_, weight)| weight as u64).sum();
+
+        if total == 0 {
+            return;
+        }
+
+        for tile in self.region_mut(x, y, w, h) {
+            let mut roll = rng() as u64 % total;
+
+            for &(id, weight) in weights {
+                if roll < weight as u64 {
+                    *tile = Tile::new(id);
+                    break;
+                }
+
+                roll -= weight as u64;
+            }
+        }
+    }
+
+    /// Replace every tile with the result of `f`, which is called with the tile's position (x, y) and the tile itself.
+    ///
+    /// The tiles are transformed in place, reusing the map's allocation.
+    pub fn map_tiles<F>(&mut self, mut f: F)
+    where
+        F: FnMut(u32, u32, Tile<U>) -> Tile<U>,
+    {
+        let width = self.width.max(1) as usize;
+
+        for (i, tile) in self.tiles.iter_mut().enumerate() {
+            *tile = f(
+                (i % width) as u32,
+                (i / width) as u32,
+                core::mem::take(tile),
+            );
+        }
+
+        self.mark_all_dirty();
+    }
+}
+
+impl<C, U, M> Tilemap<C, U, M>
+where
+    U: Clone,
+{
+    /// Set every tile in a rectangle to `tile`.
+    ///
+    /// The rectangle gets clipped to the map's bounds.
+    pub fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, tile: Tile<U>) {
+        for t in self.region_mut(x, y, w, h) {
+            t.clone_from(&tile);
+        }
+    }
+
+    /// Set every tile on the edges of a rectangle to `tile`, leaving the inside untouched.
+    ///
+    /// The rectangle gets clipped to the map's bounds.
+    pub fn draw_rect(&mut self, x: u32, y: u32, w: u32, h: u32, tile: Tile<U>) {
+        if w == 0 || h == 0 {
+            return;
+        }
+
+        let x_end = x.saturating_add(w - 1);
+        let y_end = y.saturating_add(h - 1);
+
+        self.fill_rect(x, y, w, 1, tile.clone());
+        self.fill_rect(x, y_end, w, 1, tile.clone());
+        self.fill_rect(x, y, 1, h, tile.clone());
+        self.fill_rect(x_end, y, 1, h, tile);
+    }
+
+    /// Set every tile on a straight line from (x0, y0) to (x1, y1), both ends included, to `tile`.
+    ///
+    /// Parts of the line outside of the map are skipped.
+    pub fn draw_line(&mut self, x0: u32, y0: u32, x1: u32, y1: u32, tile: Tile<U>) {
+        let (mut x, mut y) = (x0 as i64, y0 as i64);
+        let (x1, y1) = (x1 as i64, y1 as i64);
+
+        let dx = (x1 - x).abs();
+        let dy = -(y1 - y).abs();
+        let step_x = if x < x1 { 1 } else { -1 };
+        let step_y = if y < y1 { 1 } else { -1 };
+        let mut error = dx + dy;
+
+        loop {
+            if let Some(i) = self.index_of(x as u32, y as u32) {
+                self.touch(i);
+                self.tiles[i].clone_from(&tile);
+            }
+
+            if x == x1 && y == y1 {
+                break;
+            }
+
+            let doubled = error * 2;
+
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    /// Insert a row of `fill` tiles before row `at`, increasing the map's height by 1.
+    ///
+    /// Rows at `at` and below get shifted down. `at` values past the last row append a row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the total amount of tiles doesn't fit into a `u32`.
+    pub fn insert_row(&mut self, at: u32, fill: Tile<U>) {
+        let at = at.min(self.height);
+        let height = self.height.checked_add(1).expect("map too large");
+        self.width.checked_mul(height).expect("map too large");
+
+        let i = (at * self.width) as usize;
+        self.tiles
+            .splice(i..i, iter::repeat(fill).take(self.width as usize));
+
+        self.height = height;
+        self.remap_bits(|x, y| Some((x, if y < at { y } else { y + 1 })));
+        self.reset_dirty();
+    }
+
+    /// Insert a column of `fill` tiles before column `at`, increasing the map's width by 1.
+    ///
+    /// Columns at `at` and to the right get shifted right. `at` values past the last column append a column.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the total amount of tiles doesn't fit into a `u32`.
+    pub fn insert_column(&mut self, at: u32, fill: Tile<U>) {
+        let at = at.min(self.width);
+        let width = self.width.checked_add(1).expect("map too large");
+        width.checked_mul(self.height).expect("map too large");
+
+        for y in (0..self.height).rev() {
+            self.tiles
+                .insert((y * self.width + at) as usize, fill.clone());
+        }
+
+        self.width = width;
+        self.remap_bits(|x, y| Some((if x < at { x } else { x + 1 }, y)));
+        self.reset_dirty();
+    }
+}
+
+impl<C, U, M> Tilemap<C, U, M>
+where
+    C: Clone,
+    U: Clone,
+    M: Clone,
+{
+    /// Copy the tiles in a rectangle into a new map with a clone of the tileset.
+    ///
+    /// The rectangle gets clipped to the map's bounds, so the new map may be smaller than `w` x `h`.
+    /// The bit overlay (if any) is copied as well.
+    pub fn extract(&self, x: u32, y: u32, w: u32, h: u32) -> Self {
+        let x_end = x.saturating_add(w).min(self.width);
+        let y_end = y.saturating_add(h).min(self.height);
+        let x = x.min(x_end);
+        let y = y.min(y_end);
+
+        let mut map = Self {
+            tileset: self.tileset.clone(),
+            extra_tilesets: self.extra_tilesets.clone(),
+            tiles: self
+                .iter_region(x, y, w, h)
+                .map(|(_, _, tile)| tile.clone())
+                .collect(),
+            width: x_end - x,
+            height: y_end - y,
+            bits: None,
+            dirty: None,
+            layout: self.layout,
+        };
+
+        if let Some(bits) = &self.bits {
+            let mut extracted = BitGrid::new(map.width, map.height);
+
+            for (tx, ty, _) in self.iter_region(x, y, w, h) {
+                extracted.set(tx - x, ty - y, bits.get(tx, ty));
+            }
+
+            map.bits = Some(extracted);
         }
+
+        map
     }
 }
 
-impl<C, U> Tilemap<C, U> {
+impl<C, U, M> Tilemap<C, U, M> {
+    /// Remove row `at`, decreasing the map's height by 1.
+    ///
+    /// Rows below it get shifted up. Does nothing if `at` is out of bounds.
+    pub fn remove_row(&mut self, at: u32) {
+        if at >= self.height {
+            return;
+        }
+
+        let i = (at * self.width) as usize;
+        self.tiles.drain(i..i + self.width as usize);
+
+        self.height -= 1;
+        self.remap_bits(|x, y| match y.cmp(&at) {
+            Ordering::Less => Some((x, y)),
+            Ordering::Equal => None,
+            Ordering::Greater => Some((x, y - 1)),
+        });
+        self.reset_dirty();
+    }
+
+    /// Remove column `at`, decreasing the map's width by 1.
+    ///
+    /// Columns to the right of it get shifted left. Does nothing if `at` is out of bounds.
+    pub fn remove_column(&mut self, at: u32) {
+        if at >= self.width {
+            return;
+        }
+
+        let width = self.width as usize;
+        let mut i = 0;
+        self.tiles.retain(|_| {
+            i += 1;
+            (i - 1) % width != at as usize
+        });
+
+        self.width -= 1;
+        self.remap_bits(|x, y| match x.cmp(&at) {
+            Ordering::Less => Some((x, y)),
+            Ordering::Equal => None,
+            Ordering::Greater => Some((x - 1, y)),
+        });
+        self.reset_dirty();
+    }
+
     /// Map's width in tiles.
     #[inline]
     pub fn width(&self) -> u32 {
@@ -136,112 +667,3487 @@ impl<C, U> Tilemap<C, U> {
         self.height
     }
 
-    /// Tileset used by this map.
+    /// Main tileset used by this map.
+    ///
+    /// It holds all the ids below the first id of the first [additional tileset](Self::add_tileset).
     #[inline]
-    pub fn tileset(&self) -> &Tileset<C> {
+    pub fn tileset(&self) -> &Tileset<C, M> {
         &self.tileset
     }
 
-    /// Map's tiles.
-    #[inline]
-    pub fn tiles(&self) -> &[Tile<U>] {
-        &self.tiles
+    /// Add a tileset holding the ids starting at `first_id`, so that tile `first_id` of the map
+    /// is tile 0 of `tileset`, same as Tiled's "first gid".
+    ///
+    /// Ids from `first_id` up to the first id of the next tileset belong to it.
+    /// The tileset's tiles can be larger than the map's cells (e.g. tall trees),
+    /// they're placed according to its [anchor](crate::TilesetOptions::anchor) and overlap the rows drawn before them.
+    /// Returns `false` if `first_id` is 0 or already used by another tileset.
+    pub fn add_tileset(&mut self, first_id: TileId, tileset: Tileset<C, M>) -> bool {
+        if first_id == 0 {
+            return false;
+        }
+
+        match self
+            .extra_tilesets
+            .binary_search_by_key(&first_id, |(id, _)| *id)
+        {
+            Ok(_) => false,
+            Err(i) => {
+                self.extra_tilesets.insert(i, (first_id, tileset));
+                true
+            }
+        }
     }
 
-    /// Map's tiles (mutable).
-    #[inline]
-    pub fn tiles_mut(&mut self) -> &mut [Tile<U>] {
-        &mut self.tiles
+    /// Remove the additional tileset starting at `first_id`, returning it.
+    pub fn remove_tileset(&mut self, first_id: TileId) -> Option<Tileset<C, M>> {
+        let i = self
+            .extra_tilesets
+            .binary_search_by_key(&first_id, |(id, _)| *id)
+            .ok()?;
+
+        Some(self.extra_tilesets.remove(i).1)
     }
 
-    /// Get a tile at (x, y).
-    #[inline]
-    pub fn get_tile(&self, x: u32, y: u32) -> Option<&Tile<U>> {
-        self.tiles.get((y * self.width + x) as usize)
+    /// Iterate over all the tilesets used by this map along with their first ids, starting with the main one at 0.
+    pub fn tilesets(&self) -> impl Iterator<Item = (TileId, &Tileset<C, M>)> {
+        iter::once((0, &self.tileset)).chain(
+            self.extra_tilesets
+                .iter()
+                .map(|(first_id, tileset)| (*first_id, tileset)),
+        )
     }
 
-    /// Get a mutable ref to a tile at (x, y).
+    /// Get the tileset tile `id` belongs to, along with the tile's id in that tileset.
+    pub fn tileset_for(&self, id: TileId) -> (&Tileset<C, M>, TileId) {
+        match self
+            .extra_tilesets
+            .iter()
+            .rev()
+            .find(|(first_id, _)| *first_id <= id)
+        {
+            Some((first_id, tileset)) => (tileset, id - first_id),
+            None => (&self.tileset, id),
+        }
+    }
+
+    /// Get the metadata of the tile at (x, y) from the tileset it belongs to, see [`Tileset::tile_meta`].
+    ///
+    /// Returns `None` if the position is out of bounds or the tile has no metadata.
     #[inline]
-    pub fn get_mut_tile(&mut self, x: u32, y: u32) -> Option<&mut Tile<U>> {
-        self.tiles.get_mut((y * self.width + x) as usize)
+    pub fn tile_meta_at(&self, x: u32, y: u32) -> Option<&M> {
+        let (tileset, id) = self.tileset_for(self.get_tile(x, y)?.id);
+        tileset.tile_meta(id)
     }
 
-    /// Set a tile at (x, y).
+    /// Size (width x height) of a single tile in pixels.
     #[inline]
-    pub fn set_tile(&mut self, x: u32, y: u32, tile: Tile<U>) {
-        if let Some(t) = self.tiles.get_mut((y * self.width + x) as usize) {
-            *t = tile;
-        }
+    pub fn tile_size(&self) -> (u32, u32) {
+        self.tileset.opts.tile_size
     }
-}
 
-impl<C> Tilemap<C>
-where
-    C: AsRef<[u8]>,
-{
-    /// Render the map onto a buffer at pixel offset `(offset_x, offset_y)`.
-    pub fn render(
-        &self,
-        surface: &mut (impl BufferMut<Color> + ?Sized),
-        offset_x: i32,
-        offset_y: i32,
-    ) {
-        for ty in 0..self.height {
-            for tx in 0..self.width {
-                let &Tile {
-                    id: tile,
-                    color,
-                    opts,
-                    ..
-                } = self.get(tx, ty);
-
-                if let Some((x, y)) = self.tileset.get_tile_pos(tile) {
-                    blit_with(
-                        surface,
-                        (offset_x, offset_y),
-                        &self.tileset,
-                        (x as _, y as _),
-                        self.tileset.opts.tile_size,
-                        opts,
-                        |dest, src, _| {
-                            if Some(*src) != self.tileset.opts.key_color {
-                                let [r, g, b, a] = f32x4_to_srgb8([
-                                    srgb8_to_f32(src.r) * srgb8_to_f32(color.r),
-                                    srgb8_to_f32(src.g) * srgb8_to_f32(color.g),
-                                    srgb8_to_f32(src.b) * srgb8_to_f32(color.b),
-                                    srgb8_to_f32(src.a) * srgb8_to_f32(color.a),
-                                ]);
-
-                                *dest = Color::new(r, g, b, a);
-                            }
-                        },
-                    );
-                }
+    /// Size (width x height) of the whole map in pixels.
+    #[inline]
+    pub fn pixel_size(&self) -> (u32, u32) {
+        let (tile_width, tile_height) = self.tile_size();
+
+        match self.layout {
+            MapLayout::Orthogonal => (self.width * tile_width, self.height * tile_height),
+            MapLayout::Hex(hex) => {
+                hex.pixel_size((self.width, self.height), (tile_width, tile_height))
+            }
+            MapLayout::Isometric(iso) => {
+                iso.pixel_size((self.width, self.height), (tile_width, tile_height))
             }
         }
     }
-}
 
-impl<C> Buffer<Tile> for Tilemap<C> {
+    /// How the map's tiles are arranged on screen.
     #[inline]
-    fn width(&self) -> u32 {
-        self.width
+    pub fn layout(&self) -> MapLayout {
+        self.layout
     }
 
+    /// Set how the map's tiles are arranged on screen.
     #[inline]
-    fn height(&self) -> u32 {
-        self.height
+    pub fn with_layout(mut self, layout: MapLayout) -> Self {
+        self.layout = layout;
+        self
     }
 
+    /// Set how the map's tiles are arranged on screen.
     #[inline]
-    fn get(&self, x: u32, y: u32) -> &Tile {
-        self.tiles.index((y * self.width + x) as usize)
+    pub fn set_layout(&mut self, layout: MapLayout) -> &mut Self {
+        self.layout = layout;
+        self
     }
-}
 
-impl<C> BufferMut<Tile> for Tilemap<C> {
+    /// Replace the map's tilesets with the results of `f`, keeping all the tiles.
+    ///
+    /// `f` is called for the main tileset first, then for every additional one.
+    /// Useful for changing the tileset's container type, e.g. with [`Tileset::into_container`].
     #[inline]
-    fn get_mut(&mut self, x: u32, y: u32) -> &mut Tile {
-        self.tiles.index_mut((y * self.width + x) as usize)
+    pub fn map_tileset<C2, F>(self, mut f: F) -> Tilemap<C2, U, M>
+    where
+        F: FnMut(Tileset<C, M>) -> Tileset<C2, M>,
+    {
+        Tilemap {
+            tileset: f(self.tileset),
+            extra_tilesets: self
+                .extra_tilesets
+                .into_iter()
+                .map(|(first_id, tileset)| (first_id, f(tileset)))
+                .collect(),
+            tiles: self.tiles,
+            width: self.width,
+            height: self.height,
+            bits: self.bits,
+            dirty: self.dirty,
+            layout: self.layout,
+        }
+    }
+
+    /// Map's tiles.
+    #[inline]
+    pub fn tiles(&self) -> &[Tile<U>] {
+        &self.tiles
+    }
+
+    /// Map's tiles (mutable).
+    #[inline]
+    pub fn tiles_mut(&mut self) -> &mut [Tile<U>] {
+        self.mark_all_dirty();
+        &mut self.tiles
+    }
+
+    /// Iterate over the rows of the map, top to bottom.
+    #[inline]
+    pub fn rows(&self) -> impl Iterator<Item = &[Tile<U>]> {
+        self.tiles.chunks_exact(self.width.max(1) as usize)
+    }
+
+    /// Iterate over the rows of the map (mutable), top to bottom.
+    #[inline]
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [Tile<U>]> {
+        self.mark_all_dirty();
+        self.tiles.chunks_exact_mut(self.width.max(1) as usize)
+    }
+
+    /// Get a tile at (x, y).
+    ///
+    /// Returns `None` if the position is out of bounds (always the case for an empty map).
+    #[inline]
+    pub fn get_tile(&self, x: u32, y: u32) -> Option<&Tile<U>> {
+        self.tiles.get(self.index_of(x, y)?)
+    }
+
+    /// Get a tile at (x, y), with positions outside of the map wrapping around its edges.
+    ///
+    /// Returns `None` only if the map is empty.
+    #[inline]
+    pub fn get_tile_wrapped(&self, x: i64, y: i64) -> Option<&Tile<U>> {
+        if self.width == 0 || self.height == 0 {
+            return None;
+        }
+
+        let x = x.rem_euclid(self.width as i64) as u32;
+        let y = y.rem_euclid(self.height as i64) as u32;
+
+        self.get_tile(x, y)
+    }
+
+    /// Get a mutable ref to a tile at (x, y).
+    ///
+    /// Returns `None` if the position is out of bounds (always the case for an empty map).
+    #[inline]
+    pub fn get_mut_tile(&mut self, x: u32, y: u32) -> Option<&mut Tile<U>> {
+        let i = self.index_of(x, y)?;
+        self.touch(i);
+        self.tiles.get_mut(i)
+    }
+
+    /// Set a tile at (x, y).
+    ///
+    /// Does nothing if the position is out of bounds.
+    #[inline]
+    pub fn set_tile(&mut self, x: u32, y: u32, tile: Tile<U>) {
+        self.try_set_tile(x, y, tile);
+    }
+
+    /// Set a tile at (x, y).
+    ///
+    /// Returns `false` (without modifying the map) if the position is out of bounds.
+    #[inline]
+    pub fn try_set_tile(&mut self, x: u32, y: u32, tile: Tile<U>) -> bool {
+        if let Some(t) = self.get_mut_tile(x, y) {
+            *t = tile;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Bit overlay of the map (e.g. walkability), if it was created.
+    #[inline]
+    pub fn bits(&self) -> Option<&BitGrid> {
+        self.bits.as_ref()
+    }
+
+    /// Get the overlay bit at (x, y).
+    ///
+    /// Returns `false` if the position is out of bounds or the overlay wasn't created yet.
+    #[inline]
+    pub fn get_bit(&self, x: u32, y: u32) -> bool {
+        self.bits.as_ref().is_some_and(|bits| bits.get(x, y))
+    }
+
+    /// Set the overlay bit at (x, y), creating the overlay if needed.
+    ///
+    /// Does nothing if the position is out of bounds.
+    #[inline]
+    pub fn set_bit(&mut self, x: u32, y: u32, bit: bool) {
+        let (width, height) = (self.width, self.height);
+
+        self.bits
+            .get_or_insert_with(|| BitGrid::new(width, height))
+            .set(x, y, bit);
+    }
+
+    /// Set every overlay bit to the result of `f` on the corresponding tile,
+    /// creating the overlay if needed.
+    pub fn fill_bits_from<F>(&mut self, f: F)
+    where
+        F: Fn(&Tile<U>) -> bool,
+    {
+        let mut bits = BitGrid::new(self.width, self.height);
+
+        for (i, tile) in self.tiles.iter().enumerate() {
+            if f(tile) {
+                let (x, y) = self.index_to_xy(i);
+                bits.set(x, y, true);
+            }
+        }
+
+        self.bits = Some(bits);
+    }
+
+    /// Remove the bit overlay.
+    #[inline]
+    pub fn clear_bits(&mut self) {
+        self.bits = None;
+    }
+
+    /// Enable or disable change tracking.
+    ///
+    /// While enabled, tiles changed through the map's methods get flagged
+    /// and can be redrawn with [`render_dirty`](Tilemap::render_dirty).
+    /// Enabling it flags every tile.
+    pub fn set_change_tracking(&mut self, enabled: bool) {
+        self.dirty = enabled.then(|| {
+            let mut dirty = BitGrid::new(self.width, self.height);
+            dirty.set_all();
+            dirty
+        });
+    }
+
+    /// Whether change tracking is enabled.
+    #[inline]
+    pub fn is_tracking_changes(&self) -> bool {
+        self.dirty.is_some()
+    }
+
+    /// Whether the tile at (x, y) changed since the flags were last cleared.
+    ///
+    /// Returns `false` if change tracking is disabled or the position is out of bounds.
+    #[inline]
+    pub fn is_dirty(&self, x: u32, y: u32) -> bool {
+        self.dirty.as_ref().is_some_and(|dirty| dirty.get(x, y))
+    }
+
+    /// Flag the tile at (x, y) as changed.
+    ///
+    /// Does nothing if change tracking is disabled or the position is out of bounds.
+    #[inline]
+    pub fn mark_dirty(&mut self, x: u32, y: u32) {
+        if let Some(dirty) = &mut self.dirty {
+            dirty.set(x, y, true);
+        }
+    }
+
+    /// Flag every tile as changed, if change tracking is enabled.
+    #[inline]
+    pub fn mark_all_dirty(&mut self) {
+        if let Some(dirty) = &mut self.dirty {
+            dirty.set_all();
+        }
+    }
+
+    /// Clear the change flags of all the tiles.
+    #[inline]
+    pub fn clear_dirty(&mut self) {
+        if let Some(dirty) = &mut self.dirty {
+            dirty.clear();
+        }
+    }
+
+    /// Get a tile by its index in [`tiles`](Self::tiles).
+    #[inline]
+    pub fn get_tile_index(&self, i: usize) -> Option<&Tile<U>> {
+        self.tiles.get(i)
+    }
+
+    /// Get a mutable ref to a tile by its index in [`tiles`](Self::tiles).
+    #[inline]
+    pub fn get_mut_tile_index(&mut self, i: usize) -> Option<&mut Tile<U>> {
+        self.touch(i);
+        self.tiles.get_mut(i)
+    }
+
+    /// Set a tile by its index in [`tiles`](Self::tiles).
+    ///
+    /// Does nothing if the index is out of bounds.
+    #[inline]
+    pub fn set_tile_index(&mut self, i: usize, tile: Tile<U>) {
+        if let Some(t) = self.get_mut_tile_index(i) {
+            *t = tile;
+        }
+    }
+
+    /// Convert an index in [`tiles`](Self::tiles) to a position (x, y).
+    ///
+    /// Doesn't check bounds.
+    #[inline]
+    pub fn index_to_xy(&self, i: usize) -> (u32, u32) {
+        let width = self.width.max(1) as usize;
+        ((i % width) as u32, (i / width) as u32)
+    }
+
+    /// Convert a position (x, y) to an index in [`tiles`](Self::tiles).
+    ///
+    /// Doesn't check bounds.
+    #[inline]
+    pub fn xy_to_index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    /// Whether any tile in the map has id `id`.
+    #[inline]
+    pub fn contains_id(&self, id: TileId) -> bool {
+        self.tiles.iter().any(|tile| tile.id == id)
+    }
+
+    /// Amount of tiles in the map with id `id`.
+    #[inline]
+    pub fn count_id(&self, id: TileId) -> usize {
+        self.tiles.iter().filter(|tile| tile.id == id).count()
+    }
+
+    /// Sorted list of distinct tile ids used in the map.
+    ///
+    /// Together with [`Tileset::subset`] this can be used to strip unused tiles from a tileset.
+    /// [Empty](Tile::empty) tiles aren't included.
+    pub fn used_ids(&self) -> Vec<TileId> {
+        let mut ids: Vec<_> = self
+            .tiles
+            .iter()
+            .filter(|tile| !tile.is_empty())
+            .map(|tile| tile.id)
+            .collect();
+
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    /// Iterate over the tiles in a rectangle, along with their positions, row by row.
+    ///
+    /// The rectangle gets clipped to the map's bounds.
+    pub fn iter_region(
+        &self,
+        x: u32,
+        y: u32,
+        w: u32,
+        h: u32,
+    ) -> impl Iterator<Item = (u32, u32, &Tile<U>)> {
+        let x_end = x.saturating_add(w).min(self.width);
+        let y_end = y.saturating_add(h).min(self.height);
+
+        (y..y_end).flat_map(move |ty| {
+            (x..x_end).map(move |tx| (tx, ty, &self.tiles[(ty * self.width + tx) as usize]))
+        })
+    }
+
+    /// Iterate over mutable references to the tiles in a rectangle, along with their positions, row by row.
+    ///
+    /// The rectangle gets clipped to the map's bounds.
+    pub fn select_mut(
+        &mut self,
+        x: u32,
+        y: u32,
+        w: u32,
+        h: u32,
+    ) -> impl Iterator<Item = (u32, u32, &mut Tile<U>)> {
+        self.touch_rect(x, y, w, h);
+
+        let x_end = x.saturating_add(w).min(self.width) as usize;
+        let y_end = y.saturating_add(h).min(self.height);
+        let x = (x as usize).min(x_end);
+
+        self.tiles
+            .chunks_exact_mut(self.width.max(1) as usize)
+            .enumerate()
+            .take(y_end as usize)
+            .skip(y as usize)
+            .flat_map(move |(ty, row)| {
+                row[x..x_end]
+                    .iter_mut()
+                    .enumerate()
+                    .map(move |(tx, tile)| ((x + tx) as u32, ty as u32, tile))
+            })
+    }
+
+    /// Set visibility of every tile in a rectangle.
+    ///
+    /// The rectangle gets clipped to the map's bounds.
+    pub fn set_visibility_rect(&mut self, x: u32, y: u32, w: u32, h: u32, visible: bool) {
+        for tile in self.region_mut(x, y, w, h) {
+            tile.visible = visible;
+        }
+    }
+
+    /// Set color of every tile in a rectangle.
+    ///
+    /// The rectangle gets clipped to the map's bounds.
+    pub fn tint_rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: Color) {
+        for tile in self.region_mut(x, y, w, h) {
+            tile.color = color;
+        }
+    }
+
+    /// Reset color of every tile in a rectangle to white.
+    ///
+    /// The rectangle gets clipped to the map's bounds.
+    #[inline]
+    pub fn clear_tint_rect(&mut self, x: u32, y: u32, w: u32, h: u32) {
+        self.tint_rect(x, y, w, h, Color::new(255, 255, 255, 255));
+    }
+
+    /// Get mutable refs to two different tiles at once.
+    ///
+    /// Returns `None` if `a == b` or either of the positions is out of bounds.
+    pub fn get_two_mut(
+        &mut self,
+        a: (u32, u32),
+        b: (u32, u32),
+    ) -> Option<(&mut Tile<U>, &mut Tile<U>)> {
+        let ia = self.index_of(a.0, a.1)?;
+        let ib = self.index_of(b.0, b.1)?;
+
+        if ia != ib {
+            self.touch(ia);
+            self.touch(ib);
+        }
+
+        if ia < ib {
+            let (left, right) = self.tiles.split_at_mut(ib);
+            Some((&mut left[ia], &mut right[0]))
+        } else if ia > ib {
+            let (left, right) = self.tiles.split_at_mut(ia);
+            Some((&mut right[0], &mut left[ib]))
+        } else {
+            None
+        }
+    }
+
+    /// Get the position (x, y) of the tile under the pixel (px, py) of a buffer
+    /// the map was rendered to at pixel offset `(offset_x, offset_y)`, e.g. for mouse picking.
+    ///
+    /// Accounts for the map's [`layout`](Self::layout), delegating to [`hex_tile_at`](Self::hex_tile_at)
+    /// and [`iso_tile_at`](Self::iso_tile_at). Returns `None` if the pixel isn't over any of the map's tiles.
+    pub fn tile_at_pixel(
+        &self,
+        px: i32,
+        py: i32,
+        offset_x: i32,
+        offset_y: i32,
+    ) -> Option<(u32, u32)> {
+        match self.layout {
+            MapLayout::Orthogonal => {
+                let (tile_width, tile_height) = self.tileset.opts.tile_size;
+
+                if tile_width == 0 || tile_height == 0 {
+                    return None;
+                }
+
+                // rounds towards negative infinity, so pixels left of or above the map don't land on it
+                let x = (px as i64 - offset_x as i64).div_euclid(tile_width as i64);
+                let y = (py as i64 - offset_y as i64).div_euclid(tile_height as i64);
+
+                (x >= 0 && y >= 0 && x < self.width as i64 && y < self.height as i64)
+                    .then_some((x as u32, y as u32))
+            }
+            MapLayout::Hex(_) => self.hex_tile_at(px, py, offset_x, offset_y),
+            MapLayout::Isometric(_) => self.iso_tile_at(px, py, offset_x, offset_y),
+        }
+    }
+
+    /// Get the pixel position of the top-left corner of the tile at (x, y)
+    /// when the map is rendered at pixel offset `(offset_x, offset_y)`.
+    ///
+    /// The inverse of [`tile_at_pixel`](Self::tile_at_pixel). Returns `None` if the position is out of bounds.
+    #[inline]
+    pub fn tile_to_pixel(
+        &self,
+        x: u32,
+        y: u32,
+        offset_x: i32,
+        offset_y: i32,
+    ) -> Option<(i32, i32)> {
+        self.index_of(x, y)?;
+
+        Some(self.tile_dest(x, y, offset_x, offset_y))
+    }
+
+    /// Get the pixel rectangle `(x, y, width, height)` the tile at (x, y) occupies
+    /// when the map is rendered at pixel offset `(offset_x, offset_y)`.
+    ///
+    /// Tiles from tilesets with larger tiles than the map's cells occupy more than their cell.
+    /// Returns `None` if the position is out of bounds.
+    pub fn tile_screen_rect(
+        &self,
+        x: u32,
+        y: u32,
+        offset_x: i32,
+        offset_y: i32,
+    ) -> Option<(i32, i32, u32, u32)> {
+        let tile = &self.tiles[self.index_of(x, y)?];
+
+        let (tileset, id) = self.tileset_for(tile.id);
+        let image_size = tileset
+            .tile_rect(id)
+            .map_or(tileset.opts.tile_size, |(_, _, width, height)| {
+                (width, height)
+            });
+        let (tile_width, tile_height) = tile.rotation.apply_size(image_size);
+        let (left, top) = self.image_dest(
+            tileset,
+            tile,
+            image_size,
+            self.tile_dest(x, y, offset_x, offset_y),
+        );
+
+        Some((left, top, tile_width, tile_height))
+    }
+
+    /// Whether the tile at (x, y) would intersect a `surface_width` x `surface_height` surface
+    /// when the map is rendered at pixel offset `(offset_x, offset_y)`.
+    ///
+    /// Returns `false` if the position is out of bounds.
+    pub fn tile_visible(
+        &self,
+        x: u32,
+        y: u32,
+        offset_x: i32,
+        offset_y: i32,
+        surface_width: u32,
+        surface_height: u32,
+    ) -> bool {
+        self.tile_screen_rect(x, y, offset_x, offset_y)
+            .is_some_and(|(left, top, width, height)| {
+                (left as i64) < surface_width as i64
+                    && (top as i64) < surface_height as i64
+                    && left as i64 + width as i64 > 0
+                    && top as i64 + height as i64 > 0
+            })
+    }
+
+    /// Ranges of columns and rows of tiles that intersect a `surface_width` x `surface_height` surface
+    /// when the map is rendered at pixel offset `(offset_x, offset_y)`.
+    ///
+    /// Includes the cells whose tiles may stick out into the surface. Rendering only draws these tiles.
+    pub fn visible_range(
+        &self,
+        offset_x: i32,
+        offset_y: i32,
+        surface_width: u32,
+        surface_height: u32,
+    ) -> (Range<u32>, Range<u32>) {
+        let (tile_width, tile_height) = self.tileset.opts.tile_size;
+
+        let (left, top, right, bottom) = self.overhang();
+
+        let range = |offset: i32,
+                     surface_size: u32,
+                     step: u32,
+                     size: u32,
+                     shift: u32,
+                     overhang: (u32, u32),
+                     map_size: u32| {
+            clamp_span(
+                cell_span(offset as i64, surface_size, step, size, shift, overhang),
+                map_size,
+            )
+        };
+
+        let (step_x, step_y, shift_x, shift_y) = match self.layout {
+            MapLayout::Orthogonal => (tile_width, tile_height, 0, 0),
+            MapLayout::Hex(hex) => {
+                let (step_x, step_y) = hex.step((tile_width, tile_height));
+
+                match hex.orientation {
+                    HexOrientation::PointyTop => (step_x, step_y, tile_width / 2, 0),
+                    HexOrientation::FlatTop => (step_x, step_y, 0, tile_height / 2),
+                }
+            }
+            MapLayout::Isometric(iso) => {
+                return self.iso_visible_range(
+                    iso,
+                    offset_x,
+                    offset_y,
+                    surface_width,
+                    surface_height,
+                );
+            }
+        };
+
+        (
+            range(
+                offset_x,
+                surface_width,
+                step_x,
+                tile_width,
+                shift_x,
+                (left, right),
+                self.width,
+            ),
+            range(
+                offset_y,
+                surface_height,
+                step_y,
+                tile_height,
+                shift_y,
+                (top, bottom),
+                self.height,
+            ),
+        )
+    }
+
+    /// Positions (x, y) of the tiles to draw on a `surface_width` x `surface_height` surface
+    /// when the map is rendered at pixel offset `(offset_x, offset_y)`, row by row.
+    ///
+    /// Those are the tiles in the [`visible_range`](Self::visible_range) plus the [offset](Tile::offset) tiles
+    /// around it that may reach into the surface.
+    pub(crate) fn visible_cells(
+        &self,
+        offset_x: i32,
+        offset_y: i32,
+        surface_width: u32,
+        surface_height: u32,
+    ) -> impl Iterator<Item = (u32, u32)> + '_ {
+        let (columns, rows) = self.visible_range(offset_x, offset_y, surface_width, surface_height);
+
+        // tiles can be moved by up to the range of `i8` in either direction
+        let (outer_columns, outer_rows) = self.visible_range(
+            offset_x.saturating_add(OFFSET_MARGIN.0 as i32),
+            offset_y.saturating_add(OFFSET_MARGIN.0 as i32),
+            surface_width.saturating_add(OFFSET_MARGIN.0 + OFFSET_MARGIN.1),
+            surface_height.saturating_add(OFFSET_MARGIN.0 + OFFSET_MARGIN.1),
+        );
+
+        outer_rows
+            .flat_map(move |ty| outer_columns.clone().map(move |tx| (tx, ty)))
+            .filter(move |&(tx, ty)| {
+                (columns.contains(&tx) && rows.contains(&ty))
+                    || self.tiles[self.xy_to_index(tx, ty)].offset != (0, 0)
+            })
+    }
+
+    /// Check that every tile in the map is either [empty](Tile::empty) or refers to a tile that exists in its tileset.
+    ///
+    /// Returns the positions and ids of all the invalid tiles otherwise.
+    pub fn validate(&self) -> Result<(), Vec<(u32, u32, TileId)>> {
+        let invalid: Vec<_> = self
+            .tiles
+            .iter()
+            .enumerate()
+            .filter(|(_, tile)| {
+                let (tileset, id) = self.tileset_for(tile.id);
+                !tile.is_empty() && !tileset.contains(id)
+            })
+            .map(|(i, tile)| (i as u32 % self.width, i as u32 / self.width, tile.id))
+            .collect();
+
+        if invalid.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid)
+        }
+    }
+
+    /// Move the bit overlay's bits to the positions given by `f` after the map was resized.
+    fn remap_bits(&mut self, f: impl Fn(u32, u32) -> Option<(u32, u32)>) {
+        if let Some(old) = &mut self.bits {
+            let mut bits = BitGrid::new(self.width, self.height);
+
+            for y in 0..old.height() {
+                for x in 0..old.width() {
+                    if let Some((nx, ny)) = f(x, y).filter(|_| old.get(x, y)) {
+                        bits.set(nx, ny, true);
+                    }
+                }
+            }
+
+            *old = bits;
+        }
+    }
+
+    /// Pixel position where the tile at (x, y) gets drawn when the map is rendered at pixel offset `(offset_x, offset_y)`.
+    ///
+    /// Doesn't check bounds.
+    #[inline]
+    pub(crate) fn tile_dest(&self, x: u32, y: u32, offset_x: i32, offset_y: i32) -> (i32, i32) {
+        let tile_size = self.tileset.opts.tile_size;
+
+        let (left, top) = match self.layout {
+            MapLayout::Orthogonal => (x * tile_size.0, y * tile_size.1),
+            MapLayout::Hex(hex) => hex.tile_offset((x, y), tile_size),
+            MapLayout::Isometric(iso) => iso.tile_offset((x, y), self.height, tile_size),
+        };
+
+        (offset_x + left as i32, offset_y + top as i32)
+    }
+
+    /// Pixel position of the top-left corner of the `image_size` image of map tile `tile` from `tileset`
+    /// drawn in the cell at pixel position `cell`, placed according to the tileset's [anchor](crate::TileAnchor)
+    /// and the tile's offset.
+    #[inline]
+    pub(crate) fn image_dest(
+        &self,
+        tileset: &Tileset<C, M>,
+        tile: &Tile<U>,
+        image_size: (u32, u32),
+        cell: (i32, i32),
+    ) -> (i32, i32) {
+        tileset.image_dest(tile, image_size, self.tile_size(), cell)
+    }
+
+    /// How far (left, top, right, bottom) in pixels the images of tiles can stick out of their cells.
+    pub(crate) fn overhang(&self) -> (u32, u32, u32, u32) {
+        let cell_size = self.tile_size();
+
+        self.tilesets()
+            .map(|(_, tileset)| tileset.overhang(cell_size))
+            .fold((0, 0, 0, 0), |a, b| {
+                (a.0.max(b.0), a.1.max(b.1), a.2.max(b.2), a.3.max(b.3))
+            })
+    }
+
+    /// Mark the tile at index `i` as changed, if changes are tracked.
+    #[inline]
+    fn touch(&mut self, i: usize) {
+        if let Some(dirty) = &mut self.dirty {
+            let width = self.width.max(1) as usize;
+            dirty.set((i % width) as u32, (i / width) as u32, true);
+        }
+    }
+
+    /// Mark the tiles in a rectangle as changed, if changes are tracked.
+    fn touch_rect(&mut self, x: u32, y: u32, w: u32, h: u32) {
+        if let Some(dirty) = &mut self.dirty {
+            let x_end = x.saturating_add(w).min(self.width);
+            let y_end = y.saturating_add(h).min(self.height);
+
+            for ty in y..y_end {
+                for tx in x..x_end {
+                    dirty.set(tx, ty, true);
+                }
+            }
+        }
+    }
+
+    /// Recreate the change flags with every tile marked as changed after the map was resized.
+    fn reset_dirty(&mut self) {
+        if self.dirty.is_some() {
+            self.set_change_tracking(true);
+        }
+    }
+
+    /// Tiles in a rectangle clipped to the map's bounds, row by row.
+    fn region_mut(&mut self, x: u32, y: u32, w: u32, h: u32) -> impl Iterator<Item = &mut Tile<U>> {
+        self.touch_rect(x, y, w, h);
+
+        let x_end = x.saturating_add(w).min(self.width) as usize;
+        let y_end = y.saturating_add(h).min(self.height);
+        let x = (x as usize).min(x_end);
+
+        self.tiles
+            .chunks_exact_mut(self.width.max(1) as usize)
+            .take(y_end as usize)
+            .skip(y as usize)
+            .flat_map(move |row| &mut row[x..x_end])
+    }
+
+    #[inline]
+    fn index_of(&self, x: u32, y: u32) -> Option<usize> {
+        if x < self.width && y < self.height {
+            Some(self.xy_to_index(x, y))
+        } else {
+            None
+        }
+    }
+}
+
+/// Builder for a [`Tilemap`].
+///
+/// Allows for constructing small maps like
+/// `TilemapBuilder::new(8, 8, tileset).fill(Tile::new(1)).border(Tile::new(0)).build()`.
+#[derive(Clone, Debug)]
+pub struct TilemapBuilder<C, U = (), M = ()> {
+    map: Tilemap<C, U, M>,
+}
+
+impl<C, U, M> TilemapBuilder<C, U, M>
+where
+    U: Default + Clone,
+{
+    /// Create a new TilemapBuilder.
+    /// `width` and `height` are map's size in tiles.
+    #[inline]
+    pub fn new(width: u32, height: u32, tileset: Tileset<C, M>) -> Self {
+        Self {
+            map: Tilemap::new(width, height, tileset),
+        }
+    }
+}
+
+impl<C, U, M> TilemapBuilder<C, U, M>
+where
+    U: Clone,
+{
+    /// Set every tile of the map to `tile`.
+    #[inline]
+    pub fn fill(mut self, tile: Tile<U>) -> Self {
+        self.map.tiles.fill(tile);
+        self
+    }
+
+    /// Set every tile on the edges of the map to `tile`.
+    pub fn border(mut self, tile: Tile<U>) -> Self {
+        let (width, height) = (self.map.width, self.map.height);
+
+        for x in 0..width {
+            self.map.set_tile(x, 0, tile.clone());
+            self.map.set_tile(x, height.saturating_sub(1), tile.clone());
+        }
+
+        for y in 0..height {
+            self.map.set_tile(0, y, tile.clone());
+            self.map.set_tile(width.saturating_sub(1), y, tile.clone());
+        }
+
+        self
+    }
+}
+
+impl<C, U, M> TilemapBuilder<C, U, M> {
+    /// Set a tile at (x, y).
+    ///
+    /// Does nothing if the position is out of bounds.
+    #[inline]
+    pub fn set(mut self, x: u32, y: u32, tile: Tile<U>) -> Self {
+        self.map.set_tile(x, y, tile);
+        self
+    }
+
+    /// Build the map.
+    #[inline]
+    pub fn build(self) -> Tilemap<C, U, M> {
+        self.map
+    }
+}
+
+impl<C, M> Tilemap<C, (), M>
+where
+    C: AsRef<[u8]>,
+{
+    /// Render the map onto a buffer at pixel offset `(offset_x, offset_y)`.
+    ///
+    /// Only the tiles intersecting the buffer are drawn, see [`visible_range`](Self::visible_range).
+    pub fn render(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+    ) {
+        self.render_tiles(surface, offset_x, offset_y, None, draw_tile_pixel);
+    }
+
+    /// Render the map onto a buffer at pixel offset `(offset_x, offset_y)`
+    /// with a map-wide tint and opacity, e.g. for fades and flashes.
+    ///
+    /// The tint is multiplied with every tile's color. Since [`BlendMode::Normal`] overwrites the buffer's pixels,
+    /// such tiles are drawn with [`BlendMode::Alpha`] instead when the opacity isn't 255.
+    pub fn render_with_options(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+        opts: &RenderOptions,
+    ) {
+        let tint = Color {
+            a: (opts.tint.a as u16 * opts.opacity as u16 / 255) as u8,
+            ..opts.tint
+        };
+
+        self.render_tiles(surface, offset_x, offset_y, None, |dest, src, tile| {
+            let blend = match tile.blend {
+                BlendMode::Normal if opts.opacity != 255 => BlendMode::Alpha,
+                blend => blend,
+            };
+
+            *dest = blend.blend(*dest, multiply(multiply(*src, tile.color), tint));
+        });
+    }
+
+    /// Render only the tiles in a rectangle (in tiles) of the map rendered at pixel offset `(offset_x, offset_y)`.
+    ///
+    /// Tiles are drawn at the same positions as with [`render`](Self::render), so this can be used
+    /// to redraw just the part of the map that changed. The rectangle gets clipped to the map's bounds.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_region(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+        x: u32,
+        y: u32,
+        w: u32,
+        h: u32,
+    ) {
+        let columns = x..x.saturating_add(w);
+        let rows = y..y.saturating_add(h);
+
+        self.render_tile_range(
+            surface,
+            offset_x,
+            offset_y,
+            columns,
+            rows,
+            None,
+            draw_tile_pixel,
+        );
+    }
+
+    /// Render only the tiles that changed since the last call onto a buffer at pixel offset `(offset_x, offset_y)`,
+    /// then clear their change flags.
+    ///
+    /// Draws nothing unless change tracking is enabled with [`set_change_tracking`](Tilemap::set_change_tracking).
+    /// Tiles are drawn over what's already on the buffer, so pixels that became transparent aren't erased.
+    /// Tiles outside of the buffer keep their change flags. The buffer is assumed to still hold the tiles
+    /// drawn by the previous calls, so call [`mark_all_dirty`](Tilemap::mark_all_dirty) after moving the map.
+    pub fn render_dirty(
+        &mut self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+    ) {
+        let Some(mut dirty) = self.dirty.take() else {
+            return;
+        };
+
+        for (tx, ty) in self.visible_cells(offset_x, offset_y, surface.width(), surface.height()) {
+            if !dirty.get(tx, ty) {
+                continue;
+            }
+
+            let tile = &self.tiles[self.xy_to_index(tx, ty)];
+
+            if tile.visible {
+                self.render_map_tile(
+                    surface,
+                    tile,
+                    self.tile_dest(tx, ty, offset_x, offset_y),
+                    None,
+                    &mut draw_tile_pixel,
+                );
+            }
+
+            dirty.set(tx, ty, false);
+        }
+
+        self.dirty = Some(dirty);
+    }
+
+    /// Render the map onto a buffer at pixel offset `(offset_x, offset_y)`,
+    /// returning the tightest pixel rectangle `(x, y, width, height)` that was drawn to.
+    ///
+    /// Returns `None` if nothing was drawn.
+    /// Useful for presenting only the changed part of the buffer.
+    pub fn render_tracked(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+    ) -> Option<(i32, i32, u32, u32)> {
+        let mut tracker = BoundsTracker {
+            surface,
+            bounds: None,
+        };
+
+        self.render_tiles(&mut tracker, offset_x, offset_y, None, draw_tile_pixel);
+
+        tracker
+            .bounds
+            .map(|(x0, y0, x1, y1)| (x0, y0, x1.abs_diff(x0) + 1, y1.abs_diff(y0) + 1))
+    }
+
+    /// Render the map onto a buffer at pixel offset `(offset_x, offset_y)`,
+    /// multiplying every tile's color by a light color.
+    ///
+    /// `light` is called with each tile's position (x, y) in tiles. A light of `Color::new(255, 255, 255, 255)`
+    /// leaves the tile unchanged, same as [`render`](Self::render). The lit tile is then drawn with its [`blend`](Tile::blend) mode.
+    pub fn render_with_light<F>(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+        light: F,
+    ) where
+        F: Fn(u32, u32) -> Color,
+    {
+        for (tx, ty) in self.visible_cells(offset_x, offset_y, surface.width(), surface.height()) {
+            let tile = &self.tiles[self.xy_to_index(tx, ty)];
+
+            if !tile.visible {
+                continue;
+            }
+
+            let color = multiply(tile.color, light(tx, ty));
+
+            self.render_map_tile(
+                surface,
+                tile,
+                self.tile_dest(tx, ty, offset_x, offset_y),
+                None,
+                &mut |dest, src, tile| *dest = tile.blend.blend(*dest, multiply(*src, color)),
+            );
+        }
+    }
+
+    /// Render the map onto a buffer at pixel offset `(offset_x, offset_y)`,
+    /// drawing tiles in ascending [`z`](Tile::z) order.
+    ///
+    /// Tiles with equal `z` are drawn row by row, same as in [`render`](Self::render).
+    pub fn render_sorted(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+    ) {
+        let mut order: Vec<_> = (0..self.tiles.len())
+            .filter(|&i| self.tiles[i].visible)
+            .collect();
+        order.sort_by_key(|&i| self.tiles[i].z);
+
+        for i in order {
+            let (tx, ty) = self.index_to_xy(i);
+
+            self.render_map_tile(
+                surface,
+                &self.tiles[i],
+                self.tile_dest(tx, ty, offset_x, offset_y),
+                None,
+                &mut draw_tile_pixel,
+            );
+        }
+    }
+
+    /// Render the map onto a buffer with a different pixel type at pixel offset `(offset_x, offset_y)`.
+    ///
+    /// `convert` is called on every resulting color to get the value written to the buffer,
+    /// and `read` turns the buffer's pixels back into colors for the tiles' [blend modes](Tile::blend),
+    /// so that the result is the same as with [`render`](Self::render).
+    pub fn render_into<P, F, R>(
+        &self,
+        surface: &mut (impl BufferMut<P> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+        convert: F,
+        read: R,
+    ) where
+        F: Fn(Color) -> P,
+        R: Fn(&P) -> Color,
+    {
+        self.render_tiles(surface, offset_x, offset_y, None, |dest, src, tile| {
+            let mut color = read(dest);
+            draw_tile_pixel(&mut color, src, tile);
+            *dest = convert(color);
+        });
+    }
+
+    /// Render the map onto a buffer at pixel offset `(offset_x, offset_y)`,
+    /// drawing tiles with invalid ids as tile `fallback`.
+    ///
+    /// Useful for making missing tiles visible. If `fallback` itself is invalid, such tiles are skipped.
+    pub fn render_with_fallback(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+        fallback: TileId,
+    ) {
+        self.render_tiles(surface, offset_x, offset_y, Some(fallback), draw_tile_pixel);
+    }
+
+    /// Render the map onto a buffer at pixel offset `(offset_x, offset_y)`,
+    /// treating the red channel of the tileset's pixels as an index into `palette`.
+    ///
+    /// The palette color is then multiplied by the tile's color as usual.
+    /// Pixels with indices outside of the palette are skipped, same as the key color.
+    pub fn render_paletted(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+        palette: &[Color],
+    ) {
+        self.render_tiles(surface, offset_x, offset_y, None, |dest, src, tile| {
+            if let Some(&color) = palette.get(src.r as usize) {
+                *dest = multiply(color, tile.color);
+            }
+        });
+    }
+
+    /// Render the map onto a buffer at pixel offset `(offset_x, offset_y)`
+    /// with a drop shadow of color `shadow_color` shifted by `shadow_offset` beneath it.
+    ///
+    /// Only pixels that aren't the key color cast a shadow.
+    /// Shadow alpha gets multiplied by the alpha of the tile's pixels, and the shadow is alpha blended over the buffer.
+    pub fn render_with_shadow(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+        shadow_offset: (i32, i32),
+        shadow_color: Color,
+    ) {
+        self.render_tiles(
+            surface,
+            offset_x.saturating_add(shadow_offset.0),
+            offset_y.saturating_add(shadow_offset.1),
+            None,
+            |dest, src, tile| {
+                let alpha = multiply(*src, tile.color).a;
+                *dest = blend_over(
+                    *dest,
+                    multiply(shadow_color, Color::new(255, 255, 255, alpha)),
+                );
+            },
+        );
+
+        self.render(surface, offset_x, offset_y);
+    }
+
+    /// Render the map onto a buffer at pixel offset `(offset_x, offset_y)`,
+    /// first filling the map's area with `bg`.
+    ///
+    /// Unlike [`render`](Self::render), pixels under key-colored or invalid tiles get `bg`
+    /// instead of being left untouched.
+    pub fn render_with_background(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+        bg: Color,
+    ) {
+        let (map_width, map_height) = self.pixel_size();
+
+        let x0 = offset_x.max(0);
+        let y0 = offset_y.max(0);
+        let x1 = (offset_x as i64 + map_width as i64).min(surface.width() as i64);
+        let y1 = (offset_y as i64 + map_height as i64).min(surface.height() as i64);
+
+        for y in y0 as i64..y1 {
+            for x in x0 as i64..x1 {
+                *surface.get_mut(x as u32, y as u32) = bg;
+            }
+        }
+
+        self.render(surface, offset_x, offset_y);
+    }
+
+    /// Render the map onto a buffer at pixel offset `(offset_x, offset_y)`,
+    /// then draw `overlay` on top of it at pixel offset `overlay_offset`.
+    ///
+    /// Overlay tiles are drawn with their [`blend`](Tile::blend) mode, except that the normal mode alpha blends.
+    ///
+    /// The overlay can use a different tileset and user data type, e.g. for a HUD drawn over the world.
+    /// `overlay_offset` is relative to the buffer, not to the map, so the overlay doesn't move with the map.
+    pub fn render_over<C2, U2, M2>(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+        overlay: &Tilemap<C2, U2, M2>,
+        overlay_offset: (i32, i32),
+    ) where
+        C2: AsRef<[u8]>,
+    {
+        self.render(surface, offset_x, offset_y);
+
+        overlay.render_tiles(
+            surface,
+            overlay_offset.0,
+            overlay_offset.1,
+            None,
+            |dest, src, tile| draw_layer_pixel(dest, src, tile, 255),
+        );
+    }
+
+    /// Render the map repeatedly so that it covers the whole buffer,
+    /// with one of the copies at pixel offset `(offset_x, offset_y)`.
+    ///
+    /// For orthogonal maps every tile position on the buffer is drawn once,
+    /// with tiles sampled modulo the map's size (see [`get_tile_wrapped`](Tilemap::get_tile_wrapped)),
+    /// so the map wraps seamlessly across its edges.
+    pub fn render_wrapped(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+    ) {
+        if self.layout == MapLayout::Orthogonal {
+            let (tile_width, tile_height) = self.tileset.opts.tile_size;
+
+            if tile_width == 0 || tile_height == 0 {
+                return;
+            }
+
+            let (left, top, right, bottom) = self.overhang();
+
+            // positions of tiles intersecting the buffer, unbounded by the map's size
+            let range =
+                |offset: i32, surface_size: u32, tile_size: u32, (before, after): (u32, u32)| {
+                    let (offset, tile_size) = (offset as i64, tile_size as i64);
+
+                    (-offset - after as i64).div_euclid(tile_size)
+                        ..(surface_size as i64 - offset + before as i64 + tile_size - 1)
+                            .div_euclid(tile_size)
+                };
+
+            let columns = range(offset_x, surface.width(), tile_width, (left, right));
+            let rows = range(offset_y, surface.height(), tile_height, (top, bottom));
+
+            // offset tiles around those may reach into the buffer as well
+            let (before, after) = OFFSET_MARGIN;
+            let outer_columns = range(
+                offset_x,
+                surface.width(),
+                tile_width,
+                (left + before, right + after),
+            );
+            let outer_rows = range(
+                offset_y,
+                surface.height(),
+                tile_height,
+                (top + before, bottom + after),
+            );
+
+            let mut f = draw_tile_pixel;
+
+            for ty in outer_rows {
+                for tx in outer_columns.clone() {
+                    let Some(tile) = self.get_tile_wrapped(tx, ty) else {
+                        return;
+                    };
+
+                    let inside = columns.contains(&tx) && rows.contains(&ty);
+
+                    if tile.visible && (inside || tile.offset != (0, 0)) {
+                        let dest = (
+                            saturate(offset_x as i64 + tx * tile_width as i64),
+                            saturate(offset_y as i64 + ty * tile_height as i64),
+                        );
+
+                        self.render_map_tile(surface, tile, dest, None, &mut f);
+                    }
+                }
+            }
+
+            return;
+        }
+
+        let (map_width, map_height) = self.pixel_size();
+        let (map_width, map_height) = (map_width as i32, map_height as i32);
+
+        if map_width == 0 || map_height == 0 {
+            return;
+        }
+
+        let start_x = offset_x.rem_euclid(map_width) - map_width;
+        let start_y = offset_y.rem_euclid(map_height) - map_height;
+
+        for y in (start_y..surface.height() as i32).step_by(map_height as usize) {
+            for x in (start_x..surface.width() as i32).step_by(map_width as usize) {
+                self.render(surface, x, y);
+            }
+        }
+    }
+
+    /// Render the map onto a buffer at pixel offset `(offset_x, offset_y)`
+    /// with every tile pixel drawn as a `scale` x `scale` block (nearest-neighbor upscaling).
+    ///
+    /// The offset is in buffer pixels. A `scale` of 0 is treated as 1.
+    pub fn render_scaled(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+        scale: u32,
+    ) {
+        let size = (surface.width(), surface.height());
+
+        self.render_tiles_scaled(
+            surface,
+            (offset_x as i64, offset_y as i64),
+            scale,
+            size,
+            draw_tile_pixel,
+        );
+    }
+
+    /// Render the map onto a buffer at pixel offset `(offset_x, offset_y)`
+    /// with the tile size known at compile time, which lets the compiler optimize the per-pixel loop.
+    ///
+    /// Produces the same result as [`render`](Self::render), which it falls back to
+    /// if the tileset's tile size isn't `W` x `H`, the map isn't orthogonal or it uses more than one tileset.
+    pub fn render_const<const W: u32, const H: u32>(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+    ) {
+        if self.tileset.opts.tile_size != (W, H)
+            || self.layout != MapLayout::Orthogonal
+            || !self.extra_tilesets.is_empty()
+        {
+            return self.render(surface, offset_x, offset_y);
+        }
+
+        let mut f = draw_tile_pixel;
+
+        for ty in 0..self.height {
+            for tx in 0..self.width {
+                let tile = self.get(tx, ty);
+
+                if !tile.visible {
+                    continue;
+                }
+
+                let dest_x = offset_x + (tx * W) as i32;
+                let dest_y = offset_y + (ty * H) as i32;
+
+                let inside = dest_x >= 0
+                    && dest_y >= 0
+                    && dest_x as u32 + W <= surface.width()
+                    && dest_y as u32 + H <= surface.height();
+
+                // flipped, rotated, offset or clipped tiles take the generic path
+                if !inside
+                    || tile.opts != BlitOptions::None
+                    || tile.rotation != Rotation::None
+                    || tile.offset != (0, 0)
+                {
+                    self.render_map_tile(surface, tile, (dest_x, dest_y), None, &mut f);
+                    continue;
+                }
+
+                let Some(((x, y, width, height), key_color)) =
+                    self.tileset.resolve_tile(tile, None)
+                else {
+                    continue;
+                };
+
+                // atlas tiles of other sizes take the generic path as well
+                if (width, height) != (W, H) {
+                    self.render_map_tile(surface, tile, (dest_x, dest_y), None, &mut f);
+                    continue;
+                }
+
+                for py in 0..H {
+                    for px in 0..W {
+                        if let Some(src) = self.tileset.source_pixel((x + px, y + py), key_color) {
+                            f(
+                                surface.get_mut(dest_x as u32 + px, dest_y as u32 + py),
+                                &src,
+                                tile,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<C, U, M> Tilemap<C, U, M>
+where
+    C: AsRef<[u8]>,
+{
+    /// Render the map onto a buffer at pixel offset `(offset_x, offset_y)`, blending pixels with `blend`.
+    ///
+    /// `blend` is called with the buffer's pixel, the tileset's pixel and the tile it belongs to
+    /// for every pixel of the visible tiles that isn't the key color. The tile's color isn't applied,
+    /// [`render`](Tilemap::render) is the same as multiplying the tileset's pixel by it and writing the result.
+    pub fn render_with<P, F>(
+        &self,
+        surface: &mut (impl BufferMut<P> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+        blend: F,
+    ) where
+        F: FnMut(&mut P, &Color, &Tile<U>),
+    {
+        self.render_tiles(surface, offset_x, offset_y, None, blend);
+    }
+
+    /// Draw 1 pixel wide lines of color `color` along the tile edges of the map rendered at pixel offset `(offset_x, offset_y)`.
+    ///
+    /// Lines are drawn along the top and left edges of every tile, plus the bottom and right edges of the map,
+    /// all inside the map's area. Call after [`render`](Tilemap::render) to draw the grid on top of the map.
+    /// The grid is always rectangular, regardless of the map's [`layout`](Tilemap::layout).
+    pub fn render_grid(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+        color: Color,
+    ) {
+        let (tile_width, tile_height) = self.tileset.opts.tile_size;
+        let (map_width, map_height) = self.pixel_size();
+
+        if map_width == 0 || map_height == 0 {
+            return;
+        }
+
+        let is_edge =
+            |pos: u32, tile_size: u32, map_size: u32| pos % tile_size == 0 || pos == map_size - 1;
+
+        let x0 = offset_x.max(0) as i64;
+        let y0 = offset_y.max(0) as i64;
+        let x1 = (offset_x as i64 + map_width as i64).min(surface.width() as i64);
+        let y1 = (offset_y as i64 + map_height as i64).min(surface.height() as i64);
+
+        for y in y0..y1 {
+            let map_y = (y - offset_y as i64) as u32;
+            let row_edge = is_edge(map_y, tile_height, map_height);
+
+            for x in x0..x1 {
+                let map_x = (x - offset_x as i64) as u32;
+
+                if row_edge || is_edge(map_x, tile_width, map_width) {
+                    *surface.get_mut(x as u32, y as u32) = color;
+                }
+            }
+        }
+    }
+
+    /// Tileset, position in it and key color of a map tile (or of `fallback` if the tile's id is invalid).
+    #[inline]
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn resolve_tile(
+        &self,
+        tile: &Tile<U>,
+        fallback: Option<TileId>,
+    ) -> Option<(&Tileset<C, M>, (u32, u32, u32, u32), Option<Color>)> {
+        if tile.is_empty() {
+            return None;
+        }
+
+        let resolve = |id| {
+            let (tileset, id) = self.tileset_for(id);
+            Some((tileset, tileset.tile_rect(id)?))
+        };
+
+        let (tileset, rect) = resolve(tile.id).or_else(|| resolve(fallback?))?;
+        let key_color = tile.key_color_override.unwrap_or(tileset.opts.key_color);
+
+        Some((tileset, rect, key_color))
+    }
+
+    /// Blit a map tile from whichever tileset it belongs to at pixel position `dest`,
+    /// calling `f` for each of its pixels that should be drawn.
+    #[inline]
+    pub(crate) fn render_map_tile<P>(
+        &self,
+        surface: &mut (impl BufferMut<P> + ?Sized),
+        tile: &Tile<U>,
+        dest: (i32, i32),
+        fallback: Option<TileId>,
+        f: &mut impl FnMut(&mut P, &Color, &Tile<U>),
+    ) {
+        if let Some((tileset, rect, key_color)) = self.resolve_tile(tile, fallback) {
+            let dest = self.image_dest(tileset, tile, (rect.2, rect.3), dest);
+            tileset.blit_map_tile(surface, tile, rect, key_color, dest, f);
+        }
+    }
+
+    /// Blit every tile at its position, calling `f` for each of its pixels that isn't the key color.
+    ///
+    /// Tiles with invalid ids are drawn as `fallback`, if it's valid.
+    pub(crate) fn render_tiles<P>(
+        &self,
+        surface: &mut (impl BufferMut<P> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+        fallback: Option<TileId>,
+        f: impl FnMut(&mut P, &Color, &Tile<U>),
+    ) {
+        self.render_tile_range(
+            surface,
+            offset_x,
+            offset_y,
+            0..self.width,
+            0..self.height,
+            fallback,
+            f,
+        );
+    }
+
+    /// Same as [`render_tiles`](Self::render_tiles), but only for tiles in the given columns and rows.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn render_tile_range<P>(
+        &self,
+        surface: &mut (impl BufferMut<P> + ?Sized),
+        offset_x: i32,
+        offset_y: i32,
+        columns: Range<u32>,
+        rows: Range<u32>,
+        fallback: Option<TileId>,
+        mut f: impl FnMut(&mut P, &Color, &Tile<U>),
+    ) {
+        let cells = self
+            .visible_cells(offset_x, offset_y, surface.width(), surface.height())
+            .filter(|(tx, ty)| columns.contains(tx) && rows.contains(ty));
+
+        for (tx, ty) in cells {
+            let tile = &self.tiles[self.xy_to_index(tx, ty)];
+
+            if tile.visible {
+                self.render_map_tile(
+                    surface,
+                    tile,
+                    self.tile_dest(tx, ty, offset_x, offset_y),
+                    fallback,
+                    &mut f,
+                );
+            }
+        }
+    }
+
+    /// Blit every visible tile scaled by `scale` with the map's origin at buffer position `offset`,
+    /// only inside the `clip` (width x height) area in the top-left corner of the surface.
+    pub(crate) fn render_tiles_scaled<P>(
+        &self,
+        surface: &mut (impl BufferMut<P> + ?Sized),
+        offset: (i64, i64),
+        scale: u32,
+        clip: (u32, u32),
+        mut f: impl FnMut(&mut P, &Color, &Tile<U>),
+    ) {
+        let scale = scale.max(1);
+        let clip = (clip.0.min(surface.width()), clip.1.min(surface.height()));
+
+        // the clipped area in unscaled map pixels
+        let area = |offset: i64, size: u32| {
+            let start = (-offset).div_euclid(scale as i64);
+            let end = (size as i64 - offset + scale as i64 - 1).div_euclid(scale as i64);
+
+            (
+                saturate(-start),
+                (end - start).clamp(0, u32::MAX as i64) as u32,
+            )
+        };
+
+        let (area_x, area_width) = area(offset.0, clip.0);
+        let (area_y, area_height) = area(offset.1, clip.1);
+        for (tx, ty) in self.visible_cells(area_x, area_y, area_width, area_height) {
+            let tile = &self.tiles[self.xy_to_index(tx, ty)];
+
+            if !tile.visible {
+                continue;
+            }
+
+            if let Some((tileset, rect, key_color)) = self.resolve_tile(tile, None) {
+                let cell = self.tile_dest(tx, ty, 0, 0);
+                let (left, top) = self.image_dest(tileset, tile, (rect.2, rect.3), cell);
+
+                tileset.blit_map_tile_scaled(
+                    surface,
+                    tile,
+                    rect,
+                    key_color,
+                    (
+                        offset.0 + left as i64 * scale as i64,
+                        offset.1 + top as i64 * scale as i64,
+                    ),
+                    scale,
+                    clip,
+                    &mut f,
+                );
+            }
+        }
+    }
+}
+
+/// Range of the cells along one axis that intersect `0..surface_size`, with cell 0 at pixel `offset`.
+///
+/// Tiles are `step` pixels apart, `size` pixels large, shifted by up to `shift` pixels
+/// and stick out of their cells by `before` and `after` pixels.
+pub(crate) fn cell_span(
+    offset: i64,
+    surface_size: u32,
+    step: u32,
+    size: u32,
+    shift: u32,
+    (before, after): (u32, u32),
+) -> Range<i64> {
+    if step == 0 {
+        return 0..0;
+    }
+
+    let step = step as i64;
+
+    let start = (-offset - shift as i64 - size as i64 - after as i64).div_euclid(step) + 1;
+    let end = (surface_size as i64 - offset + before as i64 + step - 1).div_euclid(step);
+
+    start..end.max(start)
+}
+
+/// Part of a range of cells that lies within `0..map_size`.
+#[inline]
+pub(crate) fn clamp_span(cells: Range<i64>, map_size: u32) -> Range<u32> {
+    let start = cells.start.clamp(0, map_size as i64);
+    start as u32..cells.end.clamp(start, map_size as i64) as u32
+}
+
+/// How far (before, after) in pixels a tile's [offset](Tile::offset) can move it.
+pub(crate) const OFFSET_MARGIN: (u32, u32) = (i8::MAX as u32, i8::MIN.unsigned_abs() as u32);
+
+/// Buffer wrapper that keeps track of the corners (left, top, right, bottom) of the pixels that were drawn to.
+struct BoundsTracker<'a, B: ?Sized> {
+    surface: &'a mut B,
+    bounds: Option<(i32, i32, i32, i32)>,
+}
+
+impl<B: BufferMut<Color> + ?Sized> Buffer<Color> for BoundsTracker<'_, B> {
+    #[inline]
+    fn width(&self) -> u32 {
+        self.surface.width()
+    }
+
+    #[inline]
+    fn height(&self) -> u32 {
+        self.surface.height()
+    }
+
+    #[inline]
+    fn get(&self, x: u32, y: u32) -> &Color {
+        self.surface.get(x, y)
+    }
+}
+
+impl<B: BufferMut<Color> + ?Sized> BufferMut<Color> for BoundsTracker<'_, B> {
+    #[inline]
+    fn get_mut(&mut self, x: u32, y: u32) -> &mut Color {
+        let (x, y) = (x as i32, y as i32);
+
+        self.bounds = Some(match self.bounds {
+            Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+            None => (x, y, x, y),
+        });
+
+        self.surface.get_mut(x as u32, y as u32)
+    }
+}
+
+/// Multiply two colors channel by channel in linear space.
+#[inline]
+pub(crate) fn multiply(lhs: Color, rhs: Color) -> Color {
+    let [r, g, b, a] = f32x4_to_srgb8([
+        srgb8_to_f32(lhs.r) * srgb8_to_f32(rhs.r),
+        srgb8_to_f32(lhs.g) * srgb8_to_f32(rhs.g),
+        srgb8_to_f32(lhs.b) * srgb8_to_f32(rhs.b),
+        srgb8_to_f32(lhs.a) * srgb8_to_f32(rhs.a),
+    ]);
+
+    Color::new(r, g, b, a)
+}
+
+impl<C, M> Buffer<Tile> for Tilemap<C, (), M> {
+    #[inline]
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[inline]
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[inline]
+    fn get(&self, x: u32, y: u32) -> &Tile {
+        self.tiles.index((y * self.width + x) as usize)
+    }
+}
+
+impl<C, M> BufferMut<Tile> for Tilemap<C, (), M> {
+    #[inline]
+    fn get_mut(&mut self, x: u32, y: u32) -> &mut Tile {
+        let i = (y * self.width + x) as usize;
+        self.touch(i);
+        self.tiles.index_mut(i)
+    }
+}
+
+/// Current version of the serialized [`Tilemap`] format.
+///
+/// Saves made before the version was recorded are treated as version 0.
+#[cfg(feature = "serde")]
+pub const TILEMAP_FORMAT_VERSION: u16 = 3;
+
+/// Serialized form of a [`Tilemap`].
+///
+/// Fields added in later versions are optional, so older saves can still be loaded
+/// with [`Tilemap::from_serialized`].
+#[cfg(feature = "serde")]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct SerializedTilemap<C, U = (), M = ()> {
+    /// Version of the format the map was saved with.
+    #[serde(default)]
+    pub version: u16,
+    /// The map's main tileset.
+    pub tileset: Tileset<C, M>,
+    /// The map's additional tilesets along with their first ids.
+    #[serde(default = "Vec::new")]
+    pub extra_tilesets: Vec<(TileId, Tileset<C, M>)>,
+    /// The map's tiles, row by row.
+    pub tiles: Vec<Tile<U>>,
+    /// Width of the map in tiles.
+    pub width: u32,
+    /// Height of the map in tiles.
+    pub height: u32,
+    /// The map's bit overlay.
+    #[serde(default)]
+    pub bits: Option<BitGrid>,
+    /// The map's layout.
+    #[serde(default)]
+    pub layout: MapLayout,
+}
+
+#[cfg(feature = "serde")]
+impl<C, U, M> Tilemap<C, U, M> {
+    /// Construct a map from its serialized form.
+    ///
+    /// Fields missing from older versions get their default values.
+    /// Returns `None` if the version is newer than [`TILEMAP_FORMAT_VERSION`]
+    /// the amount of tiles doesn't match the map's size
+    /// or the first ids of the additional tilesets aren't above 0 and in ascending order.
+    pub fn from_serialized(serialized: SerializedTilemap<C, U, M>) -> Option<Self> {
+        let len = serialized.width.checked_mul(serialized.height)?;
+
+        if serialized.version > TILEMAP_FORMAT_VERSION || serialized.tiles.len() != len as usize {
+            return None;
+        }
+
+        // first ids must be unique, sorted and above 0
+        let mut prev_first_id = 0;
+
+        for &(first_id, _) in &serialized.extra_tilesets {
+            if first_id <= prev_first_id {
+                return None;
+            }
+
+            prev_first_id = first_id;
+        }
+
+        if let Some(bits) = &serialized.bits {
+            if bits.width() != serialized.width || bits.height() != serialized.height {
+                return None;
+            }
+        }
+
+        Some(Self {
+            tileset: serialized.tileset,
+            extra_tilesets: serialized.extra_tilesets,
+            tiles: serialized.tiles,
+            width: serialized.width,
+            height: serialized.height,
+            bits: serialized.bits,
+            dirty: None,
+            layout: serialized.layout,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<C, U, M> serde::Serialize for Tilemap<C, U, M>
+where
+    C: serde::Serialize,
+    U: serde::Serialize,
+    M: serde::Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("SerializedTilemap", 8)?;
+        state.serialize_field("version", &TILEMAP_FORMAT_VERSION)?;
+        state.serialize_field("tileset", &self.tileset)?;
+        state.serialize_field("extra_tilesets", &self.extra_tilesets)?;
+        state.serialize_field("tiles", &self.tiles)?;
+        state.serialize_field("width", &self.width)?;
+        state.serialize_field("height", &self.height)?;
+        state.serialize_field("bits", &self.bits)?;
+        state.serialize_field("layout", &self.layout)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, C, U, M> serde::Deserialize<'de> for Tilemap<C, U, M>
+where
+    C: serde::Deserialize<'de>,
+    U: serde::Deserialize<'de>,
+    M: serde::Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let serialized =
+            <SerializedTilemap<C, U, M> as serde::Deserialize<'de>>::deserialize(deserializer)?;
+
+        Self::from_serialized(serialized)
+            .ok_or_else(|| serde::de::Error::custom("invalid tilemap data"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{shade, tileset, tileset_from, Surface};
+    use crate::TilesetOptions;
+
+    #[test]
+    fn get_two_mut() {
+        let mut map: Tilemap<_> = Tilemap::new(3, 3, tileset(3));
+
+        let (a, b) = map.get_two_mut((0, 0), (2, 1)).unwrap();
+        a.id = 1;
+        b.id = 2;
+
+        assert_eq!(map.get_tile(0, 0).unwrap().id, 1);
+        assert_eq!(map.get_tile(2, 1).unwrap().id, 2);
+
+        let (a, b) = map.get_two_mut((2, 1), (0, 0)).unwrap();
+        assert_eq!((a.id, b.id), (2, 1));
+
+        assert!(map.get_two_mut((1, 1), (1, 1)).is_none());
+        assert!(map.get_two_mut((3, 0), (1, 1)).is_none());
+    }
+
+    #[test]
+    fn validate_reports_missing_ids() {
+        let mut map: Tilemap<_> = Tilemap::new(3, 2, tileset(3));
+        assert_eq!(map.validate(), Ok(()));
+
+        map.set_tile(1, 1, Tile::new(7));
+        assert_eq!(map.validate(), Err(vec![(1, 1, 7)]));
+    }
+
+    #[test]
+    fn render_with_background_fills_holes() {
+        let key = Color::new(255, 0, 255, 255);
+        let mut pixels = vec![shade(0); 4];
+        pixels[1] = key;
+        let tileset = tileset_from(&pixels, 2, TilesetOptions::new(2, 2).with_key_color(key));
+
+        let mut map: Tilemap<_> = Tilemap::new(2, 1, tileset);
+        map.tiles_mut().fill(Tile::new(0));
+        let mut surface = Surface::new(6, 3);
+        surface.pixels.fill(Color::new(1, 1, 1, 1));
+
+        let bg = Color::new(0, 0, 200, 255);
+        map.render_with_background(&mut surface, 1, 1, bg);
+
+        assert_eq!(surface.at(1, 1), shade(0));
+        assert_eq!(surface.at(2, 1), bg);
+        assert_eq!(surface.at(4, 1), bg);
+        assert_eq!(surface.at(0, 0), Color::new(1, 1, 1, 1));
+        assert_eq!(surface.at(5, 1), Color::new(1, 1, 1, 1));
+    }
+
+    #[test]
+    fn render_draws_tiles_at_their_cells() {
+        let mut map: Tilemap<_> = Tilemap::new(2, 2, tileset(4));
+        for (i, tile) in map.tiles_mut().iter_mut().enumerate() {
+            *tile = Tile::new(i as TileId);
+        }
+
+        let mut surface = Surface::new(5, 5);
+        map.render(&mut surface, 1, 1);
+
+        assert_eq!(surface.at(1, 1), shade(0));
+        assert_eq!(surface.at(3, 1), shade(1));
+        assert_eq!(surface.at(1, 3), shade(2));
+        assert_eq!(surface.at(4, 4), shade(3));
+        assert_eq!(surface.at(0, 0), Color::default());
+    }
+
+    #[test]
+    #[should_panic(expected = "map too large")]
+    fn new_rejects_oversized_maps() {
+        let _map: Tilemap<_> = Tilemap::new(70000, 70000, tileset(1));
+    }
+
+    #[test]
+    fn tile_screen_rect_matches_render() {
+        let mut map: Tilemap<_> = Tilemap::new(3, 3, tileset(2));
+        map.set_tile(1, 1, Tile::new(1));
+
+        assert_eq!(map.tile_screen_rect(1, 1, 5, -3), Some((7, -1, 2, 2)));
+        assert_eq!(map.tile_screen_rect(3, 0, 0, 0), None);
+
+        let (x, y, w, h) = map.tile_screen_rect(1, 1, 1, 2).unwrap();
+        let mut surface = Surface::new(8, 8);
+        map.render(&mut surface, 1, 2);
+
+        for py in 0..8 {
+            for px in 0..8 {
+                let inside = (x..x + w as i32).contains(&(px as i32))
+                    && (y..y + h as i32).contains(&(py as i32));
+                assert_eq!(surface.at(px, py) == shade(1), inside);
+            }
+        }
+    }
+
+    #[test]
+    fn zero_sized_maps() {
+        for (width, height) in [(0, 0), (0, 5), (5, 0)] {
+            let mut map: Tilemap<_> = Tilemap::new(width, height, tileset(1));
+
+            assert!(map.get_tile(0, 0).is_none());
+            assert!(map.get_mut_tile(0, 0).is_none());
+            map.set_tile(0, 0, Tile::new(0));
+            map.set_tile(0, 3, Tile::new(0));
+
+            let mut surface = Surface::new(4, 4);
+            map.render(&mut surface, 0, 0);
+            map.render_with_background(&mut surface, 0, 0, Color::new(1, 2, 3, 4));
+            assert!(surface
+                .pixels
+                .iter()
+                .all(|&pixel| pixel == Color::default()));
+        }
+    }
+
+    #[test]
+    fn iter_region_clips_to_map() {
+        let mut map: Tilemap<_> = Tilemap::new(3, 3, tileset(1));
+        for (i, tile) in map.tiles_mut().iter_mut().enumerate() {
+            tile.id = i as TileId;
+        }
+
+        let region: Vec<_> = map
+            .iter_region(1, 1, 5, 5)
+            .map(|(x, y, tile)| (x, y, tile.id))
+            .collect();
+        assert_eq!(region, vec![(1, 1, 4), (2, 1, 5), (1, 2, 7), (2, 2, 8)]);
+
+        assert_eq!(map.iter_region(3, 0, 2, 2).count(), 0);
+    }
+
+    #[test]
+    fn render_with_shadow_draws_under_tiles() {
+        let mut map: Tilemap<_> = Tilemap::new(1, 1, tileset(1));
+        map.tiles_mut().fill(Tile::new(0));
+        let mut surface = Surface::new(6, 6);
+
+        let shadow = Color::new(0, 0, 50, 255);
+        map.render_with_shadow(&mut surface, 1, 1, (2, 2), shadow);
+
+        assert_eq!(surface.at(1, 1), shade(0));
+        assert_eq!(surface.at(2, 2), shade(0));
+        assert_eq!(surface.at(3, 3), shadow);
+        assert_eq!(surface.at(4, 4), shadow);
+        assert_eq!(surface.at(5, 5), Color::default());
+
+        // translucent shadows darken what's below them
+        let background = Color::new(200, 200, 200, 255);
+        let shadow = Color::new(0, 0, 0, 128);
+        let mut surface = Surface::filled(6, 6, background);
+        map.render_with_shadow(&mut surface, 1, 1, (2, 2), shadow);
+
+        assert_eq!(surface.at(4, 4), blend_over(background, shadow));
+        assert_eq!(surface.at(4, 4).a, 255);
+        assert!(surface.at(4, 4).r < background.r);
+        assert_eq!(surface.at(5, 5), background);
+    }
+
+    #[test]
+    fn key_color_tolerance() {
+        let key = Color::new(255, 0, 255, 255);
+        let near_key = Color::new(252, 2, 255, 255);
+
+        for (tolerance, masked) in [(0, false), (4, true)] {
+            let opts = TilesetOptions::new(1, 1)
+                .with_key_color(key)
+                .with_key_color_tolerance(tolerance);
+            let mut map: Tilemap<_> = Tilemap::new(1, 1, tileset_from(&[near_key], 1, opts));
+            map.tiles_mut().fill(Tile::new(0));
+
+            let mut surface = Surface::new(1, 1);
+            map.render(&mut surface, 0, 0);
+            assert_eq!(surface.at(0, 0) == Color::default(), masked);
+        }
+    }
+
+    #[test]
+    fn lerp_color() {
+        let from = Color::new(0, 100, 255, 0);
+        let to = Color::new(255, 100, 0, 255);
+
+        let mut tile: Tile = Tile::new(0).with_color(from);
+        tile.lerp_color(to, 0.0);
+        assert_eq!(tile.color, from);
+        tile.lerp_color(to, 1.0);
+        assert_eq!(tile.color, to);
+
+        let mut tile: Tile = Tile::new(0).with_color(from);
+        tile.lerp_color(to, 0.5);
+        assert_eq!(tile.color.g, 100);
+        assert_eq!(tile.color.r, tile.color.b);
+        // halfway in linear space is brighter than halfway in sRGB
+        assert!(tile.color.r > 128);
+
+        let mut tile: Tile = Tile::new(0).with_color(from);
+        tile.lerp_color(to, 7.0);
+        assert_eq!(tile.color, to);
+    }
+
+    #[test]
+    fn builder_matches_manual_map() {
+        let built: Tilemap<_> = TilemapBuilder::new(4, 3, tileset(3))
+            .fill(Tile::new(1))
+            .border(Tile::new(2))
+            .set(1, 1, Tile::new(0))
+            .set(9, 9, Tile::new(0))
+            .build();
+
+        let mut manual: Tilemap<_> = Tilemap::new(4, 3, tileset(3));
+        for y in 0..3 {
+            for x in 0..4 {
+                let border = x == 0 || y == 0 || x == 3 || y == 2;
+                manual.set_tile(x, y, Tile::new(if border { 2 } else { 1 }));
+            }
+        }
+        manual.set_tile(1, 1, Tile::new(0));
+
+        assert_eq!(built.tiles(), manual.tiles());
+
+        let empty: Tilemap<_> = TilemapBuilder::new(0, 0, tileset(3))
+            .border(Tile::new(2))
+            .build();
+        assert!(empty.tiles().is_empty());
+    }
+
+    #[test]
+    fn alpha_mask_hides_pixels() {
+        let tileset = tileset_from(&[shade(0); 9], 3, TilesetOptions::new(2, 2));
+        assert!(tileset.clone().with_alpha_mask(vec![0; 4]).is_none());
+
+        let mut mask = vec![255; (tileset.width() * tileset.height()) as usize];
+        mask[1] = 0;
+        let tileset = tileset.with_alpha_mask(mask).unwrap();
+        assert_eq!(tileset.tile_is_opaque(0), Some(false));
+
+        let mut map: Tilemap<_> = Tilemap::new(2, 1, tileset);
+        map.set_tile(0, 0, Tile::new(0));
+        map.set_tile(
+            1,
+            0,
+            Tile::new(0).with_blit_options(BlitOptions::FlipHorizontal),
+        );
+
+        let mut surface = Surface::new(4, 2);
+        surface.pixels.fill(Color::new(1, 1, 1, 255));
+        map.render(&mut surface, 0, 0);
+
+        assert_eq!(surface.at(0, 0).a, 255);
+        assert_eq!(surface.at(1, 0).a, 0);
+        assert_eq!(surface.at(2, 0).a, 0);
+        assert_eq!(surface.at(3, 0).a, 255);
+        assert_eq!(surface.at(1, 1).a, 255);
+    }
+
+    #[test]
+    fn contains_and_count_id() {
+        let mut map: Tilemap<_> = Tilemap::new(3, 3, tileset(3));
+        assert!(!map.contains_id(2));
+        assert_eq!(map.count_id(2), 0);
+
+        map.set_tile(1, 1, Tile::new(2));
+        map.set_tile(2, 1, Tile::new(2));
+        assert!(map.contains_id(2));
+        assert_eq!(map.count_id(2), 2);
+        assert_eq!(map.count_id(0), 7);
+    }
+
+    #[test]
+    fn render_wrapped_with_large_offsets() {
+        let mut map: Tilemap<_> = Tilemap::new(2, 1, tileset(2));
+        map.set_tile(0, 0, Tile::new(0));
+        map.set_tile(1, 0, Tile::new(1));
+
+        // the map is 4 pixels wide, so an offset of 9 starts one pixel into a copy
+        let mut surface = Surface::new(6, 3);
+        map.render_wrapped(&mut surface, 9, -5);
+
+        let row = |y| (0..6).map(|x| surface.at(x, y)).collect::<Vec<_>>();
+        for y in 0..3 {
+            assert_eq!(
+                row(y),
+                [shade(1), shade(0), shade(0), shade(1), shade(1), shade(0)]
+            );
+        }
+    }
+
+    #[test]
+    fn flat_index_round_trip() {
+        let mut map: Tilemap<_> = Tilemap::new(3, 2, tileset(3));
+        for i in 0..6 {
+            let (x, y) = map.index_to_xy(i);
+            assert_eq!(map.xy_to_index(x, y), i);
+        }
+        assert_eq!(map.index_to_xy(4), (1, 1));
+
+        map.set_tile_index(4, Tile::new(2));
+        assert_eq!(map.get_tile(1, 1).unwrap().id, 2);
+        map.set_tile_index(6, Tile::new(2));
+        assert!(map.get_tile_index(6).is_none());
+
+        map.get_mut_tile_index(0).unwrap().id = 1;
+        assert_eq!(map.get_tile(0, 0).unwrap().id, 1);
+    }
+
+    #[test]
+    fn invisible_tiles_are_skipped() {
+        let tile: Tile = Tile::default();
+        assert!(tile.visible);
+
+        let mut map: Tilemap<_> = TilemapBuilder::new(3, 1, tileset(3))
+            .fill(Tile::new(1))
+            .set(0, 0, Tile::new(1).with_visibility(false))
+            .build();
+        map.set_visibility_rect(2, 0, 5, 5, false);
+
+        let mut surface = Surface::new(6, 2);
+        map.render(&mut surface, 0, 0);
+
+        assert_eq!(surface.at(0, 0), Color::default());
+        assert_eq!(surface.at(2, 0), shade(1));
+        assert_eq!(surface.at(4, 0), Color::default());
+    }
+
+    #[test]
+    fn pixel_size() {
+        let tileset = tileset_from(
+            &[Color::default(); 32 * 16],
+            32,
+            TilesetOptions::new(16, 16),
+        );
+        let map: Tilemap<_> = Tilemap::new(4, 3, tileset);
+
+        assert_eq!(map.tile_size(), (16, 16));
+        assert_eq!(map.pixel_size(), (64, 48));
+    }
+
+    #[test]
+    fn bit_overlay() {
+        let mut map: Tilemap<_> = Tilemap::new(3, 2, tileset(3));
+        assert!(!map.get_bit(1, 1));
+        map.set_bit(1, 1, true);
+        assert!(map.get_bit(1, 1));
+
+        map.set_tile(2, 0, Tile::new(2));
+        map.fill_bits_from(|tile| tile.id == 2);
+        assert!(map.get_bit(2, 0));
+        assert!(!map.get_bit(1, 1));
+    }
+
+    #[test]
+    fn rows_mut() {
+        let mut map: Tilemap<_> = Tilemap::new(3, 2, tileset(3));
+        for tile in map.rows_mut().nth(1).unwrap() {
+            tile.id = 2;
+        }
+
+        assert_eq!(map.get_tile(1, 1).unwrap().id, 2);
+        assert_eq!(map.get_tile(1, 0).unwrap().id, 0);
+        assert_eq!(map.rows().count(), 2);
+
+        let empty: Tilemap<_> = Tilemap::new(0, 3, tileset(3));
+        assert_eq!(empty.rows().count(), 0);
+    }
+
+    #[test]
+    fn tint_rect() {
+        let mut map: Tilemap<_> = Tilemap::new(3, 3, tileset(3));
+        for (i, tile) in map.tiles_mut().iter_mut().enumerate() {
+            *tile = Tile::new(i as TileId % 3);
+        }
+
+        let tint = Color::new(1, 2, 3, 4);
+        map.tint_rect(1, 1, 2, 2, tint);
+        map.tint_rect(5, 0, 2, 2, tint);
+
+        for y in 0..3 {
+            for x in 0..3 {
+                let tile = map.get_tile(x, y).unwrap();
+                assert_eq!(tile.color == tint, x >= 1 && y >= 1);
+                assert_eq!(tile.id, (y * 3 + x) % 3);
+            }
+        }
+
+        map.clear_tint_rect(0, 0, 3, 3);
+        assert!(map
+            .tiles()
+            .iter()
+            .all(|tile| tile.color == Color::new(255, 255, 255, 255)));
+    }
+
+    #[test]
+    fn map_tileset_into_arc() {
+        let mut map: Tilemap<Vec<u8>> = Tilemap::new(3, 1, tileset(3));
+        map.set_tile(1, 0, Tile::new(2));
+        let mut expected = Surface::new(6, 2);
+        map.render(&mut expected, 0, 0);
+
+        let map: Tilemap<alloc::sync::Arc<[u8]>> = map.map_tileset(Tileset::into_container);
+        let mut surface = Surface::new(6, 2);
+        map.render(&mut surface, 0, 0);
+
+        assert_eq!(surface.pixels, expected.pixels);
+        assert_eq!(surface.at(2, 0), shade(2));
+    }
+
+    #[test]
+    fn render_with_fallback() {
+        let mut map: Tilemap<_> = Tilemap::new(2, 1, tileset(3));
+        map.set_tile(0, 0, Tile::new(9));
+        map.set_tile(1, 0, Tile::new(1));
+
+        let mut surface = Surface::new(4, 2);
+        map.render_with_fallback(&mut surface, 0, 0, 2);
+        assert_eq!(surface.at(0, 0), shade(2));
+        assert_eq!(surface.at(2, 0), shade(1));
+
+        let mut surface = Surface::new(4, 2);
+        map.render_with_fallback(&mut surface, 0, 0, 7);
+        assert_eq!(surface.at(0, 0), Color::default());
+        assert_eq!(surface.at(2, 0), shade(1));
+    }
+
+    #[test]
+    fn try_set_tile() {
+        let mut map: Tilemap<_> = Tilemap::new(3, 2, tileset(3));
+        assert!(map.try_set_tile(2, 1, Tile::new(1)));
+        assert_eq!(map.get_tile(2, 1).unwrap().id, 1);
+
+        let before = map.tiles().to_vec();
+        assert!(!map.try_set_tile(3, 0, Tile::new(2)));
+        assert_eq!(map.tiles(), before);
+    }
+
+    #[test]
+    fn render_paletted() {
+        // palette indices are stored in the red channel
+        let index = |i| Color::new(i, 0, 0, 255);
+        let tileset = tileset_from(
+            &[index(0), index(1), index(5), index(0)],
+            2,
+            TilesetOptions::new(2, 2),
+        );
+        let map: Tilemap<_> = TilemapBuilder::new(1, 1, tileset)
+            .fill(Tile::new(0))
+            .build();
+
+        let first = [Color::new(10, 0, 0, 255), Color::new(0, 20, 0, 255)];
+        let second = [Color::new(0, 0, 30, 255), Color::new(40, 0, 0, 255)];
+
+        let mut a = Surface::new(2, 2);
+        map.render_paletted(&mut a, 0, 0, &first);
+        let mut b = Surface::new(2, 2);
+        map.render_paletted(&mut b, 0, 0, &second);
+
+        assert_eq!(a.at(0, 0), first[0]);
+        assert_eq!(a.at(1, 0), first[1]);
+        assert_eq!(a.at(0, 1).a, 0);
+        assert_eq!(b.at(0, 0), second[0]);
+        assert_eq!(b.at(1, 0), second[1]);
+    }
+
+    #[test]
+    fn insert_and_remove_rows() {
+        let ids = |map: &Tilemap<_>| map.tiles().iter().map(|tile| tile.id).collect::<Vec<_>>();
+
+        let mut map: Tilemap<_> = Tilemap::new(3, 3, tileset(3));
+        for (i, tile) in map.tiles_mut().iter_mut().enumerate() {
+            tile.id = i as TileId;
+        }
+        map.set_bit(1, 2, true);
+
+        map.insert_row(1, Tile::new(99));
+        assert_eq!((map.width(), map.height()), (3, 4));
+        assert_eq!(ids(&map), vec![0, 1, 2, 99, 99, 99, 3, 4, 5, 6, 7, 8]);
+        assert!(map.get_bit(1, 3));
+        assert!(!map.get_bit(1, 2));
+
+        map.insert_row(10, Tile::new(7));
+        assert_eq!(map.height(), 5);
+        assert_eq!(map.get_tile(0, 4).unwrap().id, 7);
+
+        map.remove_row(1);
+        map.remove_row(3);
+        map.remove_row(3);
+        assert_eq!(ids(&map), (0..9).collect::<Vec<_>>());
+        assert!(map.get_bit(1, 2));
+    }
+
+    #[test]
+    fn insert_and_remove_columns() {
+        let ids = |map: &Tilemap<_>| map.tiles().iter().map(|tile| tile.id).collect::<Vec<_>>();
+
+        let mut map: Tilemap<_> = Tilemap::new(3, 3, tileset(3));
+        for (i, tile) in map.tiles_mut().iter_mut().enumerate() {
+            tile.id = i as TileId;
+        }
+        map.set_bit(1, 2, true);
+
+        map.insert_column(1, Tile::new(50));
+        assert_eq!(ids(&map), vec![0, 50, 1, 2, 3, 50, 4, 5, 6, 50, 7, 8]);
+        assert!(map.get_bit(2, 2));
+
+        map.insert_column(9, Tile::new(51));
+        assert_eq!(map.width(), 5);
+        assert_eq!(map.get_tile(4, 2).unwrap().id, 51);
+
+        map.remove_column(4);
+        map.remove_column(1);
+        assert_eq!(ids(&map), (0..9).collect::<Vec<_>>());
+        assert!(map.get_bit(1, 2));
+
+        let mut empty: Tilemap<_> = Tilemap::new(0, 2, tileset(3));
+        empty.insert_column(0, Tile::new(1));
+        assert_eq!(empty.tiles().len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "map too large")]
+    fn insert_row_past_max_height() {
+        let mut map: Tilemap<_> = Tilemap::new(0, u32::MAX, tileset(3));
+        map.insert_row(0, Tile::new(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "map too large")]
+    fn insert_column_past_max_width() {
+        let mut map: Tilemap<_> = Tilemap::new(u32::MAX, 0, tileset(3));
+        map.insert_column(0, Tile::new(1));
+    }
+
+    #[test]
+    fn render_into_packed_pixels() {
+        struct Packed(Vec<u32>);
+
+        impl Buffer<u32> for Packed {
+            fn width(&self) -> u32 {
+                4
+            }
+
+            fn height(&self) -> u32 {
+                2
+            }
+
+            fn get(&self, x: u32, y: u32) -> &u32 {
+                &self.0[(y * 4 + x) as usize]
+            }
+        }
+
+        impl BufferMut<u32> for Packed {
+            fn get_mut(&mut self, x: u32, y: u32) -> &mut u32 {
+                &mut self.0[(y * 4 + x) as usize]
+            }
+        }
+
+        let mut map: Tilemap<_> = Tilemap::new(2, 1, tileset(3));
+        map.set_tile(0, 0, Tile::new(1));
+        map.set_tile(1, 0, Tile::new(2));
+
+        let pack = |c: Color| u32::from_be_bytes([c.r, c.g, c.b, c.a]);
+        let unpack = |p: &u32| {
+            let [r, g, b, a] = p.to_be_bytes();
+            Color::new(r, g, b, a)
+        };
+
+        let mut surface = Packed(vec![0; 8]);
+        map.render_into(&mut surface, 0, 0, pack, unpack);
+
+        assert_eq!(surface.0[0], 0x140000ff);
+        assert_eq!(surface.0[7], 0x1e0000ff);
+
+        // blend modes see the buffer's pixels, same as with `render`
+        map.get_mut_tile(1, 0).unwrap().blend = BlendMode::Additive;
+        let background = Color::new(40, 80, 0, 255);
+        let mut expected = Surface::filled(4, 2, background);
+        map.render(&mut expected, 0, 0);
+
+        let mut surface = Packed(vec![pack(background); 8]);
+        map.render_into(&mut surface, 0, 0, pack, unpack);
+        let pixels: Vec<_> = surface.0.iter().map(unpack).collect();
+        assert_eq!(pixels, expected.pixels);
+        assert_ne!(pixels[3], shade(2));
+    }
+
+    #[test]
+    fn key_color_override() {
+        let key = Color::new(255, 0, 255, 255);
+        let tileset = tileset_from(&[key; 4], 2, TilesetOptions::new(2, 2).with_key_color(key));
+        assert!(tileset.is_key_color(key));
+        assert!(!tileset.is_key_color(Color::new(1, 1, 1, 1)));
+
+        let mut map: Tilemap<_> = Tilemap::new(2, 1, tileset);
+        map.set_tile(0, 0, Tile::new(0));
+        map.set_tile(1, 0, Tile::new(0).with_key_color(None));
+
+        let mut surface = Surface::new(4, 2);
+        map.render(&mut surface, 0, 0);
+        assert_eq!(surface.at(0, 0), Color::default());
+        assert_eq!(surface.at(2, 0), key);
+
+        map.get_mut_tile(0, 0)
+            .unwrap()
+            .set_key_color(Some(Color::new(1, 1, 1, 1)));
+        let mut surface = Surface::new(4, 2);
+        map.render(&mut surface, 0, 0);
+        assert_eq!(surface.at(0, 0), key);
+    }
+
+    #[test]
+    fn draw_rect_outline() {
+        let mut map: Tilemap<_> = Tilemap::new(6, 6, tileset(3));
+        map.draw_rect(1, 1, 4, 4, Tile::new(2));
+
+        assert_eq!(map.count_id(2), 12);
+        for (x, y) in [(2, 2), (3, 2), (2, 3), (3, 3)] {
+            assert_eq!(map.get_tile(x, y).unwrap().id, 0);
+        }
+
+        // clipped at the map's edge
+        map.draw_rect(4, 4, 5, 5, Tile::new(1));
+        assert_eq!(map.count_id(1), 3);
+
+        // every cell of a 1-tall rectangle is on its perimeter
+        map.draw_rect(0, 5, 6, 1, Tile::new(2));
+        assert!(map.iter_region(0, 5, 6, 1).all(|(_, _, tile)| tile.id == 2));
+    }
+
+    #[test]
+    fn render_const_matches_render() {
+        let key = Color::new(255, 0, 255, 255);
+        let mut pixels: Vec<_> = (0..10 * 2)
+            .map(|i| Color::new(i as u8 * 7, 3, 200, 255))
+            .collect();
+        pixels[3] = key;
+        let tileset = tileset_from(&pixels, 10, TilesetOptions::new(2, 2).with_key_color(key));
+
+        let mut map: Tilemap<_> = Tilemap::new(4, 3, tileset);
+        for (i, tile) in map.tiles_mut().iter_mut().enumerate() {
+            *tile = Tile::new(i as TileId % 5).with_color(Color::new(200, 100, 255, 255));
+        }
+        map.get_mut_tile(1, 1)
+            .unwrap()
+            .set_blit_options(BlitOptions::FlipHorizontal);
+        map.get_mut_tile(2, 1).unwrap().set_visibility(false);
+        map.get_mut_tile(3, 1).unwrap().set_offset(1, -1);
+
+        // fully inside, clipped at the top left and at the bottom right
+        for (offset_x, offset_y) in [(0, 0), (-1, 1), (3, 2)] {
+            let mut expected = Surface::new(9, 7);
+            map.render(&mut expected, offset_x, offset_y);
+
+            let mut surface = Surface::new(9, 7);
+            map.render_const::<2, 2>(&mut surface, offset_x, offset_y);
+            assert_eq!(surface.pixels, expected.pixels);
+
+            // mismatched tile size falls back to `render`
+            let mut surface = Surface::new(9, 7);
+            map.render_const::<3, 3>(&mut surface, offset_x, offset_y);
+            assert_eq!(surface.pixels, expected.pixels);
+        }
+    }
+
+    #[test]
+    fn alpha_cutout() {
+        let mut pixels = vec![Color::new(10, 0, 0, 200); 4];
+        pixels[0].a = 99;
+        pixels[1].a = 100;
+        let tileset = tileset_from(&pixels, 2, TilesetOptions::new(2, 2).with_alpha_cutout(100));
+        assert_eq!(tileset.tile_is_opaque(0), Some(false));
+
+        let map: Tilemap<_> = TilemapBuilder::new(1, 1, tileset)
+            .fill(Tile::new(0))
+            .build();
+        let mut surface = Surface::new(2, 2);
+        map.render(&mut surface, 0, 0);
+
+        assert_eq!(surface.at(0, 0), Color::default());
+        assert_eq!(surface.at(1, 0), Color::new(10, 0, 0, 255));
+        assert_eq!(surface.at(1, 1), Color::new(10, 0, 0, 255));
+    }
+
+    #[test]
+    fn render_tracked_bounds() {
+        let mut map = Tilemap::new(3, 3, tileset(2));
+        for tile in map.tiles_mut() {
+            tile.id = 99;
+        }
+        map.set_tile(1, 1, Tile::new(0));
+
+        let mut surface = Surface::new(20, 20);
+        assert_eq!(map.render_tracked(&mut surface, 3, 4), Some((5, 6, 2, 2)));
+        assert_eq!(map.render_tracked(&mut surface, -2, -2), Some((0, 0, 2, 2)));
+        assert_eq!(map.render_tracked(&mut surface, -3, -3), Some((0, 0, 1, 1)));
+        assert_eq!(map.render_tracked(&mut surface, 30, 0), None);
+
+        map.set_tile(2, 2, Tile::new(1));
+        assert_eq!(map.render_tracked(&mut surface, 0, 0), Some((2, 2, 4, 4)));
+
+        // only the drawn pixels count, not the whole tile
+        let key = Color::new(1, 1, 1, 255);
+        let mut pixels = vec![key; 9];
+        pixels[3] = shade(0);
+        let tileset = tileset_from(&pixels, 3, TilesetOptions::new(2, 2).with_key_color(key));
+
+        let mut map = Tilemap::new(1, 1, tileset);
+        map.set_tile(0, 0, Tile::new(0));
+        assert_eq!(map.render_tracked(&mut surface, 0, 0), Some((0, 1, 1, 1)));
+
+        map.set_tile(
+            0,
+            0,
+            Tile::new(0).with_blit_options(BlitOptions::FlipHorizontal),
+        );
+        assert_eq!(map.render_tracked(&mut surface, 0, 0), Some((1, 1, 1, 1)));
+    }
+
+    #[test]
+    fn scatter_weights() {
+        let mut map: Tilemap<_> = Tilemap::new(3, 2, tileset(3));
+        let mut rolls = [0, 69, 70, 89, 90, 99, 100, 175].into_iter();
+
+        // the rectangle gets clipped to the map, so only six rolls are used
+        map.scatter(0, 0, 4, 4, &[(5, 70), (6, 20), (7, 10)], || {
+            rolls.next().unwrap()
+        });
+
+        let ids: Vec<_> = map.tiles().iter().map(|tile| tile.id).collect();
+        assert_eq!(ids, [5, 5, 6, 6, 7, 7]);
+
+        // all-zero weights leave the map unchanged
+        map.scatter(0, 0, 3, 2, &[(1, 0)], || unreachable!());
+        assert_eq!(map.get_tile(0, 0).unwrap().id, 5);
+    }
+
+    /// Minimal self-describing format for writing serialized payloads by hand.
+    #[cfg(feature = "serde")]
+    enum Value {
+        U32(u32),
+        Str(&'static str),
+        Unit,
+        Seq(Vec<Value>),
+        Map(Vec<(&'static str, Value)>),
+    }
+
+    #[cfg(feature = "serde")]
+    impl<'de> serde::de::IntoDeserializer<'de, serde::de::value::Error> for Value {
+        type Deserializer = Self;
+
+        fn into_deserializer(self) -> Self {
+            self
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    impl<'de> serde::Deserializer<'de> for Value {
+        type Error = serde::de::value::Error;
+
+        fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: serde::de::Visitor<'de>,
+        {
+            use serde::de::value::{MapDeserializer, SeqDeserializer};
+
+            match self {
+                Value::U32(n) => visitor.visit_u32(n),
+                Value::Str(s) => visitor.visit_str(s),
+                Value::Unit => visitor.visit_unit(),
+                Value::Seq(items) => visitor.visit_seq(SeqDeserializer::new(items.into_iter())),
+                Value::Map(fields) => visitor.visit_map(MapDeserializer::new(fields.into_iter())),
+            }
+        }
+
+        fn deserialize_enum<V>(
+            self,
+            _name: &'static str,
+            _variants: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, Self::Error>
+        where
+            V: serde::de::Visitor<'de>,
+        {
+            use serde::de::IntoDeserializer;
+
+            match self {
+                Value::Str(s) => visitor.visit_enum(s.into_deserializer()),
+                value => value.deserialize_any(visitor),
+            }
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map struct identifier ignored_any
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_version_0_map() {
+        use serde::Deserialize;
+        use Value::*;
+
+        let pair = |a, b| Seq(vec![U32(a), U32(b)]);
+
+        // a save from before the version, extra tilesets, bits, layout
+        // and all the optional tile fields were added
+        let payload = Map(vec![
+            (
+                "tileset",
+                Map(vec![
+                    ("data", Seq((0..16).map(|_| U32(255)).collect())),
+                    ("width", U32(2)),
+                    ("height", U32(2)),
+                    ("tile_counts", pair(1, 1)),
+                    (
+                        "opts",
+                        Map(vec![
+                            ("tile_size", pair(2, 2)),
+                            ("offset", pair(0, 0)),
+                            ("spacing", pair(0, 0)),
+                            ("key_color", Unit),
+                        ]),
+                    ),
+                ]),
+            ),
+            (
+                "tiles",
+                Seq(vec![Map(vec![
+                    ("id", U32(0)),
+                    (
+                        "color",
+                        Map(vec![
+                            ("r", U32(1)),
+                            ("g", U32(2)),
+                            ("b", U32(3)),
+                            ("a", U32(4)),
+                        ]),
+                    ),
+                    ("opts", Str("FlipVertical")),
+                    ("data", Unit),
+                ])]),
+            ),
+            ("width", U32(1)),
+            ("height", U32(1)),
+        ]);
+
+        let serialized = SerializedTilemap::<Vec<u8>>::deserialize(payload).unwrap();
+        assert_eq!(serialized.version, 0);
+
+        let map = Tilemap::from_serialized(serialized).unwrap();
+        assert_eq!((map.width(), map.height()), (1, 1));
+        assert_eq!(map.tilesets().count(), 1);
+        assert_eq!(map.layout(), MapLayout::Orthogonal);
+        assert!(!map.get_bit(0, 0));
+
+        let tile = map.get_tile(0, 0).unwrap();
+        assert_eq!(tile.color, Color::new(1, 2, 3, 4));
+        assert_eq!(tile.opts, BlitOptions::FlipVertical);
+        assert!(tile.visible);
+        assert_eq!(tile.rotation, Rotation::None);
+        assert_eq!((tile.offset, tile.z, tile.anim_phase), ((0, 0), 0, 0));
+        assert_eq!(tile.blend, BlendMode::Normal);
+        assert_eq!(tile.key_color_override, None);
+
+        let tileset = map.tileset();
+        assert_eq!(tileset.options().anchor, crate::TileAnchor::default());
+        assert!(!tileset.options().wrap_ids);
+        assert!(tileset.alpha_mask().is_none());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn from_serialized_rejects_newer_versions() {
+        let serialized = SerializedTilemap::<Vec<u8>> {
+            version: TILEMAP_FORMAT_VERSION + 1,
+            tileset: tileset(1),
+            extra_tilesets: Vec::new(),
+            tiles: vec![Tile::new(0)],
+            width: 1,
+            height: 1,
+            bits: None,
+            layout: MapLayout::Orthogonal,
+        };
+
+        assert!(Tilemap::from_serialized(serialized.clone()).is_none());
+        assert!(Tilemap::from_serialized(SerializedTilemap {
+            version: TILEMAP_FORMAT_VERSION,
+            ..serialized
+        })
+        .is_some());
+    }
+
+    #[test]
+    fn render_over_blends_overlay_with_own_tileset() {
+        #[derive(Clone, Default)]
+        struct Hud;
+
+        let mut world: Tilemap<_> = Tilemap::new(3, 1, tileset(3));
+        for x in 0..3 {
+            world.set_tile(x, 0, Tile::new(0));
+        }
+
+        let blue = Color::new(0, 0, 255, 255);
+        let hud_tileset = tileset_from(&[blue; 4], 2, TilesetOptions::new(1, 1));
+        let mut hud: Tilemap<_, Hud> = Tilemap::new(1, 1, hud_tileset);
+        hud.set_tile(
+            0,
+            0,
+            Tile::new(3).with_color(Color::new(255, 255, 255, 128)),
+        );
+
+        let mut surface = Surface::new(6, 2);
+        world.render_over(&mut surface, 0, 0, &hud, (3, 1));
+
+        assert_eq!(surface.at(0, 0), shade(0));
+        assert_eq!(surface.at(4, 1), shade(0));
+
+        let blended = surface.at(3, 1);
+        assert!(
+            blended.b > 100 && blended.r > 0 && blended.a == 255,
+            "{blended:?}"
+        );
+    }
+
+    #[test]
+    fn render_sorted_draws_higher_z_last() {
+        /// Buffer with a single pixel that logs where it's drawn to.
+        struct Log(Vec<(u32, u32)>, Color);
+
+        impl Buffer<Color> for Log {
+            fn width(&self) -> u32 {
+                4
+            }
+
+            fn height(&self) -> u32 {
+                2
+            }
+
+            fn get(&self, _: u32, _: u32) -> &Color {
+                &self.1
+            }
+        }
+
+        impl BufferMut<Color> for Log {
+            fn get_mut(&mut self, x: u32, y: u32) -> &mut Color {
+                self.0.push((x, y));
+                &mut self.1
+            }
+        }
+
+        let mut map: Tilemap<_> = Tilemap::new(2, 1, tileset(3));
+        map.set_tile(0, 0, Tile::new(0).with_z(1));
+        map.set_tile(1, 0, Tile::new(1));
+
+        let mut log = Log(Vec::new(), Color::default());
+        map.render_sorted(&mut log, 0, 0);
+        assert_eq!((log.0[0], log.0[4]), ((2, 0), (0, 0)));
+        assert_eq!(log.1, shade(0));
+
+        // equal z keeps the row-major order
+        map.get_mut_tile(0, 0).unwrap().set_z(0);
+
+        let mut log = Log(Vec::new(), Color::default());
+        map.render_sorted(&mut log, 0, 0);
+        assert_eq!(log.0[0], (0, 0));
+        assert_eq!(log.1, shade(1));
+    }
+
+    #[test]
+    fn extract_copies_tiles_and_bits() {
+        let mut map: Tilemap<_> = Tilemap::new(4, 3, tileset(3));
+        for (i, tile) in map.tiles_mut().iter_mut().enumerate() {
+            tile.id = i as TileId;
+        }
+        map.set_bit(2, 2, true);
+
+        let region = map.extract(1, 1, 2, 2);
+        assert_eq!((region.width(), region.height()), (2, 2));
+        let ids: Vec<_> = region.tiles().iter().map(|tile| tile.id).collect();
+        assert_eq!(ids, [5, 6, 9, 10]);
+        assert!(region.get_bit(1, 1));
+        assert!(!region.get_bit(0, 0));
+
+        // the rectangle gets clipped to the map
+        let corner = map.extract(3, 2, 5, 5);
+        assert_eq!((corner.width(), corner.height()), (1, 1));
+        assert_eq!(corner.get_tile(0, 0).unwrap().id, 11);
+
+        assert!(map.extract(9, 9, 5, 5).tiles().is_empty());
+    }
+
+    #[test]
+    fn render_with_light() {
+        let pixels: Vec<_> = (0..25)
+            .map(|i| Color::new(i * 10, 200 - i * 5, 77, 255 - i))
+            .collect();
+        let tileset = tileset_from(&pixels, 5, TilesetOptions::new(1, 1));
+
+        let mut map: Tilemap<_> = Tilemap::new(4, 2, tileset);
+        for (i, tile) in map.tiles_mut().iter_mut().enumerate() {
+            *tile = Tile::new(i as TileId * 3).with_color(Color::new(200, 100 + i as u8, 50, 240));
+        }
+
+        // white light changes nothing
+        let white = Color::new(255, 255, 255, 255);
+        let mut plain = Surface::new(4, 2);
+        let mut lit = Surface::new(4, 2);
+        map.render(&mut plain, 0, 0);
+        map.render_with_light(&mut lit, 0, 0, |_, _| white);
+        assert_eq!(plain.pixels, lit.pixels);
+
+        for tile in map.tiles_mut() {
+            *tile = Tile::new(6);
+        }
+
+        let gray = Color::new(100, 100, 100, 255);
+        map.render_with_light(&mut lit, 0, 0, |x, _| if x >= 2 { gray } else { white });
+        assert!(lit.at(3, 1).r < lit.at(0, 1).r);
+        assert!(lit.at(3, 0).g < lit.at(1, 0).g);
+    }
+
+    #[test]
+    fn map_tiles_in_place() {
+        let mut map: Tilemap<_, u8> = Tilemap::new(3, 2, tileset(3));
+        let tiles = map.tiles().as_ptr();
+
+        map.map_tiles(|x, y, tile| {
+            let n = if (x + y) % 2 == 0 { 1 } else { 2 };
+
+            tile.with_color(Color::new(n, n, n, n))
+                .with_user_data(x as u8 * 10 + y as u8)
+        });
+
+        // no reallocation
+        assert_eq!(tiles, map.tiles().as_ptr());
+
+        assert_eq!(map.get_tile(0, 0).unwrap().color.r, 1);
+        assert_eq!(map.get_tile(1, 0).unwrap().color.r, 2);
+        assert_eq!(map.get_tile(1, 1).unwrap().color.r, 1);
+        assert_eq!(map.get_tile(2, 1).unwrap().data, 21);
+    }
+
+    #[test]
+    fn used_ids_sorted_and_distinct() {
+        let mut map: Tilemap<_> = Tilemap::new(3, 2, tileset(3));
+        for (tile, id) in map.tiles_mut().iter_mut().zip([0, 5, 5, 2, EMPTY_TILE, 2]) {
+            tile.id = id;
+        }
+
+        assert_eq!(map.used_ids(), [0, 2, 5]);
+    }
+
+    #[test]
+    fn render_grid_edges() {
+        let map: Tilemap<_> = Tilemap::new(2, 2, tileset(3));
+        let grid = Color::new(1, 2, 3, 4);
+
+        // the map covers pixels 1..5, with tile edges at 1 and 3 and the right edge at 4
+        let mut surface = Surface::new(8, 8);
+        map.render_grid(&mut surface, 1, 1, grid);
+
+        for (x, y) in [(1, 1), (3, 2), (4, 4), (2, 3), (1, 4), (4, 1)] {
+            assert_eq!(surface.at(x, y), grid, "{x}, {y}");
+        }
+        for (x, y) in [(2, 2), (0, 0), (5, 5), (2, 5), (5, 2)] {
+            assert_ne!(surface.at(x, y), grid, "{x}, {y}");
+        }
+
+        let mut surface = Surface::new(3, 3);
+        map.render_grid(&mut surface, -1, -1, grid);
+
+        assert_eq!(surface.at(0, 0), Color::default());
+        assert_eq!(surface.at(1, 0), grid);
+        assert_eq!(surface.at(0, 1), grid);
+        assert_eq!(surface.at(2, 2), grid);
+    }
+
+    #[test]
+    fn draw_line_clips_to_map() {
+        let mut map: Tilemap<_> = Tilemap::new(6, 5, tileset(3));
+        map.draw_line(0, 0, 5, 2, Tile::new(2));
+        map.draw_line(5, 4, 9, 4, Tile::new(2));
+
+        assert_eq!(map.get_tile(0, 0).unwrap().id, 2);
+        assert_eq!(map.get_tile(5, 2).unwrap().id, 2);
+        assert_eq!(map.count_id(2), 7);
+    }
+
+    #[test]
+    fn premultiplied_tilesets_blend_without_halos() {
+        let gray = Color::new(200, 200, 200, 255);
+        let background = tileset_from(&[gray; 4], 2, TilesetOptions::new(1, 1));
+        let mut background: Tilemap<_> = Tilemap::new(1, 1, background);
+        background.set_tile(0, 0, Tile::new(0));
+
+        // (200, 200, 200) at half alpha, premultiplied
+        let half = Color::new(100, 100, 100, 128);
+
+        let blended = [false, true].map(|premultiplied| {
+            let opts = TilesetOptions::new(1, 1).with_premultiplied(premultiplied);
+            let overlay = tileset_from(&[half; 4], 2, opts);
+            let mut overlay: Tilemap<_> = Tilemap::new(1, 1, overlay);
+            overlay.set_tile(0, 0, Tile::new(0));
+
+            let mut surface = Surface::new(1, 1);
+            background.render_over(&mut surface, 0, 0, &overlay, (0, 0));
+            surface.at(0, 0)
+        });
+
+        assert!(blended[0].r < 190, "{blended:?}");
+        assert!(blended[1].r.abs_diff(200) <= 2, "{blended:?}");
+
+        let pixels = vec![
+            Color::default(),
+            Color::new(255, 255, 255, 255),
+            Color::new(64, 0, 0, 64),
+            Color::default(),
+        ];
+        let opts = TilesetOptions::new(1, 1).with_premultiplied(true);
+        let tileset = tileset_from(&pixels, 2, opts);
+
+        let mut surface = Surface::new(1, 1);
+        tileset.render_tile(&mut surface, 2, 0, 0, BlitOptions::None);
+        assert_eq!(surface.at(0, 0), Color::new(255, 0, 0, 64));
+    }
+
+    #[test]
+    fn select_mut_rectangle() {
+        let mut map: Tilemap<_> = Tilemap::new(4, 3, tileset(3));
+        for tile in map.tiles_mut() {
+            tile.id = 0;
+        }
+
+        let positions: Vec<_> = map
+            .select_mut(1, 1, 2, 2)
+            .map(|(x, y, tile)| {
+                tile.id += 1;
+                (x, y)
+            })
+            .collect();
+        assert_eq!(positions, [(1, 1), (2, 1), (1, 2), (2, 2)]);
+
+        let ids: Vec<_> = map.tiles().iter().map(|tile| tile.id).collect();
+        assert_eq!(ids, [0, 0, 0, 0, 0, 1, 1, 0, 0, 1, 1, 0]);
+
+        assert_eq!(map.select_mut(3, 2, 9, 9).count(), 1);
+        assert_eq!(map.select_mut(7, 0, 2, 2).count(), 0);
+    }
+
+    #[test]
+    fn tile_visible_clipping() {
+        let map: Tilemap<_> = Tilemap::new(4, 4, tileset(3));
+
+        assert!(map.tile_visible(1, 1, 0, 0, 10, 10));
+        assert!(!map.tile_visible(1, 1, 20, 0, 10, 10));
+        assert!(!map.tile_visible(0, 0, -2, 0, 10, 10));
+        assert!(map.tile_visible(0, 0, -1, -1, 10, 10));
+        assert!(map.tile_visible(1, 0, 7, 0, 10, 10));
+        assert!(!map.tile_visible(1, 0, 8, 0, 10, 10));
+        assert!(!map.tile_visible(5, 0, 0, 0, 100, 100));
+    }
+
+    #[test]
+    fn render_culls_offscreen_tiles() {
+        let map: Tilemap<_> = Tilemap::new(512, 512, tileset(3));
+        assert_eq!(map.visible_range(-3, 0, 4, 2), (1..4, 0..1));
+        assert_eq!(map.visible_range(0, 0, 320, 180), (0..160, 0..90));
+        assert!(map.visible_range(5000, 0, 320, 180).0.is_empty());
+        assert!(map.visible_range(-5000, 0, 320, 180).0.is_empty());
+
+        /// 4x4 buffer that counts how many pixels are drawn.
+        struct Counter(usize, Color);
+
+        impl Buffer<Color> for Counter {
+            fn width(&self) -> u32 {
+                4
+            }
+
+            fn height(&self) -> u32 {
+                4
+            }
+
+            fn get(&self, _: u32, _: u32) -> &Color {
+                &self.1
+            }
+        }
+
+        impl BufferMut<Color> for Counter {
+            fn get_mut(&mut self, _: u32, _: u32) -> &mut Color {
+                self.0 += 1;
+                &mut self.1
+            }
+        }
+
+        // only the 4 tiles on the buffer are drawn, not the whole map
+        let mut map: Tilemap<_> = Tilemap::new(512, 512, tileset(3));
+        map.fill_rect(0, 0, 512, 512, Tile::new(1));
+
+        let mut counter = Counter(0, Color::default());
+        map.render(&mut counter, -100, -100);
+        assert_eq!(counter.0, 16);
+    }
+
+    #[test]
+    fn culled_render_matches_full_render() {
+        let mut map: Tilemap<_> = Tilemap::new(3, 3, tileset(3));
+        map.fill_rect(0, 0, 3, 3, Tile::new(1));
+
+        let mut surface = Surface::new(5, 5);
+        map.render(&mut surface, -1, 1);
+
+        for y in 0..5 {
+            for x in 0..5 {
+                let expected = if y >= 1 { shade(1) } else { Color::default() };
+                assert_eq!(surface.at(x, y), expected, "{x}, {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn render_region_draws_only_the_rectangle() {
+        let mut map: Tilemap<_> = Tilemap::new(4, 4, tileset(3));
+        map.fill_rect(0, 0, 4, 4, Tile::new(2));
+
+        // tiles (1, 2) to (2, 3), the rectangle is clipped to the map
+        let mut surface = Surface::new(10, 10);
+        map.render_region(&mut surface, 1, 1, 1, 2, 2, 5);
+
+        for y in 0..10 {
+            for x in 0..10 {
+                let inside = (3..7).contains(&x) && (5..9).contains(&y);
+                assert_eq!(surface.at(x, y) == shade(2), inside, "{x}, {y}");
+            }
+        }
+
+        let mut surface = Surface::new(10, 10);
+        map.render_region(&mut surface, 0, 0, 9, 9, 5, 5);
+        assert!(surface.pixels.iter().all(|pixel| pixel.a == 0));
+    }
+
+    #[test]
+    fn render_dirty_keeps_offscreen_flags() {
+        let mut map: Tilemap<_> = Tilemap::new(3, 2, tileset(3));
+        map.fill_rect(0, 0, 3, 2, Tile::new(0));
+        map.set_change_tracking(true);
+
+        // only the left column fits on the buffer
+        let mut surface = Surface::new(2, 4);
+        map.render_dirty(&mut surface, 0, 0);
+        assert_eq!(surface.at(0, 0), shade(0));
+        assert!(!map.is_dirty(0, 0) && !map.is_dirty(0, 1));
+        assert!(map.is_dirty(1, 0) && map.is_dirty(2, 1));
+
+        map.set_tile(0, 1, Tile::new(2));
+        map.set_tile(2, 0, Tile::new(1));
+
+        // the tiles that were offscreen get drawn once they scroll into view
+        let mut surface = Surface::new(2, 4);
+        map.render_dirty(&mut surface, -4, 0);
+        assert_eq!(surface.at(0, 0), shade(1));
+        assert_eq!(surface.at(0, 2), shade(0));
+        assert!(!map.is_dirty(2, 0) && !map.is_dirty(2, 1));
+        assert!(map.is_dirty(0, 1) && map.is_dirty(1, 0));
+    }
+
+    #[test]
+    fn change_tracking() {
+        let mut map: Tilemap<_> = Tilemap::new(3, 2, tileset(3));
+        assert!(!map.is_tracking_changes());
+        map.set_tile(0, 0, Tile::new(0));
+        assert!(!map.is_dirty(0, 0));
+
+        // everything starts out dirty
+        map.set_change_tracking(true);
+        assert!(map.is_dirty(2, 1));
+
+        map.clear_dirty();
+        map.set_tile(1, 1, Tile::new(2));
+        map.fill_rect(2, 0, 5, 1, Tile::new(1));
+        assert!(map.is_dirty(1, 1) && map.is_dirty(2, 0));
+        assert!(!map.is_dirty(0, 1) && !map.is_dirty(1, 0));
+
+        let mut surface = Surface::new(6, 4);
+        map.render_dirty(&mut surface, 0, 0);
+        assert_eq!(surface.at(2, 2), shade(2));
+        assert_eq!(surface.at(4, 0), shade(1));
+        assert_eq!(surface.at(0, 0), Color::default());
+        assert!(!map.is_dirty(1, 1));
+
+        map.insert_row(0, Tile::new(0));
+        assert!(map.is_dirty(0, 2));
+
+        map.clear_dirty();
+        map.get_two_mut((0, 0), (1, 0));
+        assert!(map.is_dirty(0, 0) && map.is_dirty(1, 0));
+
+        map.set_change_tracking(false);
+        assert!(!map.is_dirty(0, 0));
+    }
+
+    #[test]
+    fn extra_tilesets() {
+        let mut map: Tilemap<_> = Tilemap::new(3, 1, tileset(2));
+        let bright = Color::new(200, 0, 0, 255);
+        let other = tileset_from(&[bright; 4], 2, TilesetOptions::new(2, 2));
+
+        assert!(!map.add_tileset(0, other.clone()));
+        assert!(map.add_tileset(100, other.clone()));
+        assert!(!map.add_tileset(100, other));
+
+        let first_ids: Vec<_> = map.tilesets().map(|(first_id, _)| first_id).collect();
+        assert_eq!(first_ids, [0, 100]);
+        assert_eq!(map.tileset_for(5).1, 5);
+        assert_eq!(map.tileset_for(100).1, 0);
+
+        map.set_tile(0, 0, Tile::new(1));
+        map.set_tile(1, 0, Tile::new(100));
+        map.set_tile(2, 0, Tile::new(101));
+        assert_eq!(map.validate(), Err(vec![(2, 0, 101)]));
+
+        let mut surface = Surface::new(6, 2);
+        map.render(&mut surface, 0, 0);
+        assert_eq!(surface.at(0, 0), shade(1));
+        assert_eq!(surface.at(2, 0), bright);
+        assert_eq!(surface.at(4, 0).a, 0);
+
+        assert_eq!(
+            map.render_tracked(&mut Surface::new(6, 2), 0, 0),
+            Some((0, 0, 4, 2))
+        );
+
+        let mut with_fallback = Surface::new(6, 2);
+        map.render_with_fallback(&mut with_fallback, 0, 0, 100);
+        assert_eq!(with_fallback.at(4, 0), bright);
+
+        let mut with_const = Surface::new(6, 2);
+        map.render_const::<2, 2>(&mut with_const, 0, 0);
+        assert_eq!(with_const.pixels, surface.pixels);
+
+        assert_eq!(
+            map.clone()
+                .map_tileset(|tileset| tileset)
+                .tilesets()
+                .count(),
+            2
+        );
+
+        let mut region = map.extract(1, 0, 1, 1);
+        assert!(region.remove_tileset(100).is_some());
+        assert!(region.remove_tileset(100).is_none());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_without_default_containers() {
+        /// Container that can't be defaulted.
+        #[derive(serde::Deserialize)]
+        struct Bytes(#[allow(dead_code)] Vec<u8>);
+
+        fn assert_deserialize<T: serde::de::DeserializeOwned>() {}
+
+        assert_deserialize::<SerializedTilemap<Bytes>>();
+        assert_deserialize::<Tilemap<Bytes>>();
+    }
+
+    #[test]
+    fn empty_tiles_are_never_drawn() {
+        let mut map: Tilemap<_> = Tilemap::new(2, 1, tileset(2));
+        map.set_tile(0, 0, Tile::empty());
+        map.set_tile(1, 0, Tile::new(1));
+
+        assert!(map.get_tile(0, 0).unwrap().is_empty());
+        assert!(!Tile::<()>::default().is_empty());
+        assert_eq!(map.validate(), Ok(()));
+        assert_eq!(map.used_ids(), [1]);
+
+        // not even as the fallback
+        let mut surface = Surface::new(4, 2);
+        map.render_with_fallback(&mut surface, 0, 0, 0);
+        assert_eq!(surface.at(0, 0).a, 0);
+        assert_eq!(surface.at(2, 0), shade(1));
+
+        assert_eq!(
+            map.render_tracked(&mut Surface::new(4, 2), 0, 0),
+            Some((2, 0, 2, 2))
+        );
+
+        let mut surface = Surface::new(4, 2);
+        map.render_const::<2, 2>(&mut surface, 0, 0);
+        assert_eq!(surface.at(0, 0).a, 0);
+
+        assert!(map.tileset().get_tile_pos(EMPTY_TILE).is_none());
+
+        let opts = TilesetOptions::new(2, 2).with_wrap_ids(true);
+        let wrapping = tileset_from(&[shade(0); 4], 2, opts);
+        assert!(wrapping.get_tile_pos(EMPTY_TILE).is_none());
+    }
+
+    #[test]
+    fn tile_at_pixel_round_trip() {
+        let map: Tilemap<_> = Tilemap::new(3, 2, tileset(1));
+
+        assert_eq!(map.tile_at_pixel(0, 0, 0, 0), Some((0, 0)));
+        assert_eq!(map.tile_at_pixel(5, 3, 0, 0), Some((2, 1)));
+        assert_eq!(map.tile_at_pixel(6, 0, 0, 0), None);
+        assert_eq!(map.tile_at_pixel(-1, 0, 0, 0), None);
+        assert_eq!(map.tile_at_pixel(0, 0, -3, -1), Some((1, 0)));
+        assert_eq!(map.tile_at_pixel(0, 0, 1, 0), None);
+
+        assert_eq!(map.tile_to_pixel(2, 1, -3, 5), Some((1, 7)));
+        assert_eq!(map.tile_to_pixel(3, 1, 0, 0), None);
+
+        for x in -10..10 {
+            for y in -10..10 {
+                if let Some((tx, ty)) = map.tile_at_pixel(x, y, -3, 1) {
+                    let (left, top) = map.tile_to_pixel(tx, ty, -3, 1).unwrap();
+                    assert!((left..left + 2).contains(&x) && (top..top + 2).contains(&y));
+                }
+            }
+        }
+
+        let hex =
+            crate::HexLayout::new(crate::HexOrientation::PointyTop, crate::HexStagger::Odd, 1);
+        let map = map.with_layout(MapLayout::Hex(hex));
+        assert_eq!(map.tile_at_pixel(1, 1, 0, 0), map.hex_tile_at(1, 1, 0, 0));
+    }
+
+    #[test]
+    fn render_scaled() {
+        let mut map: Tilemap<_> = Tilemap::new(2, 1, tileset(2));
+        map.set_tile_index(0, Tile::new(0));
+        map.set_tile_index(
+            1,
+            Tile::new(1).with_blit_options(BlitOptions::FlipHorizontal),
+        );
+
+        let mut surface = Surface::new(9, 6);
+        map.render_scaled(&mut surface, -1, 1, 2);
+        assert_eq!(surface.at(0, 0), Color::default());
+        assert_eq!(surface.at(0, 1), shade(0));
+        assert_eq!(surface.at(2, 4), shade(0));
+        assert_eq!(surface.at(3, 1), shade(1));
+        assert_eq!(surface.at(6, 4), shade(1));
+        assert_eq!(surface.at(7, 1), Color::default());
+        assert_eq!(surface.at(0, 5), Color::default());
+
+        let mut expected = Surface::new(9, 6);
+        map.render(&mut expected, 2, 1);
+        let mut surface = Surface::new(9, 6);
+        map.render_scaled(&mut surface, 2, 1, 1);
+        assert_eq!(surface.pixels, expected.pixels);
+    }
+
+    #[test]
+    fn render_wrapped_repeats_the_map() {
+        let mut map: Tilemap<_> = Tilemap::new(3, 2, tileset(6));
+        for index in 0..6 {
+            map.set_tile_index(index, Tile::new(index as TileId));
+        }
+
+        for (x, y) in [(0, 0), (-3, 1), (5, -7), (-100, 33)] {
+            let mut surface = Surface::new(11, 9);
+            map.render_wrapped(&mut surface, x, y);
+
+            let mut expected = Surface::new(11, 9);
+            for repeat_y in -20..20 {
+                for repeat_x in -30..30 {
+                    map.render(&mut expected, x + repeat_x * 6, y + repeat_y * 4);
+                }
+            }
+
+            assert_eq!(surface.pixels, expected.pixels, "offset ({x}, {y})");
+        }
+
+        assert_eq!(map.get_tile_wrapped(-1, -1).unwrap().id, 5);
+        assert_eq!(map.get_tile_wrapped(4, 2).unwrap().id, 1);
+
+        let empty: Tilemap<_> = Tilemap::new(0, 2, tileset(1));
+        assert!(empty.get_tile_wrapped(0, 0).is_none());
+    }
+
+    #[test]
+    fn render_with_custom_pixels() {
+        let mut map: Tilemap<_, u8> = Tilemap::new(2, 1, tileset(2));
+        map.set_tile_index(0, Tile::new(0).with_user_data(3));
+        map.set_tile_index(1, Tile::new(1).with_user_data(1));
+
+        let mut surface = Surface::new(4, 2);
+        for pixel in &mut surface.pixels {
+            pixel.r = 5;
+        }
+
+        map.render_with(
+            &mut surface,
+            0,
+            0,
+            |dest: &mut Color, src: &Color, tile: &Tile<u8>| dest.r += src.r * tile.data,
+        );
+        assert_eq!(surface.at(0, 0).r, 35);
+        assert_eq!(surface.at(3, 1).r, 25);
+    }
+
+    #[test]
+    fn blend_modes() {
+        let mut map: Tilemap<_> = Tilemap::new(4, 1, tileset(1));
+        map.set_tile_index(0, Tile::new(0));
+        map.set_tile_index(1, Tile::new(0).with_blend(BlendMode::Additive));
+        map.set_tile_index(2, Tile::new(0).with_blend(BlendMode::Multiply));
+        map.set_tile_index(3, Tile::new(0).with_blend(BlendMode::Screen));
+
+        let grey = Color::new(100, 100, 100, 255);
+        let mut surface = Surface::filled(8, 2, grey);
+        map.render(&mut surface, 0, 0);
+        assert_eq!(surface.at(0, 0), shade(0));
+        assert!(surface.at(2, 0).r > 100 && surface.at(2, 0).g == 100);
+        assert!(surface.at(4, 0).r < 10 && surface.at(4, 0).g == 0);
+        assert!(surface.at(6, 0).r > 100 && surface.at(6, 0).g == 100);
+
+        // lights and overlays keep the tile's blend mode
+        let mut lit = Surface::filled(8, 2, grey);
+        map.render_with_light(&mut lit, 0, 0, |_, _| Color::new(255, 255, 255, 255));
+        assert_eq!(lit.pixels, surface.pixels);
+
+        let base: Tilemap<_> = Tilemap::new(0, 0, tileset(1));
+        let mut overlaid = Surface::filled(8, 2, grey);
+        base.render_over(&mut overlaid, 0, 0, &map, (0, 0));
+        assert_eq!(overlaid.pixels, surface.pixels);
+
+        assert_eq!(
+            BlendMode::Additive.blend(Color::new(0, 0, 0, 0), Color::new(50, 60, 70, 255)),
+            Color::new(50, 60, 70, 255)
+        );
+        assert_eq!(
+            BlendMode::Screen.blend(Color::new(9, 9, 9, 255), Color::new(250, 250, 250, 0)),
+            Color::new(9, 9, 9, 255)
+        );
+    }
+
+    #[test]
+    fn alpha_blend_mode() {
+        let translucent = Color::new(255, 255, 255, 128);
+        let mut map: Tilemap<_> = Tilemap::new(2, 1, tileset(1));
+        map.set_tile_index(0, Tile::new(0).with_color(translucent));
+        map.set_tile_index(
+            1,
+            Tile::new(0)
+                .with_color(translucent)
+                .with_blend(BlendMode::Alpha),
+        );
+
+        let mut surface = Surface::new(4, 2);
+        surface.pixels.fill(Color::new(0, 200, 0, 255));
+        map.render(&mut surface, 0, 0);
+        assert_eq!(surface.at(0, 0).a, 128);
+        assert_eq!(surface.at(0, 0).g, 0);
+        assert_eq!(surface.at(2, 0).a, 255);
+        assert!(surface.at(2, 0).g > 100 && surface.at(2, 0).g < 200);
+    }
+
+    #[test]
+    fn render_with_options() {
+        let mut map: Tilemap<_> = Tilemap::new(2, 1, tileset(2));
+        map.set_tile_index(0, Tile::new(0));
+        map.set_tile_index(1, Tile::new(1));
+
+        let mut expected = Surface::new(4, 2);
+        map.render(&mut expected, 0, 0);
+        let mut surface = Surface::new(4, 2);
+        map.render_with_options(&mut surface, 0, 0, &RenderOptions::default());
+        assert_eq!(surface.pixels, expected.pixels);
+
+        let mut surface = Surface::new(4, 2);
+        let tint = RenderOptions::new().with_tint(Color::new(0, 255, 255, 255));
+        map.render_with_options(&mut surface, 0, 0, &tint);
+        assert_eq!(surface.at(0, 0), Color::new(0, 0, 0, 255));
+
+        let mut surface = Surface::new(4, 2);
+        surface.pixels.fill(Color::new(0, 0, 200, 255));
+        let faded = RenderOptions::new().with_opacity(128);
+        map.render_with_options(&mut surface, 0, 0, &faded);
+        assert_eq!(surface.at(0, 0).a, 255);
+        assert!(surface.at(0, 0).b > 50 && surface.at(0, 0).b < 200);
+
+        // the tiles themselves are left alone
+        assert_eq!(
+            map.get_tile(0, 0).unwrap().color,
+            Color::new(255, 255, 255, 255)
+        );
+    }
+
+    #[test]
+    fn tile_meta_at() {
+        use alloc::collections::BTreeMap;
+
+        let walls = tileset(3).with_meta(BTreeMap::from([(1, "wall")]));
+        let extra = tileset(2).with_meta(BTreeMap::from([(1, "water")]));
+
+        let mut map: Tilemap<_, (), &str> = Tilemap::new(3, 1, walls);
+        assert!(map.add_tileset(10, extra));
+        map.set_tile(0, 0, Tile::new(1));
+        map.set_tile(1, 0, Tile::new(11));
+        map.set_tile(2, 0, Tile::new(0));
+
+        assert_eq!(map.tile_meta_at(0, 0), Some(&"wall"));
+        assert_eq!(map.tile_meta_at(1, 0), Some(&"water"));
+        assert_eq!(map.tile_meta_at(2, 0), None);
+        assert_eq!(map.tile_meta_at(5, 0), None);
+
+        let mut surface = Surface::new(6, 2);
+        map.render(&mut surface, 0, 0);
+        assert_eq!(surface.at(0, 0), shade(1));
     }
 }
@@ -1,14 +1,27 @@
-use crate::{Buffer, BufferMut, Color};
+use crate::{
+    cell_span, clamp_span, multiply, Buffer, BufferMut, Color, Tile, TileAnimation, TileTerrain,
+};
 
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::iter;
 use core::mem::size_of;
-use core::ops::{Index, IndexMut};
-use rgb::AsPixels;
-use simple_blit::{blit_with, BlitOptions};
+use core::ops::{Index, IndexMut, Range};
+use rgb::{AsPixels, ComponentBytes};
+use simple_blit::BlitOptions;
 
 /// Id of a tile in a tileset.
 /// Tiles in a tileset are counted left-to-right then top-to-bottom.
 pub type TileId = u32;
 
+/// Tile id meaning "nothing here".
+///
+/// Map tiles with this id are never drawn, not even as a fallback tile. See [`Tile::empty`].
+pub const EMPTY_TILE: TileId = TileId::MAX;
+
 /// Options used when creating a tileset.
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -18,9 +31,31 @@ pub struct TilesetOptions {
     /// Offset (x, y) - first tile's top left corner.
     pub offset: (u32, u32),
     /// Spacing (x, y) - distance between tiles.
+    ///
+    /// Spacing only goes between neighboring tiles, the last tile in a row/column isn't followed by it.
     pub spacing: (u32, u32),
     /// Key color aka mask/background color. Gets ignored when rendering the tile.
     pub key_color: Option<Color>,
+    /// Maximum per-channel difference for a pixel to still be treated as the key color.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub key_color_tolerance: u8,
+    /// Alpha threshold. If set, pixels with alpha below it are ignored when rendering
+    /// and the rest are drawn fully opaque. Replaces the key color check.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub alpha_cutout: Option<u8>,
+    /// Whether out of range tile ids wrap around (modulo the tile count) instead of being invalid.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub wrap_ids: bool,
+    /// Whether the tileset's colors are already multiplied by their alpha.
+    ///
+    /// Such colors are converted back to straight alpha before rendering,
+    /// so that blending them doesn't darken semi-transparent edges.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub premultiplied: bool,
+    /// Where tiles are placed in a map cell when they're larger (or smaller) than the map's cells,
+    /// see [`Tilemap::add_tileset`](crate::Tilemap::add_tileset).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub anchor: TileAnchor,
 }
 
 impl TilesetOptions {
@@ -32,6 +67,11 @@ impl TilesetOptions {
             offset: (0, 0),
             spacing: (0, 0),
             key_color: None,
+            key_color_tolerance: 0,
+            alpha_cutout: None,
+            wrap_ids: false,
+            premultiplied: false,
+            anchor: TileAnchor::TopLeft,
         }
     }
 
@@ -42,7 +82,7 @@ impl TilesetOptions {
         self
     }
 
-    /// Specify spacing.
+    /// Specify spacing (distance between neighboring tiles).
     #[inline]
     pub const fn with_margin(mut self, margin_x: u32, margin_y: u32) -> Self {
         self.spacing = (margin_x, margin_y);
@@ -55,66 +95,229 @@ impl TilesetOptions {
         self.key_color = Some(key_color);
         self
     }
+
+    /// Specify key color tolerance.
+    #[inline]
+    pub const fn with_key_color_tolerance(mut self, tolerance: u8) -> Self {
+        self.key_color_tolerance = tolerance;
+        self
+    }
+
+    /// Specify alpha cutout threshold.
+    #[inline]
+    pub const fn with_alpha_cutout(mut self, threshold: u8) -> Self {
+        self.alpha_cutout = Some(threshold);
+        self
+    }
+
+    /// Specify whether out of range tile ids wrap around.
+    #[inline]
+    pub const fn with_wrap_ids(mut self, wrap_ids: bool) -> Self {
+        self.wrap_ids = wrap_ids;
+        self
+    }
+
+    /// Specify whether the tileset's colors are premultiplied by alpha.
+    #[inline]
+    pub const fn with_premultiplied(mut self, premultiplied: bool) -> Self {
+        self.premultiplied = premultiplied;
+        self
+    }
+
+    /// Specify where tiles are placed in map cells of a different size.
+    #[inline]
+    pub const fn with_anchor(mut self, anchor: TileAnchor) -> Self {
+        self.anchor = anchor;
+        self
+    }
+
+    /// Whether `color` should be treated as the key color.
+    #[inline]
+    pub(crate) fn is_key_color(&self, color: Color) -> bool {
+        self.matches_key_color(self.key_color, color)
+    }
+
+    /// Whether `color` matches `key_color`, accounting for the tolerance.
+    #[inline]
+    pub(crate) fn matches_key_color(&self, key_color: Option<Color>, color: Color) -> bool {
+        key_color.is_some_and(|key| {
+            key.r.abs_diff(color.r) <= self.key_color_tolerance
+                && key.g.abs_diff(color.g) <= self.key_color_tolerance
+                && key.b.abs_diff(color.b) <= self.key_color_tolerance
+                && key.a.abs_diff(color.a) <= self.key_color_tolerance
+        })
+    }
+}
+
+/// Point of a tile aligned with the same point of the map cell it's drawn in.
+///
+/// Only matters for tiles of a different size than the map's cells, which stick out of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TileAnchor {
+    /// Top-left corner, tiles stick out to the right and down.
+    #[default]
+    TopLeft,
+    /// Bottom-left corner (same as in Tiled), tiles stick out to the right and up.
+    BottomLeft,
+    /// Middle of the bottom edge, tiles stick out to the sides and up.
+    BottomCenter,
+    /// Center, tiles stick out evenly on all sides.
+    Center,
+}
+
+impl TileAnchor {
+    /// Position of the top-left corner of a `tile_size` tile relative to the top-left corner
+    /// of the `cell_size` cell it's drawn in.
+    #[inline]
+    pub const fn offset(self, tile_size: (u32, u32), cell_size: (u32, u32)) -> (i32, i32) {
+        let dx = cell_size.0 as i32 - tile_size.0 as i32;
+        let dy = cell_size.1 as i32 - tile_size.1 as i32;
+
+        match self {
+            Self::TopLeft => (0, 0),
+            Self::BottomLeft => (0, dy),
+            Self::BottomCenter => (dx / 2, dy),
+            Self::Center => (dx / 2, dy / 2),
+        }
+    }
+}
+
+/// Result of [`Tileset::check_layout`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LayoutReport {
+    /// Whether the tiles cover the whole image (after the offset) without leftover pixels.
+    pub exact: bool,
+    /// Amount of pixels (x, y) left after the last tile in a row/column.
+    pub leftover: (u32, u32),
+}
+
+/// Error returned when a tileset can't be constructed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TilesetError {
+    /// Data length in bytes doesn't match the image's size.
+    InvalidDataLength {
+        /// Length required by the image's size.
+        expected: usize,
+        /// Length of the data.
+        actual: usize,
+    },
+    /// Tile width or height is 0.
+    ZeroTileSize,
+    /// A tile is wider or taller than the image.
+    TileTooLarge,
+    /// The offset leaves no room for a single tile.
+    OffsetOutOfBounds,
+    /// Atlas region of the tile is empty or doesn't fit in the image, see [`Tileset::with_regions`].
+    InvalidRegion(TileId),
+    /// The image would be too large to address, see [`TilesetBuilder::build`].
+    ImageTooLarge,
+}
+
+impl fmt::Display for TilesetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidDataLength { expected, actual } => {
+                write!(f, "data is {actual} bytes long, expected {expected}")
+            }
+            Self::ZeroTileSize => write!(f, "tile size is zero"),
+            Self::TileTooLarge => write!(f, "tile size is bigger than the image"),
+            Self::OffsetOutOfBounds => write!(f, "no tiles fit in the image after the offset"),
+            Self::InvalidRegion(id) => {
+                write!(f, "region of tile {id} is empty or outside the image")
+            }
+            Self::ImageTooLarge => write!(f, "image is too large"),
+        }
+    }
 }
 
 /// Tileset holds a collection of tiles stored as their pixel data.
 ///
 /// Currently only supports RGBA 8 bits per channel.
 ///
-/// Tiles are counted left-to-right then top-to-bottom.
+/// Tiles are counted left-to-right then top-to-bottom,
+/// unless the tileset is an atlas of arbitrary regions (see [`with_regions`](Tileset::with_regions)).
 ///
 /// Generic parameter `C` is the container type, which should implement `AsRef<[u8]>`.
 /// You can use a simple `Vec<u8>`/`&[u8]` with RGBA data, `Rc<[u8]>`/`Arc<[u8]>` for cheap cloning
 /// or e.g. `image`'s [`ImageBuffer`](https://docs.rs/image/latest/image/struct.ImageBuffer.html).
+/// Containers of [`Color`]s can be used through [`PixelData`], see [`Tileset::from_pixels`].
+///
+/// Generic parameter `M` is the type of per-tile metadata (e.g. whether a tile is solid), see [`Tileset::with_meta`].
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-pub struct Tileset<C> {
+pub struct Tileset<C, M = ()> {
     data: C,
     width: u32,
     height: u32,
     tile_counts: (u32, u32),
     pub(crate) opts: TilesetOptions,
+    #[cfg_attr(feature = "serde", serde(default))]
+    alpha_mask: Option<Vec<u8>>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    regions: Option<Vec<(u32, u32, u32, u32)>>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) animations: BTreeMap<TileId, TileAnimation>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) terrains: BTreeMap<TileId, TileTerrain>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    names: BTreeMap<String, TileId>,
+    #[cfg_attr(feature = "serde", serde(default = "BTreeMap::new"))]
+    meta: BTreeMap<TileId, M>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    generation: u64,
 }
 
-impl<C> Tileset<C> {
+impl<C, M> Tileset<C, M> {
     /// Whether `id` is a valid tile id for this tileset.
     ///
-    /// Basically `id < self.tile_count()`.
+    /// Basically `id < self.tile_count()`. With [`wrap_ids`](TilesetOptions::wrap_ids) set,
+    /// any id except [`EMPTY_TILE`] is valid as long as the tileset has tiles.
     #[inline]
     pub fn contains(&self, id: TileId) -> bool {
-        id < self.tile_count()
+        self.wrap_id(id) < self.tile_count()
+    }
+
+    /// Whether `color` is treated as the key color (accounting for the tolerance) when rendering.
+    #[inline]
+    pub fn is_key_color(&self, color: Color) -> bool {
+        self.opts.is_key_color(color)
     }
 
     /// Total amount of tiles in the tileset.
     #[inline]
     pub fn tile_count(&self) -> u32 {
-        self.tile_counts.0 * self.tile_counts.1
+        match &self.regions {
+            Some(regions) => regions.len() as u32,
+            None => self.tile_counts.0 * self.tile_counts.1,
+        }
     }
 
-    /// Tileset options used when creating the tileset.
+    /// Amount of tiles in a row of the tileset's grid.
+    ///
+    /// Atlas tilesets don't lay their tiles out on a grid, so for them this says nothing about the tiles.
     #[inline]
-    pub fn options(&self) -> &TilesetOptions {
-        &self.opts
+    pub fn tile_columns(&self) -> u32 {
+        self.tile_counts.0
     }
-}
 
-impl<C> Tileset<C>
-where
-    C: AsRef<[u8]>,
-{
-    /// Construct a new tileset.
-    /// `width` and `height` are `data`'s size in pixels.
-    pub fn new(data: C, width: u32, height: u32, opts: TilesetOptions) -> Option<Self> {
-        if data.as_ref().len() == ((width * height) as usize * size_of::<C>()) {
-            let tile_counts = calc_tile_counts(width, height, &opts);
-
-            Some(Self {
-                data,
-                width,
-                height,
-                tile_counts,
-                opts,
-            })
+    /// Amount of tiles in a column of the tileset's grid.
+    ///
+    /// Atlas tilesets don't lay their tiles out on a grid, so for them this says nothing about the tiles.
+    #[inline]
+    pub fn tile_rows(&self) -> u32 {
+        self.tile_counts.1
+    }
+
+    /// Get the column and row of a tile in the tileset.
+    ///
+    /// Unlike [`get_tile_pos`](Self::get_tile_pos), the result is in tiles, not pixels.
+    /// Returns `None` for atlas tilesets.
+    #[inline]
+    pub fn tile_xy(&self, id: TileId) -> Option<(u32, u32)> {
+        if self.regions.is_none() && self.contains(id) {
+            let id = self.wrap_id(id);
+            Some((id % self.tile_counts.0, id / self.tile_counts.0))
         } else {
             None
         }
@@ -122,89 +325,1878 @@ where
 
     /// Get the position of a tile in the tileset.
     /// Useful if you need to render a single tile.
+    ///
+    /// With [`wrap_ids`](TilesetOptions::wrap_ids) set, `id` is taken modulo the tile count.
+    /// Returns `None` for [`EMPTY_TILE`].
+    #[inline]
     pub fn get_tile_pos(&self, id: TileId) -> Option<(u32, u32)> {
+        self.tile_rect(id).map(|(x, y, _, _)| (x, y))
+    }
+
+    /// Get the pixel rectangle `(x, y, width, height)` of a tile in the tileset.
+    ///
+    /// All the tiles have the options' [`tile_size`](TilesetOptions::tile_size) unless the tileset is an atlas.
+    /// Same as [`get_tile_pos`](Self::get_tile_pos) otherwise.
+    pub fn tile_rect(&self, id: TileId) -> Option<(u32, u32, u32, u32)> {
+        if id == EMPTY_TILE {
+            return None;
+        }
+
+        let id = self.wrap_id(id);
+
+        if let Some(regions) = &self.regions {
+            return regions.get(id as usize).copied();
+        }
+
         let x = (id % self.tile_counts.0) * (self.opts.tile_size.0 + self.opts.spacing.0)
             + self.opts.offset.0;
 
         let y = (id / self.tile_counts.0) * (self.opts.tile_size.1 + self.opts.spacing.1)
             + self.opts.offset.1;
 
-        if (x + self.opts.tile_size.0) < self.width as _
-            && (y + self.opts.tile_size.1) < self.height as _
-        {
-            Some((x, y))
+        let (width, height) = self.opts.tile_size;
+
+        if (x + width) <= self.width as _ && (y + height) <= self.height as _ {
+            Some((x, y, width, height))
         } else {
             None
         }
     }
 
-    /// Render a single tile from the tileset, accounting for the key color.
-    pub fn render_tile(
+    /// `id` taken modulo the tile count if [`wrap_ids`](TilesetOptions::wrap_ids) is set.
+    #[inline]
+    fn wrap_id(&self, id: TileId) -> TileId {
+        match self.tile_count() {
+            count if self.opts.wrap_ids && count > 0 && id != EMPTY_TILE => id % count,
+            _ => id,
+        }
+    }
+
+    /// How far (left, top, right, bottom) in pixels the images of map tiles can stick out of `cell_size` cells.
+    pub(crate) fn overhang(&self, cell_size: (u32, u32)) -> (u32, u32, u32, u32) {
+        self.image_sizes()
+            .map(|image_size| {
+                // rotated tiles swap their width and height
+                let size = image_size.0.max(image_size.1);
+                let (x, y) = self.opts.anchor.offset(image_size, cell_size);
+
+                (
+                    (-x).max(0) as u32,
+                    (-y).max(0) as u32,
+                    (x as i64 + size as i64 - cell_size.0 as i64).max(0) as u32,
+                    (y as i64 + size as i64 - cell_size.1 as i64).max(0) as u32,
+                )
+            })
+            .fold((0, 0, 0, 0), |a, b| {
+                (a.0.max(b.0), a.1.max(b.1), a.2.max(b.2), a.3.max(b.3))
+            })
+    }
+
+    /// Pixel position of the top-left corner of the `image_size` image of map tile `tile` drawn in the `cell_size` cell
+    /// at pixel position `cell`, placed according to the tileset's [anchor](TileAnchor) and the tile's offset.
+    #[inline]
+    pub(crate) fn image_dest<U>(
         &self,
-        surface: &mut (impl BufferMut<Color> + ?Sized),
-        id: TileId,
-        offset_x: i32,
-        offset_y: i32,
-        opts: BlitOptions,
-    ) {
-        if let Some((x, y)) = self.get_tile_pos(id) {
-            blit_with(
-                surface,
-                (offset_x, offset_y),
-                self,
-                (x as _, y as _),
-                self.opts.tile_size,
-                opts,
-                |dest, src, _| {
-                    if Some(*src) != self.opts.key_color {
-                        *dest = *src;
-                    }
-                },
-            )
+        tile: &Tile<U>,
+        image_size: (u32, u32),
+        cell_size: (u32, u32),
+        cell: (i32, i32),
+    ) -> (i32, i32) {
+        let (x, y) = self.opts.anchor.offset(image_size, cell_size);
+
+        (
+            cell.0 + x + tile.offset.0 as i32,
+            cell.1 + y + tile.offset.1 as i32,
+        )
+    }
+
+    /// Ranges of columns and rows of tiles that intersect a `surface_size` (width x height) surface
+    /// when an orthogonal `map_size` (width x height) map of this tileset's tiles is rendered at pixel offset `offset`.
+    ///
+    /// Includes the cells whose tiles may stick out into the surface, or would if they were moved
+    /// by up to `margin` (before, after) pixels.
+    pub(crate) fn map_visible_range(
+        &self,
+        offset: (i32, i32),
+        surface_size: (u32, u32),
+        map_size: (u32, u32),
+        (before, after): (u32, u32),
+    ) -> (Range<u32>, Range<u32>) {
+        let (tile_width, tile_height) = self.opts.tile_size;
+        let (left, top, right, bottom) = self.overhang(self.opts.tile_size);
+        let (left, top) = (left.saturating_add(before), top.saturating_add(before));
+        let (right, bottom) = (right.saturating_add(after), bottom.saturating_add(after));
+
+        let columns = cell_span(
+            offset.0 as i64,
+            surface_size.0,
+            tile_width,
+            tile_width,
+            0,
+            (left, right),
+        );
+        let rows = cell_span(
+            offset.1 as i64,
+            surface_size.1,
+            tile_height,
+            tile_height,
+            0,
+            (top, bottom),
+        );
+
+        (
+            clamp_span(columns, map_size.0),
+            clamp_span(rows, map_size.1),
+        )
+    }
+
+    /// Check whether the tiles fit the image exactly, which is useful for detecting misconfigured options.
+    ///
+    /// Only checks the grid, which atlas tilesets don't use.
+    pub fn check_layout(&self) -> LayoutReport {
+        let leftover = |size: u32, offset: u32, count: u32, tile_size: u32, spacing: u32| {
+            let used = count * tile_size + count.saturating_sub(1) * spacing;
+            size.saturating_sub(offset).saturating_sub(used)
+        };
+
+        let leftover = (
+            leftover(
+                self.width,
+                self.opts.offset.0,
+                self.tile_counts.0,
+                self.opts.tile_size.0,
+                self.opts.spacing.0,
+            ),
+            leftover(
+                self.height,
+                self.opts.offset.1,
+                self.tile_counts.1,
+                self.opts.tile_size.1,
+                self.opts.spacing.1,
+            ),
+        );
+
+        LayoutReport {
+            exact: leftover == (0, 0),
+            leftover,
         }
     }
-}
 
-impl<C> Buffer<Color> for Tileset<C>
-where
-    C: AsRef<[u8]>,
-{
+    /// Tileset options used when creating the tileset.
     #[inline]
-    fn width(&self) -> u32 {
-        self.width
+    pub fn options(&self) -> &TilesetOptions {
+        &self.opts
     }
 
+    /// Convert the tileset's data into a different container type,
+    /// e.g. `Vec<u8>` into `Arc<[u8]>` for cheap cloning.
     #[inline]
-    fn height(&self) -> u32 {
-        self.height
+    pub fn into_container<C2>(self) -> Tileset<C2, M>
+    where
+        C2: From<C>,
+    {
+        Tileset {
+            data: self.data.into(),
+            width: self.width,
+            height: self.height,
+            tile_counts: self.tile_counts,
+            opts: self.opts,
+            alpha_mask: self.alpha_mask,
+            regions: self.regions,
+            animations: self.animations,
+            terrains: self.terrains,
+            names: self.names,
+            meta: self.meta,
+            generation: self.generation,
+        }
     }
 
+    /// Alpha mask of the tileset, if any.
     #[inline]
-    fn get(&self, x: u32, y: u32) -> &Color {
-        self.data
-            .as_ref()
-            .as_pixels()
-            .index((y * self.width + x) as usize)
+    pub fn alpha_mask(&self) -> Option<&[u8]> {
+        self.alpha_mask.as_deref()
+    }
+
+    /// Remove the alpha mask.
+    #[inline]
+    pub fn clear_alpha_mask(&mut self) {
+        self.alpha_mask = None;
+        self.generation += 1;
+    }
+
+    /// Turn the tileset into an atlas where tile `id` is the pixel rectangle `regions[id]` (x, y, width, height)
+    /// of the image, instead of a cell of the uniform grid.
+    ///
+    /// Regions can have any size, the options' [`tile_size`](TilesetOptions::tile_size) remains the size of map cells
+    /// and tiles of other sizes are placed in them according to the [anchor](TilesetOptions::anchor).
+    /// Returns an error if a region is empty or doesn't fit in the image.
+    pub fn with_regions(
+        mut self,
+        regions: Vec<(u32, u32, u32, u32)>,
+    ) -> Result<Self, TilesetError> {
+        for (id, &(x, y, width, height)) in regions.iter().enumerate() {
+            if width == 0
+                || height == 0
+                || x as u64 + width as u64 > self.width as u64
+                || y as u64 + height as u64 > self.height as u64
+            {
+                return Err(TilesetError::InvalidRegion(id as TileId));
+            }
+        }
+
+        self.regions = Some(regions);
+        self.generation += 1;
+
+        Ok(self)
+    }
+
+    /// Atlas regions of the tiles, if any.
+    #[inline]
+    pub fn regions(&self) -> Option<&[(u32, u32, u32, u32)]> {
+        self.regions.as_deref()
+    }
+
+    /// Name tile `id` `name`, so that it can be looked up with [`id_of`](Self::id_of).
+    ///
+    /// Replaces the tile the name was given to before, if any. A tile can have several names.
+    #[inline]
+    pub fn set_tile_name(&mut self, name: impl Into<String>, id: TileId) {
+        self.names.insert(name.into(), id);
+    }
+
+    /// Get the id of the tile named `name`.
+    #[inline]
+    pub fn id_of(&self, name: &str) -> Option<TileId> {
+        self.names.get(name).copied()
+    }
+
+    /// Remove the name `name`, returning the id of the tile it was given to.
+    #[inline]
+    pub fn remove_tile_name(&mut self, name: &str) -> Option<TileId> {
+        self.names.remove(name)
+    }
+
+    /// Iterate over the tile names along with the ids of the tiles, in alphabetical order.
+    #[inline]
+    pub fn tile_names(&self) -> impl Iterator<Item = (&str, TileId)> {
+        self.names.iter().map(|(name, &id)| (name.as_str(), id))
+    }
+
+    /// Replace the tileset's per-tile metadata with `meta`, which may be of a different type.
+    #[inline]
+    pub fn with_meta<M2>(self, meta: BTreeMap<TileId, M2>) -> Tileset<C, M2> {
+        Tileset {
+            data: self.data,
+            width: self.width,
+            height: self.height,
+            tile_counts: self.tile_counts,
+            opts: self.opts,
+            alpha_mask: self.alpha_mask,
+            regions: self.regions,
+            animations: self.animations,
+            terrains: self.terrains,
+            names: self.names,
+            meta,
+            generation: self.generation,
+        }
+    }
+
+    /// Set the metadata of tile `id`, returning the previous one.
+    #[inline]
+    pub fn set_tile_meta(&mut self, id: TileId, meta: M) -> Option<M> {
+        self.meta.insert(id, meta)
+    }
+
+    /// Get the metadata of tile `id`.
+    #[inline]
+    pub fn tile_meta(&self, id: TileId) -> Option<&M> {
+        self.meta.get(&id)
+    }
+
+    /// Get a mutable ref to the metadata of tile `id`.
+    #[inline]
+    pub fn tile_meta_mut(&mut self, id: TileId) -> Option<&mut M> {
+        self.meta.get_mut(&id)
+    }
+
+    /// Remove the metadata of tile `id`, returning it.
+    #[inline]
+    pub fn remove_tile_meta(&mut self, id: TileId) -> Option<M> {
+        self.meta.remove(&id)
+    }
+
+    /// Sizes of the tiles' images, with duplicates in atlas tilesets.
+    pub(crate) fn image_sizes(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        let regions = self.regions.as_deref().unwrap_or_default();
+
+        iter::once(self.opts.tile_size)
+            .filter(|_| self.regions.is_none())
+            .chain(regions.iter().map(|&(_, _, width, height)| (width, height)))
+    }
+
+    /// Counter that gets incremented every time the tileset's pixels are edited
+    /// through [`edit_pixels`](Self::edit_pixels) or its alpha mask changes.
+    ///
+    /// Caches built from the tileset can compare it to decide whether they need to be rebuilt.
+    /// Edits done directly through [`BufferMut`] aren't tracked.
+    #[inline]
+    pub fn generation(&self) -> u64 {
+        self.generation
     }
 }
 
-impl<C> BufferMut<Color> for Tileset<C>
+impl<C> Tileset<C>
 where
-    C: AsRef<[u8]> + AsMut<[u8]>,
+    C: AsRef<[u8]>,
 {
-    #[inline]
-    fn get_mut(&mut self, x: u32, y: u32) -> &mut Color {
-        self.data
-            .as_mut()
-            .as_pixels_mut()
-            .index_mut((y * self.width + x) as usize)
+    /// Construct a new tileset.
+    /// `width` and `height` are `data`'s size in pixels.
+    ///
+    /// Returns an error if `data`'s length doesn't match the size or not a single tile fits in the image.
+    pub fn new(
+        data: C,
+        width: u32,
+        height: u32,
+        opts: TilesetOptions,
+    ) -> Result<Self, TilesetError> {
+        let expected = width as usize * height as usize * size_of::<Color>();
+        let actual = data.as_ref().len();
+
+        if actual != expected {
+            return Err(TilesetError::InvalidDataLength { expected, actual });
+        }
+
+        let (tile_width, tile_height) = opts.tile_size;
+
+        if tile_width == 0 || tile_height == 0 {
+            return Err(TilesetError::ZeroTileSize);
+        }
+
+        if tile_width > width || tile_height > height {
+            return Err(TilesetError::TileTooLarge);
+        }
+
+        if opts.offset.0 > width - tile_width || opts.offset.1 > height - tile_height {
+            return Err(TilesetError::OffsetOutOfBounds);
+        }
+
+        let tile_counts = calc_tile_counts(width, height, &opts);
+
+        Ok(Self {
+            data,
+            width,
+            height,
+            tile_counts,
+            opts,
+            alpha_mask: None,
+            regions: None,
+            animations: BTreeMap::new(),
+            terrains: BTreeMap::new(),
+            names: BTreeMap::new(),
+            meta: BTreeMap::new(),
+            generation: 0,
+        })
     }
 }
 
-#[inline]
-const fn calc_tile_counts(width: u32, height: u32, opts: &TilesetOptions) -> (u32, u32) {
-    (
-        (width - opts.offset.0 + opts.spacing.0) / (opts.tile_size.0 + opts.spacing.0),
-        (height - opts.offset.1 + opts.spacing.1) / (opts.tile_size.1 + opts.spacing.1),
-    )
+impl<C, M> Tileset<C, M>
+where
+    C: AsRef<[u8]>,
+{
+    /// Attach an alpha mask to the tileset.
+    ///
+    /// `mask` holds one value per pixel of the tileset, which gets multiplied into the pixel's alpha when rendering.
+    /// Returns `None` if `mask`'s length doesn't match the tileset's size.
+    pub fn with_alpha_mask(mut self, mask: Vec<u8>) -> Option<Self> {
+        if mask.len() == (self.width * self.height) as usize {
+            self.alpha_mask = Some(mask);
+            self.generation += 1;
+            Some(self)
+        } else {
+            None
+        }
+    }
+
+    /// Get the color the pixel (x, y) of this tileset should be rendered with,
+    /// or `None` if it should be skipped (because it's the key color or below the alpha cutout).
+    #[inline]
+    pub(crate) fn source_pixel(
+        &self,
+        (x, y): (u32, u32),
+        key_color: Option<Color>,
+    ) -> Option<Color> {
+        let pixel = *self.get(x, y);
+
+        if self.opts.alpha_cutout.is_none() && self.opts.matches_key_color(key_color, pixel) {
+            return None;
+        }
+
+        let color = if self.opts.premultiplied {
+            unpremultiply(pixel)
+        } else {
+            pixel
+        };
+        let color = Color {
+            a: self.apply_mask(pixel, (x, y)).a,
+            ..color
+        };
+
+        match self.opts.alpha_cutout {
+            Some(threshold) => (color.a >= threshold).then_some(Color { a: 255, ..color }),
+            None => Some(color),
+        }
+    }
+
+    /// Apply the alpha mask (if any) to `pixel`, the pixel (x, y) of this tileset.
+    #[inline]
+    pub(crate) fn apply_mask(&self, pixel: Color, (x, y): (u32, u32)) -> Color {
+        match &self.alpha_mask {
+            Some(mask) => {
+                let index = (y * self.width + x) as usize;
+                let alpha = (pixel.a as u16 * mask[index] as u16 / 255) as u8;
+
+                Color { a: alpha, ..pixel }
+            }
+            None => pixel,
+        }
+    }
+
+    /// Whether every pixel of a tile is fully opaque and isn't the key color.
+    ///
+    /// Returns `None` if the tile doesn't exist.
+    pub fn tile_is_opaque(&self, id: TileId) -> Option<bool> {
+        let (x, y, tile_width, tile_height) = self.tile_rect(id)?;
+
+        Some((y..y + tile_height).all(|py| {
+            (x..x + tile_width).all(|px| {
+                self.source_pixel((px, py), self.opts.key_color)
+                    .is_some_and(|pixel| pixel.a == 255)
+            })
+        }))
+    }
+
+    /// Get a view of a single tile's pixels, e.g. to blit it with your own logic.
+    ///
+    /// Position (0, 0) of the view is the tile's top left corner.
+    /// Returns `None` if the tile doesn't exist.
+    pub fn tile_view(&self, id: TileId) -> Option<TileImage<'_>> {
+        let (x, y, width, height) = self.tile_rect(id)?;
+
+        Some(TileImage {
+            pixels: self.data.as_ref().as_pixels(),
+            stride: self.width,
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    /// Iterate over all the tiles, in order of their ids.
+    #[inline]
+    pub fn iter_tiles(&self) -> impl Iterator<Item = (TileId, TileImage<'_>)> {
+        (0..self.tile_count()).filter_map(|id| Some((id, self.tile_view(id)?)))
+    }
+
+    /// Copy a tile's pixels, row by row.
+    ///
+    /// Returns `None` if the tile doesn't exist.
+    pub fn tile_pixels(&self, id: TileId) -> Option<Vec<Color>> {
+        let (x, y, tile_width, tile_height) = self.tile_rect(id)?;
+
+        Some(
+            (y..y + tile_height)
+                .flat_map(|py| (x..x + tile_width).map(move |px| *self.get(px, py)))
+                .collect(),
+        )
+    }
+
+    /// Copy `count` tiles starting from `first_id` into a new tightly packed tileset.
+    ///
+    /// Tiles in the new tileset are renumbered starting from 0. Key color, tolerance, alpha cutout
+    /// and the alpha mask are carried over, while offset and spacing are dropped.
+    /// Returns `None` if `count` is 0, some of the tiles don't exist
+    /// or (in an atlas) aren't of the options' [`tile_size`](TilesetOptions::tile_size).
+    pub fn subset(&self, first_id: TileId, count: u32) -> Option<Tileset<Vec<u8>>> {
+        let end = first_id.checked_add(count)?;
+
+        if count == 0 || end > self.tile_count() {
+            return None;
+        }
+
+        let (tile_width, tile_height) = self.opts.tile_size;
+        let columns = self.tile_counts.0.min(count);
+
+        let mut builder = TilesetBuilder::new(tile_width, tile_height).with_columns(columns);
+
+        for id in first_id..end {
+            let (_, _, width, height) = self.tile_rect(id)?;
+
+            if (width, height) != (tile_width, tile_height) {
+                return None;
+            }
+
+            builder.push_tile(&self.tile_pixels(id)?)?;
+        }
+
+        let mut subset = builder.build().ok()?;
+
+        subset.opts = TilesetOptions {
+            tile_size: self.opts.tile_size,
+            offset: (0, 0),
+            spacing: (0, 0),
+            ..self.opts.clone()
+        };
+
+        if let Some(mask) = &self.alpha_mask {
+            let mut new_mask = vec![0; (subset.width * subset.height) as usize];
+
+            for (new_id, id) in (first_id..end).enumerate() {
+                let (x, y) = self.get_tile_pos(id)?;
+                let (nx, ny) = subset.get_tile_pos(new_id as TileId)?;
+
+                for row in 0..tile_height {
+                    let src = ((y + row) * self.width + x) as usize;
+                    let dest = ((ny + row) * subset.width + nx) as usize;
+
+                    new_mask[dest..dest + tile_width as usize]
+                        .copy_from_slice(&mask[src..src + tile_width as usize]);
+                }
+            }
+
+            subset.alpha_mask = Some(new_mask);
+        }
+
+        Some(subset)
+    }
+
+    /// Blit a map tile in the cell at pixel position `cell` of a map of this tileset's tiles,
+    /// moved by its [offset](Tile::offset), calling `f` for each of its pixels that should be drawn.
+    pub(crate) fn render_map_tile<P, U>(
+        &self,
+        surface: &mut (impl BufferMut<P> + ?Sized),
+        tile: &Tile<U>,
+        cell: (i32, i32),
+        fallback: Option<TileId>,
+        f: &mut impl FnMut(&mut P, &Color, &Tile<U>),
+    ) {
+        if let Some((rect, key_color)) = self.resolve_tile(tile, fallback) {
+            let dest = self.image_dest(tile, (rect.2, rect.3), self.opts.tile_size, cell);
+            self.blit_map_tile(surface, tile, rect, key_color, dest, f);
+        }
+    }
+
+    /// Blit the tile at rectangle `(x, y, width, height)` in the tileset at pixel position `dest` on behalf of map tile `tile`,
+    /// calling `f` for each of its pixels that isn't `key_color`.
+    pub(crate) fn blit_map_tile<P, U>(
+        &self,
+        surface: &mut (impl BufferMut<P> + ?Sized),
+        tile: &Tile<U>,
+        (x, y, image_width, image_height): (u32, u32, u32, u32),
+        key_color: Option<Color>,
+        dest: (i32, i32),
+        f: &mut impl FnMut(&mut P, &Color, &Tile<U>),
+    ) {
+        let image_size = (image_width, image_height);
+        let (width, height) = tile.rotation.apply_size(image_size);
+
+        for ty in 0..height {
+            for tx in 0..width {
+                let (dx, dy) = (dest.0 as i64 + tx as i64, dest.1 as i64 + ty as i64);
+
+                if dx < 0 || dy < 0 || dx >= surface.width() as i64 || dy >= surface.height() as i64
+                {
+                    continue;
+                }
+
+                let (sx, sy) = source_pos(tile, (tx, ty), image_size);
+
+                if let Some(src) = self.source_pixel((x + sx, y + sy), key_color) {
+                    f(surface.get_mut(dx as u32, dy as u32), &src, tile);
+                }
+            }
+        }
+    }
+
+    /// Same as [`blit_map_tile`](Self::blit_map_tile), but every pixel of the tile is drawn as a `scale` x `scale` block
+    /// and only the part inside the `clip` (width x height) area in the top-left corner of the surface is drawn.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn blit_map_tile_scaled<P, U>(
+        &self,
+        surface: &mut (impl BufferMut<P> + ?Sized),
+        tile: &Tile<U>,
+        (x, y, image_width, image_height): (u32, u32, u32, u32),
+        key_color: Option<Color>,
+        dest: (i64, i64),
+        scale: u32,
+        clip: (u32, u32),
+        f: &mut impl FnMut(&mut P, &Color, &Tile<U>),
+    ) {
+        let image_size = (image_width, image_height);
+        let (tile_width, tile_height) = tile.rotation.apply_size(image_size);
+        let scale = scale as i64;
+        let clip_width = clip.0.min(surface.width()) as i64;
+        let clip_height = clip.1.min(surface.height()) as i64;
+
+        for ty in 0..tile_height {
+            let top = dest.1 + ty as i64 * scale;
+
+            if top + scale <= 0 || top >= clip_height {
+                continue;
+            }
+
+            for tx in 0..tile_width {
+                let left = dest.0 + tx as i64 * scale;
+
+                if left + scale <= 0 || left >= clip_width {
+                    continue;
+                }
+
+                let (sx, sy) = source_pos(tile, (tx, ty), image_size);
+
+                let Some(src) = self.source_pixel((x + sx, y + sy), key_color) else {
+                    continue;
+                };
+
+                for py in top.max(0)..(top + scale).min(clip_height) {
+                    for px in left.max(0)..(left + scale).min(clip_width) {
+                        f(surface.get_mut(px as u32, py as u32), &src, tile);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Rectangle of a map tile in the tileset (or of `fallback` if the tile's id is invalid)
+    /// and the key color to use for it.
+    #[inline]
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn resolve_tile<U>(
+        &self,
+        tile: &Tile<U>,
+        fallback: Option<TileId>,
+    ) -> Option<((u32, u32, u32, u32), Option<Color>)> {
+        if tile.is_empty() {
+            return None;
+        }
+
+        let rect = self
+            .tile_rect(tile.id)
+            .or_else(|| self.tile_rect(fallback?))?;
+
+        let key_color = tile.key_color_override.unwrap_or(self.opts.key_color);
+
+        Some((rect, key_color))
+    }
+
+    /// Render a single tile from the tileset, accounting for the key color.
+    ///
+    /// See [`render_tile_colored`](Self::render_tile_colored) to tint it.
+    pub fn render_tile(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        id: TileId,
+        offset_x: i32,
+        offset_y: i32,
+        opts: BlitOptions,
+    ) {
+        if let Some(rect) = self.tile_rect(id) {
+            let tile: Tile = Tile::new(id).with_blit_options(opts);
+
+            self.blit_map_tile(
+                surface,
+                &tile,
+                rect,
+                self.opts.key_color,
+                (offset_x, offset_y),
+                &mut |dest, src, _| *dest = *src,
+            );
+        }
+    }
+
+    /// Render a single tile from the tileset, accounting for the key color
+    /// and multiplying it by `color` the same way [`Tilemap::render`](crate::Tilemap::render) does.
+    pub fn render_tile_colored(
+        &self,
+        surface: &mut (impl BufferMut<Color> + ?Sized),
+        id: TileId,
+        offset_x: i32,
+        offset_y: i32,
+        color: Color,
+        opts: BlitOptions,
+    ) {
+        if let Some(rect) = self.tile_rect(id) {
+            let tile: Tile = Tile::new(id).with_blit_options(opts);
+
+            self.blit_map_tile(
+                surface,
+                &tile,
+                rect,
+                self.opts.key_color,
+                (offset_x, offset_y),
+                &mut |dest, src, _| *dest = multiply(*src, color),
+            );
+        }
+    }
+}
+
+impl<'a> Tileset<&'a [u8]> {
+    /// Construct a new tileset borrowing RGBA data from a slice, without copying it.
+    /// `width` and `height` are `data`'s size in pixels.
+    ///
+    /// Returns an error if `data`'s length doesn't match the size, see [`Tileset::new`].
+    #[inline]
+    pub fn from_slice(
+        data: &'a [u8],
+        width: u32,
+        height: u32,
+        opts: TilesetOptions,
+    ) -> Result<Self, TilesetError> {
+        Self::new(data, width, height, opts)
+    }
+}
+
+/// Adapter for containers of [`Color`]s (e.g. `Vec<Color>`, `&[Color]`) to be used as tileset data.
+///
+/// See [`Tileset::from_pixels`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct PixelData<C>(pub C);
+
+impl<C> AsRef<[u8]> for PixelData<C>
+where
+    C: AsRef<[Color]>,
+{
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref().as_bytes()
+    }
+}
+
+impl<C> AsMut<[u8]> for PixelData<C>
+where
+    C: AsRef<[Color]> + AsMut<[Color]>,
+{
+    #[inline]
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.0.as_mut().as_bytes_mut()
+    }
+}
+
+impl<C> Tileset<PixelData<C>>
+where
+    C: AsRef<[Color]>,
+{
+    /// Construct a new tileset from a container of pixels, like `Vec<Color>` or `&[Color]`, without copying it.
+    /// `width` and `height` are `pixels`' size in pixels.
+    ///
+    /// Returns an error if `pixels`' length doesn't match the size, see [`Tileset::new`].
+    #[inline]
+    pub fn from_pixels(
+        pixels: C,
+        width: u32,
+        height: u32,
+        opts: TilesetOptions,
+    ) -> Result<Self, TilesetError> {
+        Self::new(PixelData(pixels), width, height, opts)
+    }
+}
+
+impl<C, M> Buffer<Color> for Tileset<C, M>
+where
+    C: AsRef<[u8]>,
+{
+    #[inline]
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[inline]
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[inline]
+    fn get(&self, x: u32, y: u32) -> &Color {
+        self.data
+            .as_ref()
+            .as_pixels()
+            .index((y * self.width + x) as usize)
+    }
+}
+
+impl<C, M> BufferMut<Color> for Tileset<C, M>
+where
+    C: AsRef<[u8]> + AsMut<[u8]>,
+{
+    #[inline]
+    fn get_mut(&mut self, x: u32, y: u32) -> &mut Color {
+        self.data
+            .as_mut()
+            .as_pixels_mut()
+            .index_mut((y * self.width + x) as usize)
+    }
+}
+
+impl<C, M> Tileset<C, M>
+where
+    C: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Edit the tileset's pixels, incrementing its [`generation`](Self::generation).
+    pub fn edit_pixels<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut TilesetPixels),
+    {
+        f(&mut TilesetPixels {
+            pixels: self.data.as_mut().as_pixels_mut(),
+            width: self.width,
+            height: self.height,
+        });
+
+        self.generation += 1;
+    }
+
+    /// Get a mutable view of a single tile's pixels, incrementing the tileset's [`generation`](Self::generation).
+    ///
+    /// Position (0, 0) of the view is the tile's top left corner.
+    /// Returns `None` if the tile doesn't exist.
+    pub fn tile_pixels_mut(&mut self, id: TileId) -> Option<TileView<'_>> {
+        let (x, y, width, height) = self.tile_rect(id)?;
+
+        self.generation += 1;
+
+        Some(TileView {
+            pixels: self.data.as_mut().as_pixels_mut(),
+            stride: self.width,
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+}
+
+/// Mutable view of a single tile's pixels, see [`Tileset::tile_pixels_mut`].
+#[derive(Debug)]
+pub struct TileView<'a> {
+    pixels: &'a mut [Color],
+    stride: u32,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl TileView<'_> {
+    /// Set every pixel of the tile to `color`.
+    pub fn fill(&mut self, color: Color) {
+        for y in 0..self.height {
+            let start = ((self.y + y) * self.stride + self.x) as usize;
+            self.pixels[start..start + self.width as usize].fill(color);
+        }
+    }
+}
+
+impl Buffer<Color> for TileView<'_> {
+    #[inline]
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[inline]
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[inline]
+    fn get(&self, x: u32, y: u32) -> &Color {
+        self.pixels
+            .index(((self.y + y) * self.stride + self.x + x) as usize)
+    }
+}
+
+impl BufferMut<Color> for TileView<'_> {
+    #[inline]
+    fn get_mut(&mut self, x: u32, y: u32) -> &mut Color {
+        self.pixels
+            .index_mut(((self.y + y) * self.stride + self.x + x) as usize)
+    }
+}
+
+/// View of a single tile's pixels, see [`Tileset::tile_view`].
+#[derive(Clone, Copy, Debug)]
+pub struct TileImage<'a> {
+    pixels: &'a [Color],
+    stride: u32,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl TileImage<'_> {
+    /// Position (x, y) of the tile's top left corner in the tileset.
+    #[inline]
+    pub fn position(&self) -> (u32, u32) {
+        (self.x, self.y)
+    }
+}
+
+impl Buffer<Color> for TileImage<'_> {
+    #[inline]
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[inline]
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[inline]
+    fn get(&self, x: u32, y: u32) -> &Color {
+        self.pixels
+            .index(((self.y + y) * self.stride + self.x + x) as usize)
+    }
+}
+
+/// Mutable view of a tileset's pixels, see [`Tileset::edit_pixels`].
+#[derive(Debug)]
+pub struct TilesetPixels<'a> {
+    pixels: &'a mut [Color],
+    width: u32,
+    height: u32,
+}
+
+impl TilesetPixels<'_> {
+    /// All the pixels, row by row.
+    #[inline]
+    pub fn pixels_mut(&mut self) -> &mut [Color] {
+        self.pixels
+    }
+}
+
+impl Buffer<Color> for TilesetPixels<'_> {
+    #[inline]
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[inline]
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[inline]
+    fn get(&self, x: u32, y: u32) -> &Color {
+        self.pixels.index((y * self.width + x) as usize)
+    }
+}
+
+impl BufferMut<Color> for TilesetPixels<'_> {
+    #[inline]
+    fn get_mut(&mut self, x: u32, y: u32) -> &mut Color {
+        self.pixels.index_mut((y * self.width + x) as usize)
+    }
+}
+
+/// Builds a tileset by packing individual tiles into a single image.
+///
+/// Tiles are laid out left-to-right then top-to-bottom, so their ids match the order they were pushed in.
+#[derive(Clone, Debug)]
+pub struct TilesetBuilder {
+    tile_size: (u32, u32),
+    columns: Option<u32>,
+    key_color: Option<Color>,
+    tiles: Vec<Color>,
+    tile_count: u32,
+}
+
+impl TilesetBuilder {
+    /// Create a new TilesetBuilder for tiles of size `tile_width` x `tile_height`.
+    #[inline]
+    pub const fn new(tile_width: u32, tile_height: u32) -> Self {
+        Self {
+            tile_size: (tile_width, tile_height),
+            columns: None,
+            key_color: None,
+            tiles: Vec::new(),
+            tile_count: 0,
+        }
+    }
+
+    /// Specify the amount of tiles per row.
+    /// By default all the tiles are put into a single row.
+    #[inline]
+    pub fn with_columns(mut self, columns: u32) -> Self {
+        self.columns = Some(columns);
+        self
+    }
+
+    /// Specify key color.
+    #[inline]
+    pub fn with_key_color(mut self, key_color: Color) -> Self {
+        self.key_color = Some(key_color);
+        self
+    }
+
+    /// Add a tile. `pixels` are the tile's pixels, row by row.
+    ///
+    /// Returns the id of the new tile or `None` if the amount of pixels doesn't match the tile size.
+    pub fn push_tile(&mut self, pixels: &[Color]) -> Option<TileId> {
+        if pixels.len() == self.tile_size.0 as usize * self.tile_size.1 as usize {
+            self.tiles.extend_from_slice(pixels);
+            self.tile_count += 1;
+
+            Some(self.tile_count - 1)
+        } else {
+            None
+        }
+    }
+
+    /// Pack the tiles into a tileset.
+    ///
+    /// If the last row isn't full, the rest of it is filled with transparent tiles.
+    /// Returns an error if the tile width or height is 0 or the image's size doesn't fit into a `u32`.
+    pub fn build(self) -> Result<Tileset<Vec<u8>>, TilesetError> {
+        let (tile_width, tile_height) = self.tile_size;
+
+        if tile_width == 0 || tile_height == 0 {
+            return Err(TilesetError::ZeroTileSize);
+        }
+
+        let columns = self.columns.unwrap_or(self.tile_count).max(1);
+        let rows = self.tile_count.div_ceil(columns);
+
+        let width = columns
+            .checked_mul(tile_width)
+            .ok_or(TilesetError::ImageTooLarge)?;
+        let height = rows
+            .checked_mul(tile_height)
+            .ok_or(TilesetError::ImageTooLarge)?;
+        let len = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|len| len.checked_mul(size_of::<Color>()))
+            .ok_or(TilesetError::ImageTooLarge)?;
+
+        let mut data = vec![0; len];
+        let pixels = data.as_pixels_mut();
+
+        for (id, tile) in self
+            .tiles
+            .chunks_exact(tile_width as usize * tile_height as usize)
+            .enumerate()
+        {
+            let x = (id as u32 % columns) * tile_width;
+            let y = (id as u32 / columns) * tile_height;
+
+            for (row, src) in tile.chunks_exact(tile_width as usize).enumerate() {
+                let start = (y + row as u32) as usize * width as usize + x as usize;
+                pixels[start..start + tile_width as usize].copy_from_slice(src);
+            }
+        }
+
+        let mut opts = TilesetOptions::new(tile_width, tile_height);
+        opts.key_color = self.key_color;
+
+        Ok(Tileset {
+            data,
+            width,
+            height,
+            tile_counts: (columns, rows),
+            opts,
+            alpha_mask: None,
+            regions: None,
+            animations: BTreeMap::new(),
+            terrains: BTreeMap::new(),
+            names: BTreeMap::new(),
+            meta: BTreeMap::new(),
+            generation: 0,
+        })
+    }
+}
+
+/// Builds an atlas tileset by packing images of any size into a single image,
+/// see [`Tileset::with_regions`].
+///
+/// Tile ids match the order the images were pushed in.
+#[derive(Clone, Debug)]
+pub struct AtlasBuilder {
+    cell_size: (u32, u32),
+    max_width: Option<u32>,
+    key_color: Option<Color>,
+    anchor: TileAnchor,
+    images: Vec<(String, (u32, u32), Vec<Color>)>,
+}
+
+impl AtlasBuilder {
+    /// Create a new AtlasBuilder for maps with cells of size `cell_width` x `cell_height`.
+    #[inline]
+    pub const fn new(cell_width: u32, cell_height: u32) -> Self {
+        Self {
+            cell_size: (cell_width, cell_height),
+            max_width: None,
+            key_color: None,
+            anchor: TileAnchor::TopLeft,
+            images: Vec::new(),
+        }
+    }
+
+    /// Specify the maximum width of the packed image, unless an image is wider than it.
+    /// By default the packed image is roughly square.
+    #[inline]
+    pub fn with_max_width(mut self, max_width: u32) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    /// Specify key color.
+    #[inline]
+    pub fn with_key_color(mut self, key_color: Color) -> Self {
+        self.key_color = Some(key_color);
+        self
+    }
+
+    /// Specify how tiles of other sizes than the cells are placed in them.
+    #[inline]
+    pub fn with_anchor(mut self, anchor: TileAnchor) -> Self {
+        self.anchor = anchor;
+        self
+    }
+
+    /// Add a `width` x `height` image named `name`. `pixels` are the image's pixels, row by row.
+    ///
+    /// Returns the id of the new tile or `None` if the image is empty or the amount of pixels doesn't match its size.
+    pub fn push_image(
+        &mut self,
+        name: impl Into<String>,
+        width: u32,
+        height: u32,
+        pixels: &[Color],
+    ) -> Option<TileId> {
+        if width == 0 || height == 0 || pixels.len() != width as usize * height as usize {
+            return None;
+        }
+
+        self.images
+            .push((name.into(), (width, height), pixels.to_vec()));
+
+        Some(self.images.len() as TileId - 1)
+    }
+
+    /// Pack the images into a tileset, returning it along with the ids of the tiles by name.
+    /// The names are registered on the tileset as well, see [`Tileset::id_of`].
+    ///
+    /// Images are put onto shelves from the tallest to the shortest.
+    /// If several images have the same name, the last one is kept in the map.
+    /// Returns an error if the cell width or height is 0.
+    #[allow(clippy::type_complexity)]
+    pub fn build(self) -> Result<(Tileset<Vec<u8>>, BTreeMap<String, TileId>), TilesetError> {
+        if self.cell_size.0 == 0 || self.cell_size.1 == 0 {
+            return Err(TilesetError::ZeroTileSize);
+        }
+
+        let widest = self.images.iter().map(|(_, size, _)| size.0).max();
+        let area: u64 = self
+            .images
+            .iter()
+            .map(|(_, (width, height), _)| *width as u64 * *height as u64)
+            .sum();
+
+        let max_width = self
+            .max_width
+            .unwrap_or(isqrt(area) as u32)
+            .max(widest.unwrap_or(0));
+
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|&i| core::cmp::Reverse(self.images[i].1 .1));
+
+        let mut regions = vec![(0, 0, 0, 0); self.images.len()];
+        let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+        let mut width = 0;
+
+        for i in order {
+            let (image_width, image_height) = self.images[i].1;
+
+            if x + image_width > max_width {
+                y += shelf_height;
+                x = 0;
+                shelf_height = 0;
+            }
+
+            regions[i] = (x, y, image_width, image_height);
+
+            x += image_width;
+            width = width.max(x);
+            shelf_height = shelf_height.max(image_height);
+        }
+
+        let height = y + shelf_height;
+
+        let mut data = vec![0; width as usize * height as usize * size_of::<Color>()];
+        let pixels = data.as_pixels_mut();
+
+        for ((_, (image_width, _), image), &(x, y, _, _)) in self.images.iter().zip(&regions) {
+            for (row, src) in image.chunks_exact(*image_width as usize).enumerate() {
+                let start = ((y + row as u32) * width + x) as usize;
+                pixels[start..start + *image_width as usize].copy_from_slice(src);
+            }
+        }
+
+        let mut opts = TilesetOptions::new(self.cell_size.0, self.cell_size.1);
+        opts.key_color = self.key_color;
+        opts.anchor = self.anchor;
+
+        let mut tileset = Tileset {
+            data,
+            width,
+            height,
+            tile_counts: (0, 0),
+            opts,
+            alpha_mask: None,
+            regions: Some(regions),
+            animations: BTreeMap::new(),
+            terrains: BTreeMap::new(),
+            names: BTreeMap::new(),
+            meta: BTreeMap::new(),
+            generation: 0,
+        };
+
+        tileset.names = self
+            .images
+            .into_iter()
+            .enumerate()
+            .map(|(id, (name, _, _))| (name, id as TileId))
+            .collect();
+
+        let names = tileset.names.clone();
+
+        Ok((tileset, names))
+    }
+}
+
+/// Square root of `n` rounded down.
+fn isqrt(n: u64) -> u64 {
+    (0..32).rev().fold(0, |root, bit| {
+        let candidate = root | 1 << bit;
+
+        if candidate * candidate <= n {
+            candidate
+        } else {
+            root
+        }
+    })
+}
+
+/// Convert a color premultiplied by alpha to a straight alpha one.
+#[inline]
+fn unpremultiply(color: Color) -> Color {
+    match color.a {
+        0 => Color::new(0, 0, 0, 0),
+        255 => color,
+        a => {
+            let channel = |c: u8| ((c as u32 * 255 + a as u32 / 2) / a as u32).min(255) as u8;
+
+            Color::new(channel(color.r), channel(color.g), channel(color.b), a)
+        }
+    }
+}
+
+/// `n` tiles take up `n * tile_size + (n - 1) * spacing` pixels,
+/// so adding one spacing to the available size makes it divisible by `tile_size + spacing`.
+#[inline]
+const fn calc_tile_counts(width: u32, height: u32, opts: &TilesetOptions) -> (u32, u32) {
+    (
+        (width.saturating_sub(opts.offset.0) + opts.spacing.0)
+            / (opts.tile_size.0 + opts.spacing.0),
+        (height.saturating_sub(opts.offset.1) + opts.spacing.1)
+            / (opts.tile_size.1 + opts.spacing.1),
+    )
+}
+
+/// Position in the tile's image of the pixel drawn at (x, y) of map tile `tile`, after its flips and rotation.
+#[inline]
+fn source_pos<U>(tile: &Tile<U>, pos: (u32, u32), tile_size: (u32, u32)) -> (u32, u32) {
+    let rotated_size = tile.rotation.apply_size(tile_size);
+
+    flip_pos(
+        tile.opts,
+        tile.rotation.inverse().apply(pos, rotated_size),
+        tile_size,
+    )
+}
+
+/// Position in the tile's image of the pixel drawn at (x, y) of a tile blitted with `opts`.
+#[inline]
+fn flip_pos(
+    opts: BlitOptions,
+    (x, y): (u32, u32),
+    (tile_width, tile_height): (u32, u32),
+) -> (u32, u32) {
+    match opts {
+        BlitOptions::None => (x, y),
+        BlitOptions::FlipHorizontal => (tile_width - 1 - x, y),
+        BlitOptions::FlipVertical => (x, tile_height - 1 - y),
+        BlitOptions::FlipBoth => (tile_width - 1 - x, tile_height - 1 - y),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{tileset_from, Surface};
+
+    /// 2x2 tile whose pixels are all different, with `n` in the red channel.
+    fn tile(n: u8) -> [Color; 4] {
+        [0, 1, 2, 3].map(|i| Color::new(n, i, 0, 255))
+    }
+
+    /// Tileset of `count` 2x2 tiles in a row, made of [`tile`]s.
+    fn strip(count: u32) -> Tileset<Vec<u8>> {
+        let pixels: Vec<_> = (0..2)
+            .flat_map(|y| {
+                (0..count * 2).map(move |x| tile((x / 2) as u8)[(y * 2 + x % 2) as usize])
+            })
+            .collect();
+
+        tileset_from(&pixels, count * 2, TilesetOptions::new(2, 2))
+    }
+
+    /// Tileset of `width` by `height` transparent pixels.
+    fn blank(width: u32, height: u32, opts: TilesetOptions) -> Tileset<Vec<u8>> {
+        tileset_from(
+            &vec![Color::default(); (width * height) as usize],
+            width,
+            opts,
+        )
+    }
+
+    #[test]
+    fn builder_packs_in_push_order() {
+        let mut builder = TilesetBuilder::new(2, 2).with_columns(2);
+        for n in 0..3 {
+            assert_eq!(builder.push_tile(&tile(n)), Some(n as TileId));
+        }
+        assert_eq!(builder.push_tile(&[Color::default()]), None);
+
+        let tileset = builder.build().unwrap();
+        assert_eq!((tileset.width(), tileset.height()), (4, 4));
+        assert_eq!(tileset.tile_count(), 4);
+
+        for (id, pos) in [(0, (0, 0)), (1, (2, 0)), (2, (0, 2))] {
+            assert_eq!(tileset.get_tile_pos(id), Some(pos));
+
+            let pixels =
+                [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(x, y)| *tileset.get(pos.0 + x, pos.1 + y));
+            assert_eq!(pixels, tile(id as u8));
+        }
+
+        assert_eq!(TilesetBuilder::new(2, 2).build().unwrap().tile_count(), 0);
+        assert_eq!(
+            TilesetBuilder::new(0, 2).build().unwrap_err(),
+            TilesetError::ZeroTileSize
+        );
+        assert_eq!(
+            TilesetBuilder::new(u32::MAX, 1)
+                .with_columns(2)
+                .build()
+                .unwrap_err(),
+            TilesetError::ImageTooLarge
+        );
+    }
+
+    #[test]
+    fn get_tile_pos_reaches_atlas_edge() {
+        let mut builder = TilesetBuilder::new(2, 2).with_columns(3);
+        for n in 0..6 {
+            builder.push_tile(&tile(n));
+        }
+        let tileset = builder.build().unwrap();
+
+        assert_eq!((tileset.width(), tileset.height()), (6, 4));
+        assert_eq!(tileset.tile_count(), 6);
+        assert_eq!(tileset.get_tile_pos(2), Some((4, 0)));
+        assert_eq!(tileset.get_tile_pos(3), Some((0, 2)));
+        assert_eq!(tileset.get_tile_pos(5), Some((4, 2)));
+        assert_eq!(tileset.get_tile_pos(6), None);
+    }
+
+    #[test]
+    fn tile_grid_dimensions() {
+        let tileset = blank(8, 6, TilesetOptions::new(2, 2));
+
+        assert_eq!((tileset.tile_columns(), tileset.tile_rows()), (4, 3));
+        assert_eq!(tileset.tile_xy(0), Some((0, 0)));
+        assert_eq!(tileset.tile_xy(5), Some((1, 1)));
+        assert_eq!(tileset.tile_xy(11), Some((3, 2)));
+        assert_eq!(tileset.tile_xy(12), None);
+    }
+
+    #[test]
+    fn spacing_only_between_tiles() {
+        // offset 1, then 3 tiles 4 wide with 2 pixels between them: 1 + 4 + 2 + 4 + 2 + 4 = 17
+        let opts = TilesetOptions::new(4, 4)
+            .with_offset(1, 0)
+            .with_margin(2, 0);
+        let tileset = blank(17, 4, opts.clone());
+        assert_eq!(tileset.tile_columns(), 3);
+        assert_eq!(tileset.get_tile_pos(2), Some((13, 0)));
+
+        let tileset = blank(16, 4, opts);
+        assert_eq!(tileset.tile_columns(), 2);
+    }
+
+    #[test]
+    fn render_tile_colored() {
+        let tileset = strip(3);
+        let mut surface = Surface::new(14, 14);
+
+        let color = Color::new(255, 255, 255, 128);
+        tileset.render_tile_colored(&mut surface, 2, 10, 10, color, BlitOptions::None);
+
+        assert_eq!(*surface.get(10, 10), Color::new(2, 0, 0, 128));
+        assert_eq!(*surface.get(11, 11), Color::new(2, 3, 0, 128));
+        assert_eq!(*surface.get(12, 12), Color::default());
+        assert_eq!(*surface.get(9, 9), Color::default());
+
+        tileset.render_tile(&mut surface, 1, 0, 0, BlitOptions::FlipHorizontal);
+        assert_eq!(*surface.get(0, 0), tile(1)[1]);
+        assert_eq!(*surface.get(1, 1), tile(1)[2]);
+    }
+
+    #[test]
+    fn tile_is_opaque() {
+        let key = Color::new(255, 0, 255, 255);
+        let color = Color::new(10, 0, 0, 255);
+
+        let mut builder = TilesetBuilder::new(2, 2)
+            .with_columns(2)
+            .with_key_color(key);
+        builder.push_tile(&[color; 4]);
+        builder.push_tile(&[color, color, key, color]);
+        let tileset = builder.build().unwrap();
+
+        assert_eq!(tileset.tile_is_opaque(0), Some(true));
+        assert_eq!(tileset.tile_is_opaque(1), Some(false));
+        assert_eq!(tileset.tile_is_opaque(2), None);
+    }
+
+    #[test]
+    fn generation_bumps_on_edit() {
+        let mut tileset = strip(3);
+        let generation = tileset.generation();
+
+        let _ = tileset.get(0, 0);
+        assert_eq!(tileset.generation(), generation);
+
+        tileset.edit_pixels(|pixels| *pixels.get_mut(1, 1) = Color::new(1, 2, 3, 4));
+        assert_eq!(tileset.generation(), generation + 1);
+        assert_eq!(*tileset.get(1, 1), Color::new(1, 2, 3, 4));
+    }
+
+    #[test]
+    fn check_layout() {
+        let tileset = blank(30, 16, TilesetOptions::new(16, 16));
+        assert_eq!(
+            tileset.check_layout(),
+            LayoutReport {
+                exact: false,
+                leftover: (14, 0)
+            }
+        );
+
+        let opts = TilesetOptions::new(4, 4)
+            .with_offset(1, 0)
+            .with_margin(2, 0);
+        let tileset = blank(17, 4, opts);
+        assert!(tileset.check_layout().exact);
+    }
+
+    #[test]
+    fn render_from_borrowed_slice() {
+        let data: Vec<u8> = (0..4 * 2).flat_map(|_| [10, 0, 0, 255]).collect();
+        assert!(Tileset::from_slice(&data[..8], 4, 2, TilesetOptions::new(2, 2)).is_err());
+
+        let tileset = Tileset::from_slice(&data, 4, 2, TilesetOptions::new(2, 2)).unwrap();
+        let mut map: crate::Tilemap<&[u8]> = crate::Tilemap::new(2, 1, tileset);
+        map.set_tile(1, 0, crate::Tile::new(1));
+
+        let mut surface = Surface::new(4, 2);
+        map.render(&mut surface, 0, 0);
+        assert_eq!(*surface.get(3, 1), Color::new(10, 0, 0, 255));
+    }
+
+    #[test]
+    fn subset_drops_spacing_and_keeps_mask() {
+        // 4x4 1x1 tiles with 1 pixel of spacing, tile `n` has `n` in the red channel
+        let mut pixels = vec![Color::new(200, 0, 0, 255); 7 * 7];
+        for n in 0..16 {
+            pixels[(n / 4 * 14 + n % 4 * 2) as usize] = Color::new(n as u8, 1, 2, 255);
+        }
+
+        let mut opts = TilesetOptions::new(1, 1);
+        opts.spacing = (1, 1);
+
+        let tileset = tileset_from(&pixels, 7, opts)
+            .with_alpha_mask((0..49).collect())
+            .unwrap();
+        assert_eq!(tileset.tile_count(), 16);
+
+        let subset = tileset.subset(5, 4).unwrap();
+        assert_eq!(subset.tile_count(), 4);
+        assert_eq!(subset.options().spacing, (0, 0));
+
+        for id in 0..4 {
+            let (x, y) = subset.get_tile_pos(id).unwrap();
+            let (ox, oy) = tileset.get_tile_pos(5 + id).unwrap();
+
+            assert_eq!(subset.get(x, y).r, 5 + id as u8);
+            assert_eq!(
+                subset.alpha_mask().unwrap()[(y * subset.width() + x) as usize],
+                (oy * 7 + ox) as u8
+            );
+        }
+
+        assert!(tileset.subset(12, 4).is_some());
+        assert!(tileset.subset(13, 4).is_none());
+        assert!(tileset.subset(0, 0).is_none());
+    }
+
+    #[test]
+    fn tile_pixels_mut_edits_in_place() {
+        let black = Color::new(0, 0, 0, 255);
+        let mut opts = TilesetOptions::new(2, 2);
+        opts.offset = (1, 1);
+
+        let mut tileset = tileset_from(&[black; 9 * 4], 9, opts);
+        let generation = tileset.generation();
+        assert!(tileset.tile_pixels_mut(99).is_none());
+
+        {
+            let mut view = tileset.tile_pixels_mut(1).unwrap();
+            assert_eq!((view.width(), view.height()), (2, 2));
+
+            view.fill(Color::new(9, 9, 9, 255));
+            *view.get_mut(1, 1) = Color::new(1, 2, 3, 4);
+        }
+
+        assert!(tileset.generation() > generation);
+
+        let gray = Color::new(9, 9, 9, 255);
+        assert_eq!(
+            tileset.tile_pixels(1),
+            Some(vec![gray, gray, gray, Color::new(1, 2, 3, 4)])
+        );
+        assert_eq!(tileset.tile_pixels(0), Some(vec![black; 4]));
+
+        let (x, y) = tileset.get_tile_pos(1).unwrap();
+        assert_eq!(*tileset.get(x, y), gray);
+    }
+
+    #[test]
+    fn wrap_ids() {
+        let pixels = vec![Color::default(); 6 * 2];
+        let opts = TilesetOptions::new(2, 2);
+
+        let plain = tileset_from(&pixels, 6, opts.clone());
+        assert_eq!(plain.get_tile_pos(3), None);
+        assert!(!plain.contains(3));
+
+        let wrapping = tileset_from(&pixels, 6, opts.with_wrap_ids(true));
+        assert_eq!(wrapping.get_tile_pos(3), wrapping.get_tile_pos(0));
+        assert_eq!(wrapping.get_tile_pos(5), Some((4, 0)));
+        assert_eq!(wrapping.tile_xy(5), Some((2, 0)));
+        assert!(wrapping.contains(3));
+        assert!(!wrapping.contains(EMPTY_TILE));
+
+        let mut map: crate::Tilemap<_> = crate::Tilemap::new(2, 1, wrapping);
+        map.set_tile(0, 0, crate::Tile::new(7));
+        map.set_tile(1, 0, crate::Tile::empty());
+        assert_eq!(map.validate(), Ok(()));
+    }
+
+    #[test]
+    fn from_pixels() {
+        let pixels: Vec<_> = (0..4).map(|x| Color::new(10 + x * 10, 0, 0, 255)).collect();
+        assert!(Tileset::from_pixels(&pixels[..3], 2, 2, TilesetOptions::new(1, 1)).is_err());
+
+        let tileset = Tileset::from_pixels(&pixels[..], 4, 1, TilesetOptions::new(1, 1)).unwrap();
+        assert_eq!(tileset.tile_count(), 4);
+
+        let mut map = crate::Tilemap::new(4, 1, tileset);
+        for id in 0..4 {
+            map.set_tile(id, 0, Tile::new(id));
+        }
+
+        let mut surface = Surface::new(4, 1);
+        map.render(&mut surface, 0, 0);
+        assert_eq!(surface.pixels, pixels);
+
+        let mut owned = Tileset::from_pixels(pixels, 4, 1, TilesetOptions::new(1, 1)).unwrap();
+        *owned.get_mut(0, 0) = Color::new(1, 2, 3, 4);
+        assert_eq!(*owned.get(0, 0), Color::new(1, 2, 3, 4));
+    }
+
+    #[test]
+    fn construction_errors() {
+        use alloc::string::ToString;
+
+        let new = |len: usize, opts| Tileset::new(vec![0; len], 4, 2, opts);
+
+        assert_eq!(
+            new(31, TilesetOptions::new(2, 2)).err(),
+            Some(TilesetError::InvalidDataLength {
+                expected: 32,
+                actual: 31
+            })
+        );
+        assert_eq!(
+            new(32, TilesetOptions::new(0, 2)).err(),
+            Some(TilesetError::ZeroTileSize)
+        );
+        assert_eq!(
+            new(32, TilesetOptions::new(2, 3)).err(),
+            Some(TilesetError::TileTooLarge)
+        );
+        assert_eq!(
+            new(32, TilesetOptions::new(2, 2).with_offset(3, 0)).err(),
+            Some(TilesetError::OffsetOutOfBounds)
+        );
+        assert!(new(32, TilesetOptions::new(2, 2).with_offset(2, 0)).is_ok());
+
+        assert_eq!(
+            TilesetError::InvalidDataLength {
+                expected: 32,
+                actual: 31
+            }
+            .to_string(),
+            "data is 31 bytes long, expected 32"
+        );
+        assert_eq!(TilesetError::ZeroTileSize.to_string(), "tile size is zero");
+    }
+
+    #[test]
+    fn tile_view() {
+        let tileset = strip(3);
+
+        let view = tileset.tile_view(2).unwrap();
+        assert_eq!((view.width(), view.height()), (2, 2));
+        assert_eq!(*view.get(1, 1), tile(2)[3]);
+        assert!(tileset.tile_view(7).is_none());
+
+        let mut surface = Surface::new(2, 2);
+        simple_blit::blit_with(
+            &mut surface,
+            (0, 0),
+            &view,
+            (0, 0),
+            (2, 2),
+            BlitOptions::None,
+            |dest, src, _| *dest = *src,
+        );
+        assert_eq!(surface.pixels, tile(2));
+    }
+
+    #[test]
+    fn iter_tiles() {
+        let opts = TilesetOptions::new(4, 4)
+            .with_offset(1, 0)
+            .with_margin(2, 0);
+        let tileset = blank(17, 4, opts);
+
+        let tiles: Vec<_> = tileset
+            .iter_tiles()
+            .map(|(id, image)| (id, image.position(), image.width()))
+            .collect();
+        assert_eq!(tiles, [(0, (1, 0), 4), (1, (7, 0), 4), (2, (13, 0), 4)]);
+
+        let tileset = strip(3);
+        for (id, image) in tileset.iter_tiles() {
+            assert_eq!(*image.get(0, 0), tile(id as u8)[0]);
+        }
+        assert_eq!(tileset.iter_tiles().count(), 3);
+    }
+
+    #[test]
+    fn atlas_regions() {
+        // columns of the 4x2 image are colored 10, 20, 30 and 40
+        let pixels: Vec<_> = (0..8)
+            .map(|i| Color::new(10 + (i % 4) * 10, 0, 0, 255))
+            .collect();
+        let tileset = Tileset::from_pixels(pixels, 4, 2, TilesetOptions::new(1, 1)).unwrap();
+
+        assert_eq!(
+            tileset.clone().with_regions(vec![(3, 0, 2, 1)]).err(),
+            Some(TilesetError::InvalidRegion(0))
+        );
+        assert_eq!(
+            tileset
+                .clone()
+                .with_regions(vec![(0, 0, 1, 1), (0, 0, 0, 1)])
+                .err(),
+            Some(TilesetError::InvalidRegion(1))
+        );
+
+        let tileset = tileset
+            .with_regions(vec![(0, 0, 2, 1), (2, 0, 1, 2), (3, 1, 1, 1)])
+            .unwrap();
+        assert_eq!(tileset.tile_count(), 3);
+        assert_eq!(tileset.tile_rect(1), Some((2, 0, 1, 2)));
+        assert_eq!(tileset.get_tile_pos(2), Some((3, 1)));
+        assert_eq!(tileset.tile_rect(3), None);
+        assert_eq!(tileset.tile_xy(0), None);
+        assert_eq!(tileset.tile_view(0).unwrap().width(), 2);
+
+        let mut map = crate::Tilemap::new(4, 3, tileset);
+        map.fill_rect(0, 0, 4, 3, Tile::empty());
+        map.set_tile(0, 0, Tile::new(0));
+        map.set_tile(3, 0, Tile::new(1));
+        map.set_tile(1, 2, Tile::new(2));
+
+        let mut surface = Surface::new(4, 3);
+        map.render(&mut surface, 0, 0);
+        let reds: Vec<_> = surface.pixels.iter().map(|pixel| pixel.r).collect();
+        assert_eq!(reds, [10, 20, 0, 30, 0, 0, 0, 30, 0, 40, 0, 0]);
+        assert_eq!(map.tile_screen_rect(3, 0, 0, 0), Some((3, 0, 1, 2)));
+
+        let mut constant = Surface::new(4, 3);
+        map.render_const::<1, 1>(&mut constant, 0, 0);
+        assert_eq!(constant.pixels, surface.pixels);
+
+        let mut scaled = Surface::new(4, 3);
+        map.render_scaled(&mut scaled, 0, 0, 1);
+        assert_eq!(scaled.pixels, surface.pixels);
+
+        // the tall tile still shows when its cell is above the surface
+        let mut surface = Surface::new(4, 1);
+        map.render(&mut surface, 0, -1);
+        assert_eq!(surface.get(3, 0).r, 30);
+    }
+
+    #[test]
+    fn atlas_builder() {
+        let image = |width: u32, height: u32, red: u8| {
+            vec![Color::new(red, 0, 0, 255); (width * height) as usize]
+        };
+
+        let mut builder = AtlasBuilder::new(2, 2).with_anchor(TileAnchor::BottomLeft);
+        assert_eq!(builder.push_image("bad", 2, 2, &image(1, 1, 1)), None);
+        assert_eq!(builder.push_image("grass", 2, 2, &image(2, 2, 10)), Some(0));
+        assert_eq!(builder.push_image("tree", 2, 4, &image(2, 4, 20)), Some(1));
+        assert_eq!(builder.push_image("rock", 1, 1, &image(1, 1, 30)), Some(2));
+
+        let (tileset, names) = builder.build().unwrap();
+        assert_eq!(names.len(), 3);
+        assert_eq!(names["tree"], 1);
+        assert_eq!(tileset.id_of("tree"), Some(1));
+        assert_eq!(tileset.tile_count(), 3);
+
+        let rects: Vec<_> = (0..3).map(|id| tileset.tile_rect(id).unwrap()).collect();
+        for (id, &(_, _, width, height)) in rects.iter().enumerate() {
+            let view = tileset.tile_view(id as TileId).unwrap();
+            for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
+                assert_eq!(view.get(x, y).r, (id as u8 + 1) * 10);
+            }
+        }
+
+        // no two images overlap
+        for (i, a) in rects.iter().enumerate() {
+            for b in &rects[..i] {
+                assert!(
+                    a.0 + a.2 <= b.0 || b.0 + b.2 <= a.0 || a.1 + a.3 <= b.1 || b.1 + b.3 <= a.1
+                );
+            }
+        }
+
+        // the tall tree is anchored to the bottom of its cell
+        let mut map = crate::Tilemap::new(1, 2, tileset);
+        map.set_tile(0, 0, Tile::new(2));
+        map.set_tile(0, 1, Tile::new(1));
+
+        let mut surface = Surface::new(2, 4);
+        map.render(&mut surface, 0, 0);
+        assert_eq!(surface.get(0, 0).r, 20);
+        assert_eq!(surface.get(1, 3).r, 20);
+
+        let (empty, names) = AtlasBuilder::new(2, 2).build().unwrap();
+        assert_eq!(empty.tile_count(), 0);
+        assert!(names.is_empty());
+
+        let mut builder = AtlasBuilder::new(1, 1).with_max_width(2);
+        for n in 0..4 {
+            builder.push_image(alloc::format!("tile {n}"), 1, 1, &image(1, 1, 5));
+        }
+        let (tileset, _) = builder.build().unwrap();
+        assert_eq!((tileset.width(), tileset.height()), (2, 2));
+
+        assert_eq!(
+            AtlasBuilder::new(0, 2).build().err(),
+            Some(TilesetError::ZeroTileSize)
+        );
+    }
+
+    #[test]
+    fn tile_names() {
+        let mut tileset = strip(3);
+        tileset.set_tile_name("grass", 1);
+        tileset.set_tile_name(String::from("water"), 2);
+        tileset.set_tile_name("lawn", 1);
+        assert_eq!(tileset.id_of("grass"), Some(1));
+        assert_eq!(tileset.id_of("sand"), None);
+
+        tileset.set_tile_name("grass", 0);
+        assert_eq!(tileset.id_of("grass"), Some(0));
+        assert_eq!(
+            tileset.tile_names().collect::<Vec<_>>(),
+            [("grass", 0), ("lawn", 1), ("water", 2)]
+        );
+
+        assert_eq!(tileset.remove_tile_name("lawn"), Some(1));
+        assert_eq!(tileset.remove_tile_name("lawn"), None);
+        assert_eq!(tileset.id_of("lawn"), None);
+
+        // names survive changing the container
+        let tileset: Tileset<alloc::rc::Rc<[u8]>> = tileset.into_container();
+        assert_eq!(tileset.id_of("water"), Some(2));
+    }
+
+    #[test]
+    fn tile_meta() {
+        let mut tileset = strip(3).with_meta(BTreeMap::new());
+        assert_eq!(tileset.set_tile_meta(1, (true, 5)), None);
+        assert_eq!(tileset.set_tile_meta(1, (true, 6)), Some((true, 5)));
+        assert_eq!(tileset.tile_meta(1), Some(&(true, 6)));
+        assert_eq!(tileset.tile_meta(0), None);
+
+        tileset.tile_meta_mut(1).unwrap().1 = 7;
+        assert_eq!(tileset.tile_meta(1), Some(&(true, 7)));
+        assert_eq!(tileset.remove_tile_meta(1), Some((true, 7)));
+        assert_eq!(tileset.tile_meta(1), None);
+
+        let tileset = strip(2).with_meta(BTreeMap::from([(0, "grass")]));
+        assert_eq!(tileset.tile_meta(0), Some(&"grass"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_without_default_meta() {
+        /// Metadata that can't be defaulted.
+        #[derive(serde::Deserialize)]
+        struct Meta(#[allow(dead_code)] bool);
+
+        fn assert_deserialize<T: serde::de::DeserializeOwned>() {}
+
+        assert_deserialize::<Tileset<Vec<u8>, Meta>>();
+        assert_deserialize::<crate::Tilemap<Vec<u8>, (), Meta>>();
+        assert_deserialize::<crate::SerializedTilemap<Vec<u8>, (), Meta>>();
+    }
+
+    #[test]
+    fn isqrt_rounds_down() {
+        for (n, root) in [(0, 0), (1, 1), (3, 1), (4, 2), (99, 9), (100, 10)] {
+            assert_eq!(isqrt(n), root);
+        }
+        assert_eq!(isqrt(u64::MAX), u32::MAX as u64);
+    }
 }